@@ -1,3 +1,5 @@
 fn main() {
+    // Only the GUI needs the Tauri build step (nes-headless is built with --no-default-features)
+    #[cfg(feature = "gui")]
     tauri_build::build()
 }
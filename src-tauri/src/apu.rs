@@ -1,40 +1,833 @@
-struct Apu {
-    // APU関連のフィールド（例: チャネル、タイマー、ボリュームなど）
+// 2A03 APU: two pulse channels, triangle, noise, DMC and the frame counter.
+// Clocked once per CPU cycle by the bus; output is downsampled to SAMPLE_RATE.
+use crate::savestate::{SaveState, StateReader, StateWriter};
+
+pub const SAMPLE_RATE: u32 = 44100;
+const NTSC_CPU_CLOCK: f64 = 1_789_773.0;
+const PAL_CPU_CLOCK: f64 = 1_662_607.0;
+const DENDY_CPU_CLOCK: f64 = 1_773_448.0;
+// Samples kept when nobody drains them (about one second); the oldest are dropped first
+const MAX_BUFFERED_SAMPLES: usize = SAMPLE_RATE as usize;
+
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+const TRIANGLE_SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+// Rate tables and frame counter step positions (in CPU cycles)
+#[derive(Debug, Clone, Copy)]
+pub struct ApuTiming {
+    pub cpu_clock: f64,
+    pub noise_periods: [u16; 16],
+    pub dmc_rates: [u16; 16],
+    pub frame_steps_4: [u32; 4],
+    pub frame_steps_5: [u32; 5],
+}
+
+pub const NTSC_APU_TIMING: ApuTiming = ApuTiming {
+    cpu_clock: NTSC_CPU_CLOCK,
+    noise_periods: [4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068],
+    dmc_rates: [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54],
+    frame_steps_4: [7457, 14913, 22371, 29829],
+    frame_steps_5: [7457, 14913, 22371, 29829, 37281],
+};
+
+pub const PAL_APU_TIMING: ApuTiming = ApuTiming {
+    cpu_clock: PAL_CPU_CLOCK,
+    noise_periods: [4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778],
+    dmc_rates: [398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50],
+    frame_steps_4: [8313, 16627, 24939, 33253],
+    frame_steps_5: [8313, 16627, 24939, 33253, 41565],
+};
+
+// The Dendy's UA6527P keeps the NTSC rate tables but runs from a slower CPU clock
+pub const DENDY_APU_TIMING: ApuTiming = ApuTiming {
+    cpu_clock: DENDY_CPU_CLOCK,
+    ..NTSC_APU_TIMING
+};
+
+#[derive(Default)]
+struct Envelope {
+    start: bool,
+    loop_flag: bool, // Also the length counter halt flag
+    constant_volume: bool,
+    volume: u8,
+    divider: u8,
+    decay: u8,
+}
+
+impl Envelope {
+    fn write(&mut self, data: u8) {
+        self.loop_flag = data & 0x20 != 0;
+        self.constant_volume = data & 0x10 != 0;
+        self.volume = data & 0x0F;
+    }
+
+    fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.loop_flag {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.constant_volume { self.volume } else { self.decay }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.start);
+        w.write_bool(self.loop_flag);
+        w.write_bool(self.constant_volume);
+        w.write_u8(self.volume);
+        w.write_u8(self.divider);
+        w.write_u8(self.decay);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.start = r.read_bool()?;
+        self.loop_flag = r.read_bool()?;
+        self.constant_volume = r.read_bool()?;
+        self.volume = r.read_u8()? & 0x0F;
+        self.divider = r.read_u8()?;
+        self.decay = r.read_u8()? & 0x0F;
+        Ok(())
+    }
+}
+
+struct Pulse {
+    ones_complement: bool, // Pulse 1 negates with one's complement
+    enabled: bool,
+    duty: u8,
+    duty_pos: u8,
+    envelope: Envelope,
+    length: u8,
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_reload: bool,
+    sweep_divider: u8,
+    timer_period: u16,
+    timer: u16,
+}
+
+impl Pulse {
+    fn new(ones_complement: bool) -> Self {
+        Self {
+            ones_complement,
+            enabled: false,
+            duty: 0,
+            duty_pos: 0,
+            envelope: Envelope::default(),
+            length: 0,
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_reload: false,
+            sweep_divider: 0,
+            timer_period: 0,
+            timer: 0,
+        }
+    }
+
+    fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            0 => {
+                self.duty = data >> 6;
+                self.envelope.write(data);
+            }
+            1 => {
+                self.sweep_enabled = data & 0x80 != 0;
+                self.sweep_period = (data >> 4) & 0x07;
+                self.sweep_negate = data & 0x08 != 0;
+                self.sweep_shift = data & 0x07;
+                self.sweep_reload = true;
+            }
+            2 => self.timer_period = (self.timer_period & 0x0700) | data as u16,
+            _ => {
+                self.timer_period = (self.timer_period & 0x00FF) | (((data & 0x07) as u16) << 8);
+                if self.enabled {
+                    self.length = LENGTH_TABLE[(data >> 3) as usize];
+                }
+                self.duty_pos = 0;
+                self.envelope.start = true;
+            }
+        }
+    }
+
+    // Clocked every APU cycle (every other CPU cycle)
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.duty_pos = (self.duty_pos + 1) & 0x07;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift;
+        if self.sweep_negate {
+            let change = if self.ones_complement { change + 1 } else { change };
+            self.timer_period.saturating_sub(change)
+        } else {
+            self.timer_period + change
+        }
+    }
+
+    fn is_muted(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target() > 0x7FF
+    }
+
+    fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.is_muted() {
+            self.timer_period = self.sweep_target();
+        }
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    fn clock_length(&mut self) {
+        if !self.envelope.loop_flag && self.length > 0 {
+            self.length -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled || self.length == 0 || self.is_muted() || DUTY_TABLE[self.duty as usize][self.duty_pos as usize] == 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.enabled);
+        w.write_u8(self.duty);
+        w.write_u8(self.duty_pos);
+        self.envelope.save_state(w);
+        w.write_u8(self.length);
+        w.write_bool(self.sweep_enabled);
+        w.write_u8(self.sweep_period);
+        w.write_bool(self.sweep_negate);
+        w.write_u8(self.sweep_shift);
+        w.write_bool(self.sweep_reload);
+        w.write_u8(self.sweep_divider);
+        w.write_u16(self.timer_period);
+        w.write_u16(self.timer);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.enabled = r.read_bool()?;
+        self.duty = r.read_u8()? & 0x03;
+        self.duty_pos = r.read_u8()? & 0x07;
+        self.envelope.load_state(r)?;
+        self.length = r.read_u8()?;
+        self.sweep_enabled = r.read_bool()?;
+        self.sweep_period = r.read_u8()?;
+        self.sweep_negate = r.read_bool()?;
+        self.sweep_shift = r.read_u8()? & 0x07;
+        self.sweep_reload = r.read_bool()?;
+        self.sweep_divider = r.read_u8()?;
+        self.timer_period = r.read_u16()? & 0x07FF;
+        self.timer = r.read_u16()?;
+        Ok(())
+    }
 }
 
-struct AudioData {
-    samples: Vec<f32>, // オーディオサンプルの配列
-    sample_rate: u32,  // サンプリングレート（Hz）
+#[derive(Default)]
+struct Triangle {
+    enabled: bool,
+    control: bool, // Also the length counter halt flag
+    linear_reload_value: u8,
+    linear_counter: u8,
+    linear_reload: bool,
+    length: u8,
+    timer_period: u16,
+    timer: u16,
+    sequence_pos: u8,
 }
 
-impl AudioData {
-    pub fn new(samples: Vec<f32>, sample_rate: u32) -> Self {
-        Self { samples, sample_rate }
+impl Triangle {
+    fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            0 => {
+                self.control = data & 0x80 != 0;
+                self.linear_reload_value = data & 0x7F;
+            }
+            2 => self.timer_period = (self.timer_period & 0x0700) | data as u16,
+            3 => {
+                self.timer_period = (self.timer_period & 0x00FF) | (((data & 0x07) as u16) << 8);
+                if self.enabled {
+                    self.length = LENGTH_TABLE[(data >> 3) as usize];
+                }
+                self.linear_reload = true;
+            }
+            _ => {}
+        }
+    }
+
+    // Clocked every CPU cycle
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.length > 0 && self.linear_counter > 0 {
+                self.sequence_pos = (self.sequence_pos + 1) & 0x1F;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_linear(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    fn clock_length(&mut self) {
+        if !self.control && self.length > 0 {
+            self.length -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        // Ultrasonic periods are inaudible; hold the sequencer output
+        TRIANGLE_SEQUENCE[self.sequence_pos as usize]
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.enabled);
+        w.write_bool(self.control);
+        w.write_u8(self.linear_reload_value);
+        w.write_u8(self.linear_counter);
+        w.write_bool(self.linear_reload);
+        w.write_u8(self.length);
+        w.write_u16(self.timer_period);
+        w.write_u16(self.timer);
+        w.write_u8(self.sequence_pos);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.enabled = r.read_bool()?;
+        self.control = r.read_bool()?;
+        self.linear_reload_value = r.read_u8()?;
+        self.linear_counter = r.read_u8()?;
+        self.linear_reload = r.read_bool()?;
+        self.length = r.read_u8()?;
+        self.timer_period = r.read_u16()? & 0x07FF;
+        self.timer = r.read_u16()?;
+        self.sequence_pos = r.read_u8()? & 0x1F;
+        Ok(())
     }
+}
 
-    // 必要に応じて他のメソッドを追加...
+struct Noise {
+    enabled: bool,
+    envelope: Envelope,
+    length: u8,
+    mode: bool,
+    period_index: u8,
+    timer: u16,
+    shift_register: u16,
 }
+
+impl Noise {
+    fn new() -> Self {
+        Self {
+            enabled: false,
+            envelope: Envelope::default(),
+            length: 0,
+            mode: false,
+            period_index: 0,
+            timer: 0,
+            shift_register: 1,
+        }
+    }
+
+    fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            0 => self.envelope.write(data),
+            2 => {
+                self.mode = data & 0x80 != 0;
+                self.period_index = data & 0x0F;
+            }
+            3 => {
+                if self.enabled {
+                    self.length = LENGTH_TABLE[(data >> 3) as usize];
+                }
+                self.envelope.start = true;
+            }
+            _ => {}
+        }
+    }
+
+    // Clocked every CPU cycle; the period table is in CPU cycles
+    fn clock_timer(&mut self, timing: &ApuTiming) {
+        if self.timer == 0 {
+            self.timer = timing.noise_periods[self.period_index as usize] - 1;
+            let tap = if self.mode { 6 } else { 1 };
+            let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 0x01;
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_length(&mut self) {
+        if !self.envelope.loop_flag && self.length > 0 {
+            self.length -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled || self.length == 0 || self.shift_register & 0x01 != 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.enabled);
+        self.envelope.save_state(w);
+        w.write_u8(self.length);
+        w.write_bool(self.mode);
+        w.write_u8(self.period_index);
+        w.write_u16(self.timer);
+        w.write_u16(self.shift_register);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.enabled = r.read_bool()?;
+        self.envelope.load_state(r)?;
+        self.length = r.read_u8()?;
+        self.mode = r.read_bool()?;
+        self.period_index = r.read_u8()? & 0x0F;
+        self.timer = r.read_u16()?;
+        self.shift_register = r.read_u16()?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Dmc {
+    irq_enabled: bool,
+    irq_flag: bool,
+    loop_flag: bool,
+    rate_index: u8,
+    timer: u16,
+    output_level: u8,
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+}
+
+impl Dmc {
+    fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            0 => {
+                self.irq_enabled = data & 0x80 != 0;
+                self.loop_flag = data & 0x40 != 0;
+                self.rate_index = data & 0x0F;
+                if !self.irq_enabled {
+                    self.irq_flag = false;
+                }
+            }
+            1 => self.output_level = data & 0x7F,
+            2 => self.sample_address = 0xC000 | ((data as u16) << 6),
+            _ => self.sample_length = ((data as u16) << 4) | 1,
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    // Address the memory reader wants to fetch, if the buffer is empty
+    fn pending_fetch(&self) -> Option<u16> {
+        (self.sample_buffer.is_none() && self.bytes_remaining > 0).then_some(self.current_address)
+    }
+
+    fn fill_buffer(&mut self, data: u8) {
+        self.sample_buffer = Some(data);
+        self.current_address = if self.current_address == 0xFFFF { 0x8000 } else { self.current_address + 1 };
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.loop_flag {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq_flag = true;
+            }
+        }
+    }
+
+    fn clock_timer(&mut self, timing: &ApuTiming) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = timing.dmc_rates[self.rate_index as usize] - 1;
+
+        if !self.silence {
+            if self.shift_register & 0x01 != 0 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        if self.bits_remaining > 0 {
+            self.bits_remaining -= 1;
+        }
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(sample) => {
+                    self.silence = false;
+                    self.shift_register = sample;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.irq_enabled);
+        w.write_bool(self.irq_flag);
+        w.write_bool(self.loop_flag);
+        w.write_u8(self.rate_index);
+        w.write_u16(self.timer);
+        w.write_u8(self.output_level);
+        w.write_u16(self.sample_address);
+        w.write_u16(self.sample_length);
+        w.write_u16(self.current_address);
+        w.write_u16(self.bytes_remaining);
+        w.write_bool(self.sample_buffer.is_some());
+        w.write_u8(self.sample_buffer.unwrap_or(0));
+        w.write_u8(self.shift_register);
+        w.write_u8(self.bits_remaining);
+        w.write_bool(self.silence);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.irq_enabled = r.read_bool()?;
+        self.irq_flag = r.read_bool()?;
+        self.loop_flag = r.read_bool()?;
+        self.rate_index = r.read_u8()? & 0x0F;
+        self.timer = r.read_u16()?;
+        self.output_level = r.read_u8()? & 0x7F;
+        self.sample_address = r.read_u16()?;
+        self.sample_length = r.read_u16()?;
+        self.current_address = r.read_u16()?;
+        self.bytes_remaining = r.read_u16()?;
+        let has_sample = r.read_bool()?;
+        let sample = r.read_u8()?;
+        self.sample_buffer = has_sample.then_some(sample);
+        self.shift_register = r.read_u8()?;
+        self.bits_remaining = r.read_u8()?;
+        self.silence = r.read_bool()?;
+        Ok(())
+    }
+}
+
+pub struct Apu {
+    timing: ApuTiming,
+    pulse1: Pulse,
+    pulse2: Pulse,
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+
+    // Frame counter ($4017)
+    five_step_mode: bool,
+    irq_inhibit: bool,
+    frame_irq: bool,
+    frame_cycle: u32,
+    odd_cycle: bool, // Pulse timers run at half the CPU rate
+
+    // Output
+    pulse_table: [f32; 31],
+    tnd_table: [f32; 203],
+    sample_phase: f64,
+    sample_sum: f32,
+    sample_count: u32,
+    highpass_prev_in: f32,
+    highpass_prev_out: f32,
+    samples: Vec<f32>,
+}
+
 impl Apu {
     pub fn new() -> Self {
+        let mut pulse_table = [0.0; 31];
+        for (n, v) in pulse_table.iter_mut().enumerate().skip(1) {
+            *v = 95.52 / (8128.0 / n as f32 + 100.0);
+        }
+        let mut tnd_table = [0.0; 203];
+        for (n, v) in tnd_table.iter_mut().enumerate().skip(1) {
+            *v = 163.67 / (24329.0 / n as f32 + 100.0);
+        }
+
         Self {
-            // APUの初期化
+            timing: NTSC_APU_TIMING,
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            triangle: Triangle::default(),
+            noise: Noise::new(),
+            dmc: Dmc::default(),
+            five_step_mode: false,
+            irq_inhibit: false,
+            frame_irq: false,
+            frame_cycle: 0,
+            odd_cycle: false,
+            pulse_table,
+            tnd_table,
+            sample_phase: 0.0,
+            sample_sum: 0.0,
+            sample_count: 0,
+            highpass_prev_in: 0.0,
+            highpass_prev_out: 0.0,
+            samples: Vec::new(),
         }
     }
 
-    pub fn update_pulse_channel(&mut self) {
-        // パルスチャネルの音を生成する処理
+    pub fn reset(&mut self) {
+        let timing = self.timing;
+        *self = Self::new();
+        self.timing = timing;
     }
 
-    pub fn update_triangle_channel(&mut self) {
-        // トライアングルチャネルの音を生成する処理
+    pub fn set_timing(&mut self, timing: ApuTiming) {
+        self.timing = timing;
     }
 
-    pub fn output_audio(&mut self) -> AudioData {
-        // 各チャネルのオーディオデータをミックスする
-        // 最終的なオーディオデータを生成して返す
-        AudioData{
-            samples: vec![],
-            sample_rate: 0,
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4000..=0x4003 => self.pulse1.write(addr - 0x4000, data),
+            0x4004..=0x4007 => self.pulse2.write(addr - 0x4004, data),
+            0x4008..=0x400B => self.triangle.write(addr - 0x4008, data),
+            0x400C..=0x400F => self.noise.write(addr - 0x400C, data),
+            0x4010..=0x4013 => self.dmc.write(addr - 0x4010, data),
+            0x4015 => {
+                self.pulse1.enabled = data & 0x01 != 0;
+                self.pulse2.enabled = data & 0x02 != 0;
+                self.triangle.enabled = data & 0x04 != 0;
+                self.noise.enabled = data & 0x08 != 0;
+                if !self.pulse1.enabled { self.pulse1.length = 0; }
+                if !self.pulse2.enabled { self.pulse2.length = 0; }
+                if !self.triangle.enabled { self.triangle.length = 0; }
+                if !self.noise.enabled { self.noise.length = 0; }
+                if data & 0x10 == 0 {
+                    self.dmc.bytes_remaining = 0;
+                } else if self.dmc.bytes_remaining == 0 {
+                    self.dmc.restart();
+                }
+                self.dmc.irq_flag = false;
+            }
+            0x4017 => {
+                self.five_step_mode = data & 0x80 != 0;
+                self.irq_inhibit = data & 0x40 != 0;
+                if self.irq_inhibit {
+                    self.frame_irq = false;
+                }
+                self.frame_cycle = 0;
+                if self.five_step_mode {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                }
+            }
+            _ => {}
         }
     }
-}
\ No newline at end of file
+
+    // $4015 read without side effects
+    pub fn peek_status(&self) -> u8 {
+        let mut status = 0;
+        if self.pulse1.length > 0 { status |= 0x01; }
+        if self.pulse2.length > 0 { status |= 0x02; }
+        if self.triangle.length > 0 { status |= 0x04; }
+        if self.noise.length > 0 { status |= 0x08; }
+        if self.dmc.bytes_remaining > 0 { status |= 0x10; }
+        if self.frame_irq { status |= 0x40; }
+        if self.dmc.irq_flag { status |= 0x80; }
+        status
+    }
+
+    // $4015 read: acknowledges the frame IRQ
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.frame_irq = false;
+        status
+    }
+
+    pub fn irq_pending(&self) -> bool {
+        self.frame_irq || self.dmc.irq_flag
+    }
+
+    pub fn dmc_pending_fetch(&self) -> Option<u16> {
+        self.dmc.pending_fetch()
+    }
+
+    pub fn dmc_fill_buffer(&mut self, data: u8) {
+        self.dmc.fill_buffer(data);
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulse1.envelope.clock();
+        self.pulse2.envelope.clock();
+        self.noise.envelope.clock();
+        self.triangle.clock_linear();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulse1.clock_length();
+        self.pulse2.clock_length();
+        self.triangle.clock_length();
+        self.noise.clock_length();
+        self.pulse1.clock_sweep();
+        self.pulse2.clock_sweep();
+    }
+
+    fn clock_frame_counter(&mut self) {
+        self.frame_cycle += 1;
+        let steps: &[u32] = if self.five_step_mode { &self.timing.frame_steps_5 } else { &self.timing.frame_steps_4 };
+        let Some(step) = steps.iter().position(|&s| s == self.frame_cycle) else {
+            return;
+        };
+        let last = step == steps.len() - 1;
+        match (self.five_step_mode, step) {
+            (false, 3) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+                if !self.irq_inhibit {
+                    self.frame_irq = true;
+                }
+            }
+            (true, 3) => {} // 5-step mode has an empty fourth step
+            (_, 1) | (true, 4) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            }
+            _ => self.clock_quarter_frame(),
+        }
+        if last {
+            self.frame_cycle = 0;
+        }
+    }
+
+    // One CPU cycle. `expansion` is the cartridge's expansion audio level (0.0 - 1.0).
+    pub fn clock(&mut self, expansion: f32) {
+        self.clock_frame_counter();
+        self.triangle.clock_timer();
+        self.noise.clock_timer(&self.timing);
+        self.dmc.clock_timer(&self.timing);
+        if self.odd_cycle {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.odd_cycle = !self.odd_cycle;
+
+        self.sample_sum += self.mix() + expansion * 0.5;
+        self.sample_count += 1;
+        self.sample_phase += SAMPLE_RATE as f64;
+        if self.sample_phase >= self.timing.cpu_clock {
+            self.sample_phase -= self.timing.cpu_clock;
+            let sample = self.sample_sum / self.sample_count as f32;
+            self.sample_sum = 0.0;
+            self.sample_count = 0;
+            // DC-blocking high-pass filter (~90 Hz)
+            let out = sample - self.highpass_prev_in + 0.987 * self.highpass_prev_out;
+            self.highpass_prev_in = sample;
+            self.highpass_prev_out = out;
+            if self.samples.len() >= MAX_BUFFERED_SAMPLES {
+                // Drop the oldest 100 ms at once rather than shifting on every sample
+                self.samples.drain(..MAX_BUFFERED_SAMPLES / 10);
+            }
+            self.samples.push(out);
+        }
+    }
+
+    fn mix(&self) -> f32 {
+        let pulse = self.pulse1.output() + self.pulse2.output();
+        let tnd = 3 * self.triangle.output() as usize + 2 * self.noise.output() as usize + self.dmc.output_level as usize;
+        self.pulse_table[pulse as usize] + self.tnd_table[tnd]
+    }
+
+    // Mono samples at SAMPLE_RATE produced since the last call
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+}
+
+impl SaveState for Apu {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.begin_section(b"APU ");
+        self.pulse1.save_state(w);
+        self.pulse2.save_state(w);
+        self.triangle.save_state(w);
+        self.noise.save_state(w);
+        self.dmc.save_state(w);
+        w.write_bool(self.five_step_mode);
+        w.write_bool(self.irq_inhibit);
+        w.write_bool(self.frame_irq);
+        w.write_u32(self.frame_cycle);
+        w.write_bool(self.odd_cycle);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.expect_section(b"APU ")?;
+        self.pulse1.load_state(r)?;
+        self.pulse2.load_state(r)?;
+        self.triangle.load_state(r)?;
+        self.noise.load_state(r)?;
+        self.dmc.load_state(r)?;
+        self.five_step_mode = r.read_bool()?;
+        self.irq_inhibit = r.read_bool()?;
+        self.frame_irq = r.read_bool()?;
+        self.frame_cycle = r.read_u32()?;
+        self.odd_cycle = r.read_bool()?;
+        self.samples.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,429 @@
+// Headless runner for CI: runs a ROM without a window and writes results to files.
+// Build with `cargo build --bin nes-headless --no-default-features` to leave out Tauri.
+//
+// Exit codes: 0 = finished (or --until-mem matched), 1 = error, 3 = --until-mem never matched,
+// 4 = an --expect-* check failed. (clap itself exits with 2 on usage errors.)
+use clap::{value_parser, Arg, ArgAction, Command};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter};
+use std::path::Path;
+use std::process::ExitCode;
+use tauri_nes::apu;
+use tauri_nes::emulator::Emulator;
+use tauri_nes::patch;
+use tauri_nes::ppu::FrameData;
+use tauri_nes::region::Region;
+
+const EXIT_ERROR: u8 = 1;
+const EXIT_TIMEOUT: u8 = 3;
+const EXIT_CHECK_FAILED: u8 = 4;
+const DEFAULT_FRAMES: u32 = 600;
+
+fn cli() -> Command {
+    Command::new("nes-headless")
+        .about("Run a NES ROM without a window and dump screenshots, audio, RAM or a CPU trace")
+        .after_help("Exit codes: 0 = ok, 1 = error, 3 = --until-mem not reached, 4 = --expect-* mismatch")
+        .arg(Arg::new("rom").required(true).help("ROM (.nes) or disk image (.fds)"))
+        .arg(Arg::new("patch").long("patch").value_name("FILE").help("IPS/UPS/BPS patch to apply"))
+        .arg(Arg::new("fds-bios").long("fds-bios").value_name("FILE").help("disksys.rom for .fds images"))
+        .arg(
+            Arg::new("region")
+                .long("region")
+                .value_name("REGION")
+                .value_parser(["ntsc", "pal", "dendy"])
+                .help("Force the console region instead of detecting it"),
+        )
+        .arg(
+            Arg::new("frames")
+                .long("frames")
+                .short('n')
+                .value_name("N")
+                .value_parser(value_parser!(u32))
+                .help("Frames to run (default: movie length, or 600)"),
+        )
+        .arg(
+            Arg::new("until-mem")
+                .long("until-mem")
+                .value_name("ADDR=VALUE")
+                .help("Stop as soon as a CPU address holds a value, e.g. 6000=00 (hex)"),
+        )
+        .arg(Arg::new("movie").long("movie").value_name("FILE").help("Replay an FCEUX .fm2 input movie"))
+        .arg(Arg::new("screenshot").long("screenshot").value_name("FILE").help("Write the last frame as PNG"))
+        .arg(Arg::new("wav").long("wav").value_name("FILE").help("Write all audio as 16-bit mono WAV"))
+        .arg(Arg::new("dump-ram").long("dump-ram").value_name("FILE").help("Hexdump of the 2KB CPU RAM at exit"))
+        .arg(Arg::new("trace").long("trace").value_name("FILE").help("Log every executed CPU instruction"))
+        .arg(
+            Arg::new("expect-mem")
+                .long("expect-mem")
+                .value_name("ADDR=VALUE")
+                .action(ArgAction::Append)
+                .help("Fail (exit 4) unless the address holds the value at exit; repeatable"),
+        )
+        .arg(
+            Arg::new("expect-frame-crc")
+                .long("expect-frame-crc")
+                .value_name("CRC32")
+                .help("Fail (exit 4) unless the CRC32 of the last frame's RGBA pixels matches (hex)"),
+        )
+        .arg(Arg::new("quiet").long("quiet").short('q').action(ArgAction::SetTrue).help("Only print errors"))
+}
+
+fn main() -> ExitCode {
+    let matches = cli().get_matches();
+    match run(&matches) {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+fn run(matches: &clap::ArgMatches) -> Result<u8, String> {
+    let quiet = matches.get_flag("quiet");
+    let rom = matches.get_one::<String>("rom").unwrap();
+
+    let mut emulator = Emulator::new();
+    if let Some(bios) = matches.get_one::<String>("fds-bios") {
+        emulator.set_fds_bios_path(bios)?;
+    }
+    if let Some(region) = matches.get_one::<String>("region") {
+        emulator.set_region_override(Some(Region::from_name(region)?));
+    }
+    emulator.load_rom_with_patch(rom, matches.get_one::<String>("patch").map(String::as_str))?;
+    emulator.rewind_enabled = false;
+
+    if let Some(path) = matches.get_one::<String>("trace") {
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        emulator.set_trace_output(Some(Box::new(BufWriter::new(file))));
+    }
+
+    let movie = match matches.get_one::<String>("movie") {
+        Some(path) => Some(Movie::load(Path::new(path))?),
+        None => None,
+    };
+    let until_mem = matches.get_one::<String>("until-mem").map(|s| parse_mem_check(s)).transpose()?;
+    let frames = matches
+        .get_one::<u32>("frames")
+        .copied()
+        .or(movie.as_ref().map(|m| m.frames.len() as u32))
+        .unwrap_or(DEFAULT_FRAMES);
+
+    let mut audio = Vec::new();
+    let mut frame = FrameData::default();
+    let mut frames_run = 0;
+    let mut condition_met = false;
+    while frames_run < frames {
+        if let Some(input) = movie.as_ref().and_then(|m| m.frames.get(frames_run as usize)) {
+            if input.reset {
+                emulator.reset();
+            }
+            emulator.bus.controller1.borrow_mut().set_button_states(input.controller1);
+            emulator.bus.controller2.borrow_mut().set_button_states(input.controller2);
+        }
+        frame = emulator.run_frame()?;
+        audio.extend(emulator.take_audio_samples());
+        frames_run += 1;
+
+        if let Some((addr, value)) = until_mem {
+            if emulator.bus.debug_read(addr) == value {
+                condition_met = true;
+                break;
+            }
+        }
+    }
+    emulator.set_trace_output(None);
+
+    let frame_crc = patch::crc32(&frame.pixels);
+    if !quiet {
+        println!("frames: {}", frames_run);
+        println!("frame crc32: {:08X}", frame_crc);
+    }
+
+    if let Some(path) = matches.get_one::<String>("screenshot") {
+        write_file(path, &encode_png(&frame))?;
+    }
+    if let Some(path) = matches.get_one::<String>("wav") {
+        write_file(path, &encode_wav(&audio, apu::SAMPLE_RATE))?;
+    }
+    if let Some(path) = matches.get_one::<String>("dump-ram") {
+        let ram = emulator.bus.cpu_ram.borrow().ram[..0x800].to_vec();
+        write_file(path, hexdump(&ram).as_bytes())?;
+    }
+
+    if until_mem.is_some() && !condition_met {
+        eprintln!("--until-mem condition not reached within {} frames", frames);
+        return Ok(EXIT_TIMEOUT);
+    }
+
+    let mut failed = false;
+    for check in matches.get_many::<String>("expect-mem").into_iter().flatten() {
+        let (addr, expected) = parse_mem_check(check)?;
+        let actual = emulator.bus.debug_read(addr);
+        if actual != expected {
+            eprintln!("expect-mem failed: ${:04X} = {:02X}, expected {:02X}", addr, actual, expected);
+            failed = true;
+        }
+    }
+    if let Some(expected) = matches.get_one::<String>("expect-frame-crc") {
+        let expected = u32::from_str_radix(expected.trim_start_matches("0x"), 16)
+            .map_err(|_| format!("Invalid CRC32: {}", expected))?;
+        if frame_crc != expected {
+            eprintln!("expect-frame-crc failed: {:08X}, expected {:08X}", frame_crc, expected);
+            failed = true;
+        }
+    }
+    Ok(if failed { EXIT_CHECK_FAILED } else { 0 })
+}
+
+// "6000=80" -> ($6000, $80)
+fn parse_mem_check(s: &str) -> Result<(u16, u8), String> {
+    let (addr, value) = s.split_once('=').ok_or_else(|| format!("Expected ADDR=VALUE, got '{}'", s))?;
+    let addr = u16::from_str_radix(addr.trim().trim_start_matches('$'), 16)
+        .map_err(|_| format!("Invalid address in '{}'", s))?;
+    let value = u8::from_str_radix(value.trim().trim_start_matches('$'), 16)
+        .map_err(|_| format!("Invalid value in '{}'", s))?;
+    Ok((addr, value))
+}
+
+fn write_file(path: &str, data: &[u8]) -> Result<(), String> {
+    std::fs::write(path, data).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+// --- FCEUX .fm2 movies: one "|commands|RLDUTSBA|RLDUTSBA||" line per frame ---
+struct MovieFrame {
+    reset: bool,
+    controller1: u8,
+    controller2: u8,
+}
+
+struct Movie {
+    frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    fn load(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("Failed to open movie {}: {}", path.display(), e))?;
+        Self::parse(BufReader::new(file))
+    }
+
+    fn parse(reader: impl BufRead) -> Result<Self, String> {
+        let mut frames = Vec::new();
+        for line in reader.lines() {
+            let line = line.map_err(|e| format!("Failed to read movie: {}", e))?;
+            // Header lines ("version 3", "romFilename ...") don't start with '|'
+            let Some(record) = line.strip_prefix('|') else { continue };
+            let fields: Vec<&str> = record.split('|').collect();
+            let commands: u32 = fields.first().and_then(|c| c.trim().parse().ok()).unwrap_or(0);
+            frames.push(MovieFrame {
+                reset: commands & 0x03 != 0, // 1 = soft reset, 2 = power cycle
+                controller1: fields.get(1).map_or(0, |f| parse_fm2_buttons(f)),
+                controller2: fields.get(2).map_or(0, |f| parse_fm2_buttons(f)),
+            });
+        }
+        Ok(Self { frames })
+    }
+}
+
+// "RLDUTSBA" order maps onto bits 7..0 of the controller bitfield
+fn parse_fm2_buttons(field: &str) -> u8 {
+    field
+        .bytes()
+        .take(8)
+        .enumerate()
+        .filter(|&(_, c)| c != b'.' && c != b' ')
+        .fold(0, |acc, (i, _)| acc | (0x80 >> i))
+}
+
+// --- Output encoders (no image/audio crates needed) ---
+
+fn hexdump(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (row, chunk) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02X}", b)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        out.push_str(&format!("{:04X}: {:<47}  |{}|\n", row * 16, hex.join(" "), ascii));
+    }
+    out
+}
+
+fn encode_wav(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&1u16.to_le_bytes()); // Mono
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for &sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out
+}
+
+// RGBA PNG with uncompressed (stored) deflate blocks
+fn encode_png(frame: &FrameData) -> Vec<u8> {
+    let stride = frame.width * 4;
+    let mut raw = Vec::with_capacity((stride + 1) * frame.height);
+    for row in 0..frame.height {
+        raw.push(0); // Filter: none
+        raw.extend_from_slice(&frame.pixels[row * stride..(row + 1) * stride]);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        zlib.push(blocks.peek().is_none() as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(frame.width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(frame.height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]); // 8-bit RGBA, no interlace
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_png_chunk(&mut out, b"IHDR", &ihdr);
+    write_png_chunk(&mut out, b"IDAT", &zlib);
+    write_png_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn write_png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = patch::crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mem_checks() {
+        assert_eq!(parse_mem_check("6000=80"), Ok((0x6000, 0x80)));
+        assert_eq!(parse_mem_check("$00FF = $0a"), Ok((0x00FF, 0x0A)));
+        for bad in ["6000", "6000=", "=80", "10000=00", "6000=100", "zz=00"] {
+            assert!(parse_mem_check(bad).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn fm2_movies() {
+        let fm2 = "version 3\nromFilename test\n|0|R......A|........||\n|1|........|.L..T...||\n|2|RLDUTSBA|||\n";
+        let movie = Movie::parse(fm2.as_bytes()).unwrap();
+        let frames: Vec<(bool, u8, u8)> = movie.frames.iter().map(|f| (f.reset, f.controller1, f.controller2)).collect();
+        assert_eq!(frames, vec![(false, 0x81, 0x00), (true, 0x00, 0x48), (true, 0xFF, 0x00)]);
+    }
+
+    #[test]
+    fn wav_header_and_samples() {
+        let wav = encode_wav(&[0.0, 1.0, -1.0, 2.0], 44100);
+        assert_eq!(wav.len(), 44 + 8);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()), 36 + 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 44100);
+        assert_eq!(u32::from_le_bytes(wav[28..32].try_into().unwrap()), 88200);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 8);
+        let samples: Vec<i16> = wav[44..].chunks(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect();
+        assert_eq!(samples, vec![0, i16::MAX, -i16::MAX, i16::MAX]);
+    }
+
+    #[test]
+    fn adler32_reference_value() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    // Walk the chunks, check their CRCs and undo the stored deflate blocks
+    fn decode_png(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let (mut pos, mut size, mut zlib) = (8, (0, 0), Vec::new());
+        while pos < png.len() {
+            let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+            let chunk = &png[pos + 4..pos + 8 + len];
+            let crc = u32::from_be_bytes(png[pos + 8 + len..pos + 12 + len].try_into().unwrap());
+            assert_eq!(patch::crc32(chunk), crc);
+            let (kind, data) = chunk.split_at(4);
+            match kind {
+                b"IHDR" => {
+                    size = (u32::from_be_bytes(data[..4].try_into().unwrap()), u32::from_be_bytes(data[4..8].try_into().unwrap()));
+                    assert_eq!(&data[8..], &[8, 6, 0, 0, 0]);
+                }
+                b"IDAT" => zlib.extend_from_slice(data),
+                b"IEND" => assert!(data.is_empty()),
+                _ => panic!("unexpected chunk"),
+            }
+            pos += 12 + len;
+        }
+
+        assert_eq!(&zlib[..2], &[0x78, 0x01]);
+        let (mut pos, mut raw) = (2, Vec::new());
+        loop {
+            let last = zlib[pos] == 1;
+            let len = u16::from_le_bytes([zlib[pos + 1], zlib[pos + 2]]);
+            assert_eq!(!len, u16::from_le_bytes([zlib[pos + 3], zlib[pos + 4]]));
+            raw.extend_from_slice(&zlib[pos + 5..pos + 5 + len as usize]);
+            pos += 5 + len as usize;
+            if last {
+                break;
+            }
+        }
+        assert_eq!(zlib[pos..], adler32(&raw).to_be_bytes());
+        (size.0, size.1, raw)
+    }
+
+    #[test]
+    fn png_round_trips_through_stored_blocks() {
+        // A full frame needs several 64KB stored blocks
+        let mut frame = FrameData::new(256, 240);
+        for (i, byte) in frame.pixels.iter_mut().enumerate() {
+            *byte = (i % 251) as u8;
+        }
+        let (width, height, raw) = decode_png(&encode_png(&frame));
+        assert_eq!((width, height), (256, 240));
+        assert_eq!(raw.len(), (256 * 4 + 1) * 240);
+        for (row, line) in raw.chunks(256 * 4 + 1).enumerate() {
+            assert_eq!(line[0], 0);
+            assert_eq!(&line[1..], &frame.pixels[row * 256 * 4..(row + 1) * 256 * 4]);
+        }
+
+        let (width, height, raw) = decode_png(&encode_png(&FrameData::new(0, 0)));
+        assert_eq!((width, height, raw.len()), (0, 0, 0));
+    }
+}
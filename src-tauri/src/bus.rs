@@ -1,6 +1,7 @@
 use crate::ram::Memory;
 use crate::cartridge::{Cartridge};
 use crate::ppu::Ppu;
+use crate::apu::Apu;
 use crate::cpu::{self, Cpu6502};
 use crate::controller::Controller;
 use std::sync::{Arc, Mutex};
@@ -8,12 +9,20 @@ use std::cell::{RefCell, RefMut, UnsafeCell};
 use crate::cpu::InspectState;
 use crate::ppu::FrameData;
 use crate::Mirroring;
+use crate::savestate::{SaveState, StateReader, StateWriter};
+use crate::region::Region;
+use crate::debugger::{AccessKind, Debugger, WatchSpace};
+use crate::expr::Machine;
+use crate::cdl::{self, CodeDataLog};
+use crate::cheats::RomPatch;
+use serde::{Deserialize, Serialize};
 
 // The main system bus, connecting CPU, PPU, RAM, Cartridge, etc.
 pub struct Bus {
     pub cpu_ram: RefCell<Memory>,
     pub ppu: RefCell<Ppu>,
     pub cpu: RefCell<Cpu6502>,
+    pub apu: RefCell<Apu>,
     cartridge: Option<Arc<Mutex<Cartridge>>>,
     pub controller1: RefCell<Controller>,
     pub controller2: RefCell<Controller>,
@@ -26,6 +35,11 @@ pub struct Bus {
     oam_dma_offset: u8,
     oam_dma_data: u8,
     irq_cooldown: UnsafeCell<u32>, // Use UnsafeCell for interior mutability
+    region: Region,
+    ppu_dot_remainder: u32, // PALの 3.2 ドット/CPUサイクルの端数
+    pub debugger: RefCell<Debugger>, // ブレークポイント / ウォッチポイント
+    pub cdl: RefCell<CodeDataLog>, // コード/データログ (FCEUX .cdl)
+    rom_patches: Vec<RomPatch>, // 有効なゲームジニーコード
 }
 
 impl Bus {
@@ -34,6 +48,7 @@ impl Bus {
             cpu_ram: RefCell::new(Memory::new()),
             ppu: RefCell::new(Ppu::new()),
             cpu: RefCell::new(Cpu6502::new()),
+            apu: RefCell::new(Apu::new()),
             cartridge: None,
             controller1: RefCell::new(Controller::new()),
             controller2: RefCell::new(Controller::new()),
@@ -46,16 +61,72 @@ impl Bus {
             oam_dma_offset: 0,
             oam_dma_data: 0,
             irq_cooldown: UnsafeCell::new(0),
+            region: Region::Ntsc,
+            ppu_dot_remainder: 0,
+            debugger: RefCell::new(Debugger::new()),
+            cdl: RefCell::new(CodeDataLog::new()),
+            rom_patches: Vec::new(),
         }
     }
 
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    // Switch the PPU frame layout, CPU/PPU clock ratio and APU rate tables
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.ppu_dot_remainder = 0;
+        self.ppu.borrow_mut().region = region;
+        self.apu.borrow_mut().set_timing(region.apu_timing());
+    }
+
+    // Number of PPU dots that elapse during `cpu_cycles` CPU cycles
+    pub fn ppu_dots_for(&mut self, cpu_cycles: u64) -> u64 {
+        let (num, den) = self.region.ppu_clock_ratio();
+        let total = cpu_cycles * num as u64 + self.ppu_dot_remainder as u64;
+        self.ppu_dot_remainder = (total % den as u64) as u32;
+        total / den as u64
+    }
+
+    // Game Genie codes: substitutions for CPU reads of $8000-$FFFF
+    pub fn set_rom_patches(&mut self, patches: Vec<RomPatch>) {
+        self.rom_patches = patches;
+    }
+
     // Method to insert a cartridge into the bus
     pub fn insert_cartridge(&mut self, cartridge: Cartridge) {
+        self.cdl.get_mut().resize(cartridge.prg_rom().len(), cartridge.chr_rom_len());
         self.cartridge = Some(Arc::new(Mutex::new(cartridge)));
         self.reset(); // Reset system on cartridge insertion
     }
 
     fn bus_read(&self, addr: u16) -> u8 {
+        let mut value = self.read_mapped(addr);
+        if addr >= 0x8000 {
+            value = self.rom_patches.iter().find_map(|p| p.apply(addr, value)).unwrap_or(value);
+        }
+        if !self.is_watching() {
+            return value;
+        }
+        self.watch(WatchSpace::Cpu, AccessKind::Read, addr, value);
+        match addr & 0xE007 {
+            0x2004 => {
+                let oam_addr = self.ppu.borrow().oam_addr;
+                self.watch(WatchSpace::Oam, AccessKind::Read, oam_addr as u16, value);
+            }
+            0x2007 => {
+                // The VRAM byte fetched by this read (what the CPU gets is the previous buffer content)
+                let vram_addr = self.ppu.borrow().get_vram_address() & 0x3FFF;
+                let data = self.ppu_read(vram_addr);
+                self.watch(ppu_watch_space(vram_addr), AccessKind::Read, vram_addr, data);
+            }
+            _ => {}
+        }
+        value
+    }
+
+    fn read_mapped(&self, addr: u16) -> u8 {
         match addr {
             0x0000..=0x1FFF => self.cpu_ram.borrow().ram[addr as usize & 0x07FF],
             0x2000..=0x3FFF => { // PPU Registers
@@ -77,7 +148,8 @@ impl Bus {
                     _ => 0,
                 }
             }
-            0x4000..=0x4015 => 0,
+            0x4000..=0x4014 => 0,
+            0x4015 => self.apu.borrow_mut().read_status(),
             0x4016 => self.controller1.borrow_mut().read(),
             0x4017 => self.controller2.borrow_mut().read(),
             0x4018..=0x401F => 0,
@@ -90,7 +162,7 @@ impl Bus {
                     if cooldown == 0 {
                         // Only log IRQ vector reads occasionally
                         let value = self.cartridge.as_ref().map_or(0xFF, |cart| cart.lock().unwrap().read_prg(addr));
-                        println!("IRQ vector read at ${:04X}: ${:02X} (ROM addr: ${:04X})", 
+                        log::trace!("IRQ vector read at ${:04X}: ${:02X} (ROM addr: ${:04X})", 
                             addr, value, addr & 0x7FFF);
                         
                         // Set cooldown safely with UnsafeCell
@@ -103,14 +175,15 @@ impl Bus {
                     }
                 }
                 
-                // Regular cartridge read
-                self.cartridge.as_ref().map_or(0xFF, |cart| cart.lock().unwrap().read_prg(addr))
+                // Regular cartridge read (mapper registers may have read side effects)
+                self.cartridge.as_ref().map_or(0xFF, |cart| cart.lock().unwrap().cpu_read(addr))
             }
         }
     }
 
     // fn bus_write(&self, addr: u16, data: u8) {
     fn bus_write(&mut self, addr: u16, data: u8) { // Change &self to &mut self
+        self.watch(WatchSpace::Cpu, AccessKind::Write, addr, data);
         match addr {
             0x0000..=0x1FFF => self.cpu_ram.borrow_mut().write(addr & 0x07FF, data),
             0x2000..=0x3FFF => { // PPU Registers
@@ -125,13 +198,13 @@ impl Bus {
                     static mut RENDER_WRITE_WARN_COUNT: u32 = 0;
                     unsafe {
                         if RENDER_WRITE_WARN_COUNT < 50 { // Log first 50 warnings
-                            println!(
+                            log::warn!(
                                 "[WARN] PPU Write during render! Addr=${:04X} Data=${:02X} Scanline={}, Cycle={}", 
                                 addr, data, scanline, ppu_ref.cycle
                             );
                             RENDER_WRITE_WARN_COUNT += 1;
                         } else if RENDER_WRITE_WARN_COUNT == 50 {
-                            println!("[WARN] PPU Write during render: Further warnings suppressed...");
+                            log::warn!("[WARN] PPU Write during render: Further warnings suppressed...");
                             RENDER_WRITE_WARN_COUNT += 1;
                         }
                     }
@@ -143,35 +216,39 @@ impl Bus {
                 // println!("[PPU Write] Addr=${:04X} (Register ${:04X}) Data=${:02X}", addr, register, data);
                 match register {
                     0x0000 => { // PPUCTRL ($2000)
-                        println!("[PPU Write] PPUCTRL (${:04X}) write: ${:02X}", addr, data); // Log PPUCTRL writes
+                        log::trace!("[PPU Write] PPUCTRL (${:04X}) write: ${:02X}", addr, data); // Log PPUCTRL writes
                         self.ppu.borrow_mut().write_ctrl(data)
                     },
                     0x0001 => self.ppu.borrow_mut().write_mask(data),
                     0x0003 => self.ppu.borrow_mut().write_oam_addr(data),
                     0x0004 => {
-                        println!("[PPU Write] OAMDATA (${:04X}) write: ${:02X}", addr, data); // Log OAMDATA
+                        log::trace!("[PPU Write] OAMDATA (${:04X}) write: ${:02X}", addr, data); // Log OAMDATA
+                        let oam_addr = self.ppu.borrow().oam_addr;
+                        self.watch(WatchSpace::Oam, AccessKind::Write, oam_addr as u16, data);
                         self.ppu.borrow_mut().write_oam_data(data)
                     },
                     0x0005 => {
-                        println!("[PPU Write] PPUSCROLL (${:04X}) write: ${:02X}", addr, data); // Log PPUSCROLL
+                        log::trace!("[PPU Write] PPUSCROLL (${:04X}) write: ${:02X}", addr, data); // Log PPUSCROLL
                         self.ppu.borrow_mut().write_scroll(data)
                     },
                     0x0006 => {
-                        println!("[PPU Write] PPUADDR (${:04X}) write: ${:02X}", addr, data); // Log PPUADDR
+                        log::trace!("[PPU Write] PPUADDR (${:04X}) write: ${:02X}", addr, data); // Log PPUADDR
                         self.ppu.borrow_mut().write_addr(data)
                     },
                     0x0007 => {
-                        println!("[PPU Write] PPUDATA (${:04X}) write: ${:02X}", addr, data); // Log PPUDATA
+                        log::trace!("[PPU Write] PPUDATA (${:04X}) write: ${:02X}", addr, data); // Log PPUDATA
                         // Get VRAM address *before* potential write borrows
                         let vram_addr = self.ppu.borrow().vram_addr.get();
-                        println!("  -> Target VRAM Addr = ${:04X}", vram_addr);
+                        log::trace!("  -> Target VRAM Addr = ${:04X}", vram_addr);
 
                         // Perform the actual write to VRAM/Palette/CHR
+                        let vram_addr = vram_addr & 0x3FFF;
                         if vram_addr >= 0x3F00 {
-                            println!("  -> Writing to Palette...");
+                            self.watch(WatchSpace::Palette, AccessKind::Write, vram_addr, data);
+                            log::trace!("  -> Writing to Palette...");
                             self.write_palette(vram_addr, data); // Use internal palette helper
                         } else {
-                            println!("  -> Writing to VRAM/CHR via BusAccess::ppu_write_vram...");
+                            log::trace!("  -> Writing to VRAM/CHR via BusAccess::ppu_write_vram...");
                             // Use the BusAccess trait method directly on self
                             self.ppu_write_vram(vram_addr, data);
                         }
@@ -179,19 +256,23 @@ impl Bus {
                         // Increment PPU address *after* the write is done
                         // This separates the borrows and resolves E0502
                         self.ppu.borrow_mut().increment_vram_addr();
-                        println!("  -> PPU VRAM address incremented.");
+                        log::trace!("  -> PPU VRAM address incremented.");
                     }
                     _ => {}
                 }
             }
-            0x4000..=0x4013 => {},
+            0x4000..=0x4013 => self.apu.borrow_mut().write_register(addr, data),
             0x4014 => {
                 // println!("Write to $4014 (OAM DMA Trigger): ${:02X}", data);
                 self.trigger_oam_dma(data);
             },
-            0x4015 => {},
-            0x4016 => self.controller1.borrow_mut().write(data),
-            0x4017 => self.controller2.borrow_mut().write(data),
+            0x4015 => self.apu.borrow_mut().write_register(addr, data),
+            0x4016 => {
+                // The strobe line is shared by both controller ports
+                self.controller1.borrow_mut().write(data);
+                self.controller2.borrow_mut().write(data);
+            }
+            0x4017 => self.apu.borrow_mut().write_register(addr, data), // Frame counter
             0x4018..=0x401F => {},
             0x4020..=0xFFFF => { // Cartridge
                 if let Some(cart) = &self.cartridge {
@@ -215,6 +296,43 @@ impl Bus {
         }
     }
 
+    // CPU reads of PRG ROM, and of CHR ROM through $2007
+    fn log_cdl_read(&self, addr: u16) {
+        let Some(cart) = self.cartridge.as_ref() else { return };
+        if addr & 0xE007 == 0x2007 {
+            let vram_addr = self.ppu.borrow().get_vram_address() & 0x3FFF;
+            if vram_addr < 0x2000 {
+                self.log_cdl_chr(vram_addr, cdl::CHR_READ);
+            }
+        } else if let Some(offset) = cart.lock().unwrap().prg_rom_offset(addr) {
+            self.cdl.borrow_mut().log_prg_read(addr, offset);
+        }
+    }
+
+    fn log_cdl_chr(&self, addr: u16, flags: u8) {
+        let offset = self.cartridge.as_ref().and_then(|c| c.lock().unwrap().chr_rom_offset(addr));
+        if let Some(offset) = offset {
+            self.cdl.borrow_mut().log_chr(offset, flags);
+        }
+    }
+
+    // Report an access to the debugger's watchpoints.
+    // PPU rendering fetches happen while the PPU is borrowed; they aren't CPU accesses and are not watched.
+    fn is_watching(&self) -> bool {
+        self.debugger.try_borrow().is_ok_and(|debugger| debugger.is_watching())
+    }
+
+    fn watch(&self, space: WatchSpace, kind: AccessKind, addr: u16, value: u8) {
+        let Ok(mut debugger) = self.debugger.try_borrow_mut() else { return };
+        if !debugger.is_watching() {
+            return;
+        }
+        if self.ppu.try_borrow_mut().is_err() {
+            return;
+        }
+        debugger.on_access(space, kind, addr, value, &DebugView(self));
+    }
+
     // Method to handle side effects of reading PPU status register $2002
     // This should be called by the CPU after reading $2002
     pub fn ppu_status_read_side_effects(&mut self) {
@@ -232,24 +350,24 @@ impl Bus {
     }
 
     pub fn write_palette(&mut self, addr: u16, data: u8) {
-        println!("[write_palette] Addr=${:04X}, Data=${:02X}", addr, data); // ★★★ Log entry
+        log::trace!("[write_palette] Addr=${:04X}, Data=${:02X}", addr, data); // ★★★ Log entry
         let mirrored_addr = addr & 0x3F1F; // Apply palette mirroring
-        println!("[write_palette] Mirrored Addr = ${:04X}", mirrored_addr); // ★★★ Log mirrored
+        log::trace!("[write_palette] Mirrored Addr = ${:04X}", mirrored_addr); // ★★★ Log mirrored
         let final_addr = match mirrored_addr {
             0x3F10 | 0x3F14 | 0x3F18 | 0x3F1C => {
-                println!("[write_palette] Mirroring ${:04X} to ${:04X}", mirrored_addr, mirrored_addr - 0x10); // ★★★ Log specific mirroring
+                log::trace!("[write_palette] Mirroring ${:04X} to ${:04X}", mirrored_addr, mirrored_addr - 0x10); // ★★★ Log specific mirroring
                 mirrored_addr - 0x10
             },
             _ => mirrored_addr,
         };
         let palette_index = (final_addr & 0x1F) as usize; // Calculate index
-        println!("[write_palette] Final Addr = ${:04X}, Index = {}", final_addr, palette_index); // ★★★ Log final addr and index
+        log::trace!("[write_palette] Final Addr = ${:04X}, Index = {}", final_addr, palette_index); // ★★★ Log final addr and index
         // Write to PPU's internal palette RAM
         if palette_index < self.ppu.borrow().palette_ram.len() { // ★★★ Add bounds check ★★★
             self.ppu.borrow_mut().palette_ram[palette_index] = data;
-            println!("[write_palette] Wrote to palette index {}", palette_index); // ★★★ Log success
+            log::trace!("[write_palette] Wrote to palette index {}", palette_index); // ★★★ Log success
         } else {
-            println!("[write_palette] ERROR: Palette index {} out of bounds (size {})!", palette_index, self.ppu.borrow().palette_ram.len()); // ★★★ Log error
+            log::warn!("[write_palette] ERROR: Palette index {} out of bounds (size {})!", palette_index, self.ppu.borrow().palette_ram.len()); // ★★★ Log error
             // Optionally panic here if this should never happen
             // panic!("Palette index out of bounds!");
         }
@@ -293,7 +411,7 @@ impl Bus {
             if mirrored_addr < self.ppu.borrow().vram.len() { // Check bounds
                 let data = self.ppu.borrow().vram[mirrored_addr];
                 // ★★★ Log Nametable Read ★★★
-                println!("--- Nametable Read: OrigAddr:{:04X} Mirrored:{:04X} -> Data:{:02X} ---", addr, mirrored_addr, data);
+                log::trace!("--- Nametable Read: OrigAddr:{:04X} Mirrored:{:04X} -> Data:{:02X} ---", addr, mirrored_addr, data);
                 // ★★★ ここまで ★★★
                 data
             } else {
@@ -311,40 +429,41 @@ impl Bus {
 
     pub fn ppu_write_vram(&mut self, addr: u16, data: u8) {
         let addr = addr & 0x3FFF;
-        println!("[ppu_write_vram] Addr=${:04X}, Data=${:02X}", addr, data); // ★★★ Log entry
+        self.watch(ppu_watch_space(addr), AccessKind::Write, addr, data);
+        log::trace!("[ppu_write_vram] Addr=${:04X}, Data=${:02X}", addr, data); // ★★★ Log entry
         match addr {
             0x0000..=0x1FFF => { // Pattern Tables
-                println!("[ppu_write_vram] Writing to Pattern Table (CHR)..."); // ★★★ Log path
+                log::trace!("[ppu_write_vram] Writing to Pattern Table (CHR)..."); // ★★★ Log path
                 if let Some(cart) = &self.cartridge {
                     // Consider adding a check here if CHR is RAM or ROM
                     // For now, assume write is possible (might panic if ROM)
-                     println!("[ppu_write_vram] Attempting cart.write_chr..."); // ★★★ Log before cart write
+                     log::trace!("[ppu_write_vram] Attempting cart.write_chr..."); // ★★★ Log before cart write
                     cart.lock().unwrap().write_chr(addr, data);
-                     println!("[ppu_write_vram] cart.write_chr completed."); // ★★★ Log after cart write
+                     log::trace!("[ppu_write_vram] cart.write_chr completed."); // ★★★ Log after cart write
                 } else {
-                    println!("[ppu_write_vram] No cartridge found for CHR write."); // ★★★ Log no cart
+                    log::trace!("[ppu_write_vram] No cartridge found for CHR write."); // ★★★ Log no cart
                 }
-                println!("[ppu_write_vram] Wrote to Pattern Table (CHR)."); // ★★★ Log path end
+                log::trace!("[ppu_write_vram] Wrote to Pattern Table (CHR)."); // ★★★ Log path end
             }
             0x2000..=0x3EFF => { // Name Tables
-                println!("[ppu_write_vram] Writing to Name Table..."); // ★★★ Log path
+                log::trace!("[ppu_write_vram] Writing to Name Table..."); // ★★★ Log path
                 let mirroring = self.get_mirroring(); // Use the unified method
                 let mirrored_addr = self.ppu.borrow().mirror_vram_addr(addr, mirroring);
-                 println!("[ppu_write_vram] Mirrored NT Addr = ${:04X}", mirrored_addr); // ★★★ Log mirrored addr
+                 log::trace!("[ppu_write_vram] Mirrored NT Addr = ${:04X}", mirrored_addr); // ★★★ Log mirrored addr
                  if (mirrored_addr as usize) < self.ppu.borrow().vram.len() { // ★★★ Add bounds check
                     self.ppu.borrow_mut().vram[mirrored_addr as usize] = data;
-                    println!("[ppu_write_vram] Wrote to Name Table index {}.", mirrored_addr); // ★★★ Fix: Add argument
+                    log::trace!("[ppu_write_vram] Wrote to Name Table index {}.", mirrored_addr); // ★★★ Fix: Add argument
                  } else {
-                     println!("[ppu_write_vram] ERROR: VRAM index {} out of bounds (size {})!", mirrored_addr, self.ppu.borrow().vram.len()); // ★★★ Log error
+                     log::warn!("[ppu_write_vram] ERROR: VRAM index {} out of bounds (size {})!", mirrored_addr, self.ppu.borrow().vram.len()); // ★★★ Log error
                      // panic!("[ppu_write_vram] VRAM index out of bounds!");
                  }
             }
             0x3F00..=0x3FFF => { // Palette RAM
-                println!("[ppu_write_vram] Writing to Palette via ppu_write_vram..."); // ★★★ Log path
+                log::trace!("[ppu_write_vram] Writing to Palette via ppu_write_vram..."); // ★★★ Log path
                 self.write_palette(addr, data); // Forward to write_palette
-                println!("[ppu_write_vram] Wrote to Palette via ppu_write_vram."); // ★★★ Log path end
+                log::trace!("[ppu_write_vram] Wrote to Palette via ppu_write_vram."); // ★★★ Log path end
             },
-            _ => {println!("[ppu_write_vram] Invalid address range: ${:04X}", addr);} // ★★★ Log invalid range
+            _ => {log::trace!("[ppu_write_vram] Invalid address range: ${:04X}", addr);} // ★★★ Log invalid range
         }
     }
 
@@ -368,6 +487,7 @@ impl Bus {
             } else { // Write cycle
                 // Write to OAM data via PPU's method
                 self.ppu.borrow_mut().write_oam_byte(self.oam_dma_offset, self.oam_dma_data);
+                self.watch(WatchSpace::Oam, AccessKind::Write, self.oam_dma_offset as u16, self.oam_dma_data);
                 self.oam_dma_offset = self.oam_dma_offset.wrapping_add(1);
                 if self.oam_dma_offset == 0 { // Finished writing 256 bytes
                     self.oam_dma_cycles_remaining = 0; // End DMA
@@ -391,6 +511,9 @@ impl Bus {
         // --- PPU Clocking ---
         self.clock_ppu(cycles_executed);
 
+        // --- APU / Mapper Clocking (IRQ timers, disk drive, audio) ---
+        self.clock_peripherals(cycles_executed);
+
         // --- NMI Check (after PPU clocking) ---
         let current_nmi_line = self.ppu.borrow().nmi_line_low;
         if !current_nmi_line && self.prev_nmi_line { // Falling edge (true -> false)
@@ -410,7 +533,7 @@ impl Bus {
     // Clock PPU based on CPU cycles executed
     fn clock_ppu(&mut self, cpu_cycles: u64) {
         let bus_ptr = self as *mut Self; // Get raw pointer to self for BusAccess
-        for _ in 0..cpu_cycles * 3 {
+        for _ in 0..self.ppu_dots_for(cpu_cycles) {
             // Pass BusAccess via unsafe pointer to ppu.step_cycle
             let mut ppu = self.ppu.borrow_mut();
             unsafe { ppu.step_cycle(&mut *bus_ptr); }
@@ -457,13 +580,15 @@ impl Bus {
             0x2000..=0x3FFF => { // PPU Registers
                 let register = addr & 0x0007;
                 match register {
-                    0x0002 /* PPUSTATUS */ => 0, // TODO: Implement self.ppu.borrow().peek_status(),
+                    0x0002 /* PPUSTATUS */ => self.ppu.borrow().read_status_peek(),
                     0x0004 /* OAMDATA */ => self.ppu.borrow().read_oam_data(),
-                    0x0007 /* PPUDATA */ => 0, // TODO: Implement self.ppu.borrow().peek_data_read_buffer(),
+                    0x0007 /* PPUDATA */ => self.ppu.borrow().data_buffer,
                     _ => 0,
                 }
             }
-            0x4016 => 0, // TODO: Implement self.controller1.borrow().peek(),
+            0x4015 => self.apu.borrow().peek_status(),
+            0x4016 => self.controller1.borrow().peek(),
+            0x4017 => self.controller2.borrow().peek(),
             0x4020..=0xFFFF => { // Cartridge
                 self.cartridge.as_ref().map_or(0xFF, |cart| cart.lock().unwrap().read_prg(addr))
             }
@@ -471,6 +596,110 @@ impl Bus {
         }
     }
 
+    // Write on behalf of a debugger front-end; it doesn't trigger watchpoints
+    pub fn debug_write(&mut self, addr: u16, data: u8) {
+        let debugger = std::mem::take(self.debugger.get_mut());
+        self.bus_write(addr, data);
+        *self.debugger.get_mut() = debugger;
+    }
+
+    // Size of an address space as seen by the memory editor
+    pub fn memory_size(&self, space: MemorySpace) -> usize {
+        let cart = self.cartridge.as_ref().map(|c| c.lock().unwrap());
+        match space {
+            MemorySpace::Cpu => 0x10000,
+            MemorySpace::Vram => 0x4000,
+            MemorySpace::Palette => 0x20,
+            MemorySpace::Oam => 0x100,
+            MemorySpace::PrgRom => cart.map_or(0, |c| c.prg_rom().len()),
+            MemorySpace::Chr => cart.map_or(0, |c| c.chr().len()),
+            MemorySpace::PrgRam => cart.map_or(0, |c| c.prg_ram().len()),
+        }
+    }
+
+    // Memory editor read; never has side effects (CPU space goes through debug_read)
+    pub fn peek_memory(&self, space: MemorySpace, addr: usize) -> u8 {
+        match space {
+            MemorySpace::Cpu => self.debug_read(addr as u16),
+            MemorySpace::Vram => match addr as u16 & 0x3FFF {
+                a @ 0x0000..=0x1FFF => self.cartridge.as_ref().map_or(0, |c| c.lock().unwrap().read_chr(a)),
+                a @ 0x2000..=0x3EFF => {
+                    let ppu = self.ppu.borrow();
+                    ppu.vram.get(ppu.mirror_vram_addr(a, self.get_mirroring())).copied().unwrap_or(0)
+                }
+                a => self.read_palette(a),
+            },
+            MemorySpace::Palette => self.read_palette(addr as u16),
+            MemorySpace::Oam => self.ppu.borrow().oam_data[addr & 0xFF],
+            MemorySpace::PrgRom | MemorySpace::Chr | MemorySpace::PrgRam => {
+                let Some(cart) = self.cartridge.as_ref() else { return 0 };
+                let cart = cart.lock().unwrap();
+                let memory = match space {
+                    MemorySpace::PrgRom => cart.prg_rom(),
+                    MemorySpace::Chr => cart.chr(),
+                    _ => cart.prg_ram(),
+                };
+                memory.get(addr).copied().unwrap_or(0)
+            }
+        }
+    }
+
+    // Memory editor write. ROM and RAM are patched in place without watchpoints or mapper side effects.
+    // In CPU space anything else (PPU/APU/controller registers, unmapped cartridge space) is a real bus
+    // write with the hardware behaviour that goes with it; Emulator::write_memory refuses $2000-$5FFF.
+    pub fn poke_memory(&mut self, space: MemorySpace, addr: usize, data: u8) {
+        match space {
+            MemorySpace::Cpu => {
+                let addr = addr as u16;
+                if addr < 0x2000 {
+                    self.cpu_ram.get_mut().write(addr & 0x07FF, data);
+                    return;
+                }
+                let rom_offset = self.cartridge.as_ref().and_then(|c| c.lock().unwrap().prg_rom_offset(addr));
+                match (rom_offset, self.cartridge.as_ref()) {
+                    (Some(offset), Some(cart)) => cart.lock().unwrap().prg_rom_mut()[offset] = data,
+                    (None, Some(cart)) if (0x6000..0x8000).contains(&addr) => cart.lock().unwrap().write_prg(addr, data),
+                    _ => self.debug_write(addr, data),
+                }
+            }
+            MemorySpace::Vram => match addr as u16 & 0x3FFF {
+                a @ 0x0000..=0x1FFF => {
+                    let Some(cart) = self.cartridge.as_ref() else { return };
+                    let mut cart = cart.lock().unwrap();
+                    match cart.chr_rom_offset(a) {
+                        Some(offset) => cart.chr_mut()[offset] = data,
+                        None => cart.write_chr(a, data),
+                    }
+                }
+                a @ 0x2000..=0x3EFF => {
+                    let index = self.ppu.borrow().mirror_vram_addr(a, self.get_mirroring());
+                    if let Some(byte) = self.ppu.get_mut().vram.get_mut(index) {
+                        *byte = data;
+                    }
+                }
+                a => self.poke_memory(MemorySpace::Palette, a as usize, data),
+            },
+            MemorySpace::Palette => {
+                let index = addr & 0x1F;
+                let index = if index & 0x13 == 0x10 { index & 0x0F } else { index }; // $3F1x mirrors $3F0x
+                self.ppu.get_mut().palette_ram[index] = data;
+            }
+            MemorySpace::Oam => self.ppu.get_mut().oam_data[addr & 0xFF] = data,
+            MemorySpace::PrgRom | MemorySpace::Chr | MemorySpace::PrgRam => {
+                let Some(cart) = self.cartridge.as_ref() else { return };
+                let mut cart = cart.lock().unwrap();
+                let memory = match space {
+                    MemorySpace::PrgRom => cart.prg_rom_mut(),
+                    MemorySpace::Chr => cart.chr_mut(),
+                    _ => cart.prg_ram_mut(),
+                };
+                if let Some(byte) = memory.get_mut(addr) {
+                    *byte = data;
+                }
+            }
+        }
+    }
+
     pub fn trigger_oam_dma(&mut self, page: u8) {
         if self.oam_dma_cycles_remaining > 0 {}
         self.oam_dma_page = page;
@@ -494,29 +723,30 @@ impl Bus {
         // self.controller2.borrow_mut().reset();
         self.total_cycles = 0;
         self.oam_dma_cycles_remaining = 0;
+        self.apu.borrow_mut().reset();
         
         // ROM読み込み確認
         if let Some(_) = &self.cartridge {
             // OK
         } else {
-            println!("WARNING: Attempting to reset without cartridge loaded");
+            log::warn!("WARNING: Attempting to reset without cartridge loaded");
         }
         
         // CPU reset
-        println!("[Bus Reset] Calling cpu.reset()..."); // Log before CPU reset
+        log::debug!("[Bus Reset] Calling cpu.reset()..."); // Log before CPU reset
         let bus_ptr = self as *mut Self;
         let mut cpu_ref = self.cpu.borrow_mut();
         unsafe { cpu_ref.reset(&mut *bus_ptr); }
-        println!("[Bus Reset] cpu.reset() finished."); // Log after CPU reset
+        log::debug!("[Bus Reset] cpu.reset() finished."); // Log after CPU reset
     }
 
     pub fn toggle_test_mode(&mut self) {
         self.test_mode = !self.test_mode;
-        println!("Test mode toggled: {}", self.test_mode);
+        log::debug!("Test mode toggled: {}", self.test_mode);
         if self.test_mode {
             self.test_pattern_rendered = false;
         } else {
-            println!("Exiting test mode.");
+            log::debug!("Exiting test mode.");
             self.reset();
         }
     }
@@ -524,12 +754,12 @@ impl Bus {
     pub fn handle_key_event(&mut self, key_code: &str, pressed: bool) {
         // TODO: Implement handle_key in controller.rs
         // self.controller1.borrow_mut().handle_key(key_code, pressed);
-        println!("Ignoring key event for now: {} ({})", key_code, pressed);
+        log::trace!("Ignoring key event for now: {} ({})", key_code, pressed);
     }
 
     pub fn set_cpu_pc(&mut self, addr: u16) {
         self.cpu.borrow_mut().registers.program_counter = addr;
-        println!("Debug: Set CPU PC to ${:04X}", addr);
+        log::trace!("Debug: Set CPU PC to ${:04X}", addr);
     }
 
     pub fn get_ppu(&self) -> RefMut<'_, Ppu> {
@@ -541,7 +771,7 @@ impl Bus {
     }
 
     pub fn init_test_pattern(&mut self) {
-        println!("Initializing PPU VRAM with test pattern...");
+        log::debug!("Initializing PPU VRAM with test pattern...");
         let mut ppu = self.ppu.borrow_mut();
         for i in 0..960 { // Fill Name Table 0
             let tile_index = (i % 32) as u8;
@@ -559,7 +789,7 @@ impl Bus {
              ppu.write_palette(0x01 + i as u8, colors[i]);
         }
         ppu.write_palette(0x00, 0x0D);
-        println!("Test pattern VRAM initialization complete.");
+        log::debug!("Test pattern VRAM initialization complete.");
         self.test_pattern_rendered = true;
     }
 
@@ -624,6 +854,49 @@ impl Bus {
         Ok(frame)
     }
 
+    // Advance the APU and mapper timers/IRQ counters by the given number of CPU cycles
+    pub fn clock_peripherals(&self, cpu_cycles: u64) {
+        let dmc_fetch = {
+            let mut cart = self.cartridge.as_ref().map(|cart| cart.lock().unwrap());
+            let mut apu = self.apu.borrow_mut();
+            for _ in 0..cpu_cycles {
+                let expansion = match cart.as_mut() {
+                    Some(cart) => {
+                        cart.clock_cpu();
+                        cart.audio_output()
+                    }
+                    None => 0.0,
+                };
+                apu.clock(expansion);
+            }
+            apu.dmc_pending_fetch()
+        };
+        // DMC sample fetch (CPU stall cycles are not emulated)
+        if let Some(addr) = dmc_fetch {
+            let data = self.bus_read(addr);
+            self.apu.borrow_mut().dmc_fill_buffer(data);
+            if self.cdl.borrow().is_logging() {
+                if let Some(offset) = self.cartridge.as_ref().and_then(|c| c.lock().unwrap().prg_rom_offset(addr)) {
+                    self.cdl.borrow_mut().log_sample(offset);
+                }
+            }
+        }
+    }
+
+    pub fn take_audio_samples(&self) -> Vec<f32> {
+        self.apu.borrow_mut().take_samples()
+    }
+
+    // Shared handle to the inserted cartridge (used for mapper-specific controls)
+    pub fn cartridge(&self) -> Option<Arc<Mutex<Cartridge>>> {
+        self.cartridge.clone()
+    }
+
+    // Expansion audio level from the cartridge (e.g. FDS wavetable channel)
+    pub fn expansion_audio_output(&self) -> f32 {
+        self.cartridge.as_ref().map_or(0.0, |cart| cart.lock().unwrap().audio_output())
+    }
+
     pub fn is_rom_loaded(&self) -> bool {
         self.cartridge.is_some()
     }
@@ -640,6 +913,54 @@ impl Bus {
     }
 }
 
+impl SaveState for Bus {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.begin_section(b"BUS ");
+        w.write_u64(self.total_cycles);
+        w.write_bool(self.prev_nmi_line);
+        w.write_u32(self.oam_dma_cycles_remaining as u32);
+        w.write_u8(self.oam_dma_page);
+        w.write_u8(self.oam_dma_offset);
+        w.write_u8(self.oam_dma_data);
+        w.write_u8(self.region.to_state_code());
+        w.write_u32(self.ppu_dot_remainder);
+
+        self.cpu.borrow().save_state(w);
+        self.cpu_ram.borrow().save_state(w);
+        self.ppu.borrow().save_state(w);
+        self.controller1.borrow().save_state(w);
+        self.controller2.borrow().save_state(w);
+        self.apu.borrow().save_state(w);
+        if let Some(cart) = &self.cartridge {
+            cart.lock().unwrap().save_state(w);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.expect_section(b"BUS ")?;
+        self.total_cycles = r.read_u64()?;
+        self.prev_nmi_line = r.read_bool()?;
+        self.oam_dma_cycles_remaining = r.read_u32()? as usize;
+        self.oam_dma_page = r.read_u8()?;
+        self.oam_dma_offset = r.read_u8()?;
+        self.oam_dma_data = r.read_u8()?;
+        let region = Region::from_state_code(r.read_u8()?)?;
+        self.set_region(region);
+        self.ppu_dot_remainder = r.read_u32()?;
+
+        self.cpu.borrow_mut().load_state(r)?;
+        self.cpu_ram.borrow_mut().load_state(r)?;
+        self.ppu.borrow_mut().load_state(r)?;
+        self.controller1.borrow_mut().load_state(r)?;
+        self.controller2.borrow_mut().load_state(r)?;
+        self.apu.borrow_mut().load_state(r)?;
+        if let Some(cart) = &self.cartridge {
+            cart.lock().unwrap().load_state(r)?;
+        }
+        Ok(())
+    }
+}
+
 // BusAccessトレイトを公開定義
 pub trait BusAccess {
     fn read(&self, addr: u16) -> u8;
@@ -650,6 +971,16 @@ pub trait BusAccess {
     fn ppu_write_vram(&mut self, addr: u16, data: u8); // Add method for PPU VRAM/CHR writes
     fn get_mirroring(&self) -> Mirroring; // <<< NEW: Method to get current mirroring mode
     fn read_u16_zp(&self, addr: u16) -> u16; // ゼロページラップアラウンド付き 16 ビット読み込み
+    fn irq_pending(&self) -> bool; // Level-triggered IRQ line (mapper/APU)
+
+    // Read without side effects (traces, debugger views)
+    fn peek(&self, addr: u16) -> u8 {
+        self.read(addr)
+    }
+    // (scanline, dot) for trace output
+    fn ppu_position(&self) -> (isize, usize) {
+        (0, 0)
+    }
 
     // 16ビット読み込み用ヘルパー（デフォルト実装）
     fn read_u16(&self, addr: u16) -> u16 {
@@ -670,7 +1001,11 @@ pub trait BusAccess {
 // Bus構造体にBusAccessを実装
 impl BusAccess for Bus {
     fn read(&self, addr: u16) -> u8 {
-        self.bus_read(addr)
+        let value = self.bus_read(addr);
+        if self.cdl.borrow().is_logging() {
+            self.log_cdl_read(addr);
+        }
+        value
     }
     
     fn write(&mut self, addr: u16, data: u8) {
@@ -686,6 +1021,10 @@ impl BusAccess for Bus {
     }
 
     fn ppu_read_vram(&self, addr: u16) -> u8 {
+        // Only the PPU's own fetches happen while it is mutably borrowed (inside step_cycle)
+        if addr & 0x3FFF < 0x2000 && self.cdl.borrow().is_logging() && self.ppu.try_borrow().is_err() {
+            self.log_cdl_chr(addr, cdl::CHR_RENDERED);
+        }
         self.ppu_read_vram(addr)
     }
 
@@ -715,6 +1054,22 @@ impl BusAccess for Bus {
         self.cartridge.as_ref().map_or(Mirroring::Horizontal, |cart| cart.lock().unwrap().get_mirroring())
     }
 
+    fn peek(&self, addr: u16) -> u8 {
+        self.debug_read(addr)
+    }
+
+    fn ppu_position(&self) -> (isize, usize) {
+        let ppu = self.ppu.borrow();
+        // The pre-render line is -1 internally but the last line in nestest.log
+        let scanline = if ppu.scanline < 0 { self.region.scanlines_per_frame() - 1 } else { ppu.scanline };
+        (scanline, ppu.cycle)
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.apu.borrow().irq_pending()
+            || self.cartridge.as_ref().is_some_and(|cart| cart.lock().unwrap().irq_pending())
+    }
+
     fn read_u16_zp(&self, addr: u16) -> u16 {
         let lo_addr = addr & 0x00FF;
         let hi_addr = (addr.wrapping_add(1)) & 0x00FF;
@@ -723,3 +1078,48 @@ impl BusAccess for Bus {
         (hi << 8) | lo
     }
 }
+
+// Watch space of a PPU address
+fn ppu_watch_space(addr: u16) -> WatchSpace {
+    if addr & 0x3FFF >= 0x3F00 {
+        WatchSpace::Palette
+    } else {
+        WatchSpace::Vram
+    }
+}
+
+// Address spaces the memory editor can view and patch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemorySpace {
+    Cpu,     // $0000-$FFFF as the CPU sees it
+    Vram,    // PPU $0000-$3FFF (pattern tables, nametables, palette)
+    Palette, // 32 bytes
+    Oam,     // 256 bytes
+    PrgRom,
+    Chr,     // CHR ROM, or CHR RAM
+    PrgRam,
+}
+
+// Side-effect free view of the machine for debugger condition expressions
+pub struct DebugView<'a>(pub &'a Bus);
+
+impl Machine for DebugView<'_> {
+    fn peek(&self, addr: u16) -> u8 {
+        self.0.debug_read(addr)
+    }
+
+    fn ppu_position(&self) -> (isize, usize) {
+        BusAccess::ppu_position(self.0)
+    }
+
+    fn frame(&self) -> u64 {
+        self.0.ppu.borrow().frame_counter
+    }
+
+    fn prg_bank(&self, addr: u16) -> Option<usize> {
+        let cart = self.0.cartridge.as_ref()?;
+        let offset = cart.lock().unwrap().prg_rom_offset(addr)?;
+        Some(offset / 0x2000)
+    }
+}
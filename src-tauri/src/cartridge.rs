@@ -1,3 +1,5 @@
+use crate::fds::{FdsDisk, FdsMapper};
+use crate::savestate::{StateReader, StateWriter};
 use crate::Mirroring; // Mirroring enum is defined in main.rs
 
 // Trait for Memory Mappers
@@ -8,8 +10,57 @@ pub trait Mapper: Send + Sync {
     fn read_chr(&self, addr: u16) -> u8;
     fn write_chr(&mut self, addr: u16, data: u8);
     fn mirroring(&self) -> Mirroring;
-    // fn irq_state(&self) -> bool; // Add later if needed for specific mappers
-    // fn irq_clear(&mut self);    // Add later if needed
+    // CPU read with side effects (register reads that acknowledge IRQs etc.)
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        self.read_prg(addr)
+    }
+    // Called once per CPU cycle for mappers with timers/IRQ counters
+    fn cpu_clock(&mut self) {}
+    fn irq_pending(&self) -> bool {
+        false
+    }
+    // Expansion audio output (0.0 - 1.0)
+    fn audio_output(&self) -> f32 {
+        0.0
+    }
+    // Famicom Disk System controls (disk swapping)
+    fn disk_system(&mut self) -> Option<&mut FdsMapper> {
+        None
+    }
+    // PRG ROM image and where a CPU address currently maps into it (bank-aware debugger views)
+    fn prg_rom(&self) -> &[u8] {
+        &[]
+    }
+    fn prg_rom_offset(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+    // Same for CHR ROM and PPU pattern table addresses (None for CHR RAM)
+    fn chr_rom_len(&self) -> usize {
+        0
+    }
+    fn chr_rom_offset(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+    // Raw memory for the memory editor (writes don't go through mapper registers).
+    // CHR is the CHR ROM, or the CHR RAM on boards without one.
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+    fn chr(&self) -> &[u8] {
+        &[]
+    }
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+    fn prg_ram(&self) -> &[u8] {
+        &[]
+    }
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+    // Save state support: every mapper must write all of its mutable state
+    fn save_state(&self, w: &mut StateWriter);
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String>;
     // fn scanline(&mut self);     // Add later if needed for scanline counters
 }
 
@@ -20,6 +71,7 @@ struct Mapper0 {
     prg_rom: Vec<u8>,
     chr_rom: Vec<u8>, // Used if chr_banks > 0
     chr_ram: Vec<u8>, // Added for CHR RAM support (8KB)
+    prg_ram: Vec<u8>, // 8KB at $6000-$7FFF (Family BASIC boards, test ROM result output)
     mirroring: Mirroring,
     // BG切り替えスイッチ対応
     bg_switch_enabled: bool,
@@ -29,6 +81,9 @@ struct Mapper0 {
 impl Mapper for Mapper0 {
     fn read_prg(&self, addr: u16) -> u8 {
         // PRGメモリは0x8000-0xFFFFの範囲にマッピングされるべき
+        if (0x6000..0x8000).contains(&addr) {
+            return self.prg_ram[(addr - 0x6000) as usize];
+        }
         if addr < 0x8000 {
             // 一部のゲームは低アドレス領域も使用することがある
             // 警告を出さずに0を返す
@@ -51,13 +106,17 @@ impl Mapper for Mapper0 {
             // Handle potential out-of-bounds read, although masking should prevent this
              // Limit log spam
             if addr % 0x100 == 0 {
-                 eprintln!("WARN: Read out of bounds PRG ROM access at {:04X} (Mapped: {}, Size: {})", addr, mapped_addr, self.prg_rom.len());
+                 log::warn!("WARN: Read out of bounds PRG ROM access at {:04X} (Mapped: {}, Size: {})", addr, mapped_addr, self.prg_rom.len());
             }
             0xFF // Return 0xFF (often represents open bus behavior)
         }
     }
 
     fn write_prg(&mut self, addr: u16, data: u8) {
+        if (0x6000..0x8000).contains(&addr) {
+            self.prg_ram[(addr - 0x6000) as usize] = data;
+            return;
+        }
         // マッパー0は通常PRG ROMに書き込めないが、特殊な機能を追加
         // BG切り替えスイッチ機能の実装
         // if addr >= 0x8000 && addr <= 0x8FFF { // <<< この if ブロック全体をコメントアウト
@@ -94,7 +153,7 @@ impl Mapper for Mapper0 {
                 // ★★★ ここまで ★★★
                 self.chr_ram[index]
             } else {
-                eprintln!("WARN: Read out of bounds CHR RAM access at {:04X} (Index: {}, Size: {})", addr, index, self.chr_ram.len());
+                log::warn!("WARN: Read out of bounds CHR RAM access at {:04X} (Index: {}, Size: {})", addr, index, self.chr_ram.len());
                 0
             }
         } else {
@@ -136,7 +195,7 @@ impl Mapper for Mapper0 {
                     self.chr_rom[index]
                 } else {
                      if addr % 0x100 == 0 { // Limit log spam
-                        eprintln!("WARN: Read out of bounds CHR ROM access at {:04X} (Index: {}, Size: {})", addr, index, self.chr_rom.len());
+                        log::warn!("WARN: Read out of bounds CHR ROM access at {:04X} (Index: {}, Size: {})", addr, index, self.chr_rom.len());
                      }
                     0
                 }
@@ -155,12 +214,12 @@ impl Mapper for Mapper0 {
             if index < self.chr_ram.len() {
                 // ★★★ CHR RAM 書き込みログ ★★★
                 // Limit log spam if necessary
-                 println!("--- CHR RAM Write: OrigAddr:{:04X} Addr:{:04X} Index:{} Size:{} Data:{:02X} ---",
+                 log::trace!("--- CHR RAM Write: OrigAddr:{:04X} Addr:{:04X} Index:{} Size:{} Data:{:02X} ---",
                           original_addr, addr, index, self.chr_ram.len(), data);
                 // ★★★ ここまで ★★★
                 self.chr_ram[index] = data;
             } else {
-                eprintln!("WARN: Write out of bounds CHR RAM access at {:04X} (Index: {}, Size: {})", addr, index, self.chr_ram.len());
+                log::warn!("WARN: Write out of bounds CHR RAM access at {:04X} (Index: {}, Size: {})", addr, index, self.chr_ram.len());
             }
         } else {
             // CHR ROM is generally not writable
@@ -171,6 +230,61 @@ impl Mapper for Mapper0 {
     fn mirroring(&self) -> Mirroring {
         self.mirroring
     }
+
+    fn prg_rom(&self) -> &[u8] {
+        &self.prg_rom
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        if addr < 0x8000 {
+            return None;
+        }
+        let mask = if self.prg_banks == 1 { 0x3FFF } else { 0x7FFF };
+        Some((addr & mask) as usize).filter(|&offset| offset < self.prg_rom.len())
+    }
+
+    fn chr_rom_len(&self) -> usize {
+        if self.chr_banks == 0 { 0 } else { self.chr_rom.len() }
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        Some((addr & 0x1FFF) as usize).filter(|&offset| offset < self.chr_rom_len())
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr(&self) -> &[u8] {
+        if self.chr_banks == 0 { &self.chr_ram } else { &self.chr_rom }
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        if self.chr_banks == 0 { &mut self.chr_ram } else { &mut self.chr_rom }
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.chr_ram);
+        w.write_bytes(&self.prg_ram);
+        w.write_bool(self.bg_switch_enabled);
+        w.write_u8(self.bg_bank_selected);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_bytes_into(&mut self.chr_ram)?;
+        r.read_bytes_into(&mut self.prg_ram)?;
+        self.bg_switch_enabled = r.read_bool()?;
+        self.bg_bank_selected = r.read_u8()?;
+        Ok(())
+    }
 }
 
 // Cartridge Structure
@@ -183,6 +297,9 @@ pub struct Cartridge {
     mirroring: Mirroring, // Store mirroring determined at load time
 }
 
+// iNES mapper number reserved for the Famicom Disk System
+pub const FDS_MAPPER_ID: u8 = 20;
+
 impl Cartridge {
     pub fn new(
         prg_rom: Vec<u8>,
@@ -209,7 +326,7 @@ impl Cartridge {
                 // Create Mapper 0 instance
                 let mut chr_ram = vec![0u8; 0]; // Initialize as empty
                 if chr_banks == 0 {
-                     println!("Mapper 0: Using 8KB CHR RAM");
+                     log::debug!("Mapper 0: Using 8KB CHR RAM");
                     chr_ram = vec![0u8; 8192]; // Allocate 8KB if no CHR ROM
                 }
                 let chr_data = if chr_banks == 0 { Vec::new() } else { chr_rom }; // Pass empty Vec if CHR RAM
@@ -220,6 +337,7 @@ impl Cartridge {
                     prg_rom,
                     chr_rom: chr_data,
                     chr_ram, // Add chr_ram field
+                    prg_ram: vec![0u8; 8192],
                     mirroring,
                     // BG切り替えスイッチ対応
                     bg_switch_enabled: false,
@@ -232,7 +350,7 @@ impl Cartridge {
             }
         };
 
-        println!(
+        log::debug!(
             "Cartridge loaded: Mapper {}, PRG Banks: {}, CHR Banks: {}, Mirroring: {:?}",
             mapper_id, prg_banks, chr_banks, mirroring
         );
@@ -246,6 +364,21 @@ impl Cartridge {
         })
     }
 
+    // Famicom Disk System: BIOS + RAM adapter instead of a ROM cartridge
+    pub fn new_fds(bios: Vec<u8>, disk: FdsDisk) -> Result<Self, String> {
+        let mapper = FdsMapper::new(bios, disk)?;
+        let mirroring = mapper.mirroring();
+        log::debug!("Cartridge loaded: Famicom Disk System, Sides: {}", mapper.disk_info().side_count);
+
+        Ok(Self {
+            mapper_id: FDS_MAPPER_ID,
+            prg_banks: 0,
+            chr_banks: 0,
+            mapper: Box::new(mapper),
+            mirroring,
+        })
+    }
+
     // Read/Write methods delegate to the contained mapper
     pub fn read_prg(&self, addr: u16) -> u8 {
         self.mapper.read_prg(addr)
@@ -263,12 +396,87 @@ impl Cartridge {
         self.mapper.write_chr(addr, data);
     }
 
+    pub fn prg_rom(&self) -> &[u8] {
+        self.mapper.prg_rom()
+    }
+
+    pub fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.mapper.prg_rom_offset(addr)
+    }
+
+    pub fn chr_rom_len(&self) -> usize {
+        self.mapper.chr_rom_len()
+    }
+
+    pub fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.mapper.chr_rom_offset(addr)
+    }
+
+    pub fn prg_rom_mut(&mut self) -> &mut [u8] {
+        self.mapper.prg_rom_mut()
+    }
+
+    pub fn chr(&self) -> &[u8] {
+        self.mapper.chr()
+    }
+
+    pub fn chr_mut(&mut self) -> &mut [u8] {
+        self.mapper.chr_mut()
+    }
+
+    pub fn prg_ram(&self) -> &[u8] {
+        self.mapper.prg_ram()
+    }
+
+    pub fn prg_ram_mut(&mut self) -> &mut [u8] {
+        self.mapper.prg_ram_mut()
+    }
+
+    pub fn cpu_read(&mut self, addr: u16) -> u8 {
+        self.mapper.cpu_read(addr)
+    }
+
+    pub fn clock_cpu(&mut self) {
+        self.mapper.cpu_clock();
+    }
+
+    pub fn irq_pending(&self) -> bool {
+        self.mapper.irq_pending()
+    }
+
+    pub fn audio_output(&self) -> f32 {
+        self.mapper.audio_output()
+    }
+
+    pub fn disk_system(&mut self) -> Option<&mut FdsMapper> {
+        self.mapper.disk_system()
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.begin_section(b"CART");
+        w.write_u8(self.mapper_id);
+        self.mapper.save_state(w);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.expect_section(b"CART")?;
+        let mapper_id = r.read_u8()?;
+        if mapper_id != self.mapper_id {
+            return Err(format!(
+                "Save state is for mapper {}, but the loaded cartridge uses mapper {}",
+                mapper_id, self.mapper_id
+            ));
+        }
+        self.mapper.load_state(r)
+    }
+
     pub fn mirror_mode(&self) -> Mirroring {
         self.mirroring // Return stored mirroring mode
     }
 
+    // Mappers may switch mirroring at runtime, so ask the mapper
     pub fn get_mirroring(&self) -> Mirroring {
-        self.mirroring
+        self.mapper.mirroring()
     }
 
     pub fn get_mapper_id(&self) -> u8 {
@@ -0,0 +1,287 @@
+// Code/Data Logger: records how each PRG and CHR ROM byte has been used, in FCEUX's .cdl format
+// (one flag byte per PRG ROM byte, followed by one per CHR ROM byte).
+//
+// PRG flags (xPdcAADC):
+//   C  0x01  executed as code (opcode or operand)
+//   D  0x02  read as data by the CPU
+//   AA 0x0C  8KB CPU window it was last accessed through (0: $8000, 1: $A000, 2: $C000, 3: $E000)
+//   c  0x10  target of an indirect jump, JMP ($nnnn)
+//   d  0x20  read through a pointer, (zp,X) / (zp),Y
+//   P  0x40  played as a DMC sample
+// CHR flags:
+//   0x01  fetched by the PPU while rendering
+//   0x02  read by the CPU through $2007
+use crate::cpu::{AddressingMode, Cpu6502};
+use crate::trace::operand_length;
+use serde::Serialize;
+use std::path::Path;
+
+pub const PRG_CODE: u8 = 0x01;
+pub const PRG_DATA: u8 = 0x02;
+pub const PRG_WINDOW_MASK: u8 = 0x0C;
+pub const PRG_INDIRECT_CODE: u8 = 0x10;
+pub const PRG_INDIRECT_DATA: u8 = 0x20;
+pub const PRG_PCM: u8 = 0x40;
+pub const CHR_RENDERED: u8 = 0x01;
+pub const CHR_READ: u8 = 0x02;
+
+const OPCODE_JMP_INDIRECT: u8 = 0x6C;
+
+// The instruction the CPU is executing, to tell its own bytes from the data it reads
+#[derive(Debug, Clone, Copy)]
+struct CurrentInstruction {
+    pc: u16,
+    length: u16,
+    reads_operand: bool, // False for stores and jumps, whose target the CPU core still reads
+    pointer: Option<u16>, // JMP ($nnnn): the pointer is the only data it reads
+    indirect_data: bool, // (zp,X) / (zp),Y: ROM data it reads came through a pointer
+    indirect_target: bool, // Reached through JMP ($nnnn)
+}
+
+impl CurrentInstruction {
+    fn reads_data_at(&self, addr: u16) -> bool {
+        match self.pointer {
+            // The high byte comes from the same page (6502 JMP indirect bug)
+            Some(pointer) => addr == pointer || addr == (pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF),
+            None => self.reads_operand,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CdlStats {
+    pub logging: bool,
+    pub prg_size: usize,
+    pub prg_code: usize,
+    pub prg_data: usize,
+    pub prg_unused: usize,
+    pub chr_size: usize,
+    pub chr_rendered: usize,
+    pub chr_read: usize,
+    pub chr_unused: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct CodeDataLog {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    logging: bool,
+    current: Option<CurrentInstruction>,
+    jumped_indirect: bool, // The last instruction was JMP ($nnnn)
+}
+
+impl CodeDataLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A new ROM: start over with one flag byte per ROM byte (CHR RAM has no entries)
+    pub fn resize(&mut self, prg_size: usize, chr_size: usize) {
+        self.prg = vec![0; prg_size];
+        self.chr = vec![0; chr_size];
+        self.current = None;
+        self.jumped_indirect = false;
+    }
+
+    pub fn reset(&mut self) {
+        self.prg.fill(0);
+        self.chr.fill(0);
+    }
+
+    pub fn is_logging(&self) -> bool {
+        self.logging
+    }
+
+    pub fn set_logging(&mut self, logging: bool) {
+        self.logging = logging;
+        self.current = None;
+        self.jumped_indirect = false;
+    }
+
+    // Called before the CPU runs the instruction at `pc`; `operand` is the (little endian) bytes after the opcode
+    pub fn begin_instruction(&mut self, pc: u16, opcode: u8, operand: u16) {
+        let (mode, _, name) = Cpu6502::opcode_info(opcode);
+        self.current = Some(CurrentInstruction {
+            pc,
+            length: 1 + operand_length(mode),
+            reads_operand: !matches!(name, "STA" | "STX" | "STY" | "SAX*" | "JMP" | "JSR"),
+            pointer: (opcode == OPCODE_JMP_INDIRECT).then_some(operand),
+            indirect_data: matches!(mode, AddressingMode::IndexedIndirect | AddressingMode::IndirectIndexed),
+            indirect_target: self.jumped_indirect,
+        });
+        self.jumped_indirect = opcode == OPCODE_JMP_INDIRECT;
+    }
+
+    // A CPU read of PRG ROM: the current instruction's bytes are code, anything else is data
+    pub fn log_prg_read(&mut self, addr: u16, prg_offset: usize) {
+        let window = ((addr >> 13) as u8 & 0x03) << 2;
+        let mut flags = PRG_DATA;
+        if let Some(current) = self.current {
+            if addr.wrapping_sub(current.pc) < current.length {
+                flags = PRG_CODE;
+                if addr == current.pc && current.indirect_target {
+                    flags |= PRG_INDIRECT_CODE;
+                }
+            } else if !current.reads_data_at(addr) {
+                return;
+            } else if current.indirect_data {
+                flags |= PRG_INDIRECT_DATA;
+            }
+        }
+        if let Some(entry) = self.prg.get_mut(prg_offset) {
+            *entry = (*entry & !PRG_WINDOW_MASK) | flags | window;
+        }
+    }
+
+    pub fn log_sample(&mut self, prg_offset: usize) {
+        if let Some(entry) = self.prg.get_mut(prg_offset) {
+            *entry |= PRG_PCM;
+        }
+    }
+
+    pub fn log_chr(&mut self, chr_offset: usize, flags: u8) {
+        if let Some(entry) = self.chr.get_mut(chr_offset) {
+            *entry |= flags;
+        }
+    }
+
+    pub fn prg_flags(&self, prg_offset: usize) -> u8 {
+        self.prg.get(prg_offset).copied().unwrap_or(0)
+    }
+
+    // Read as data but never executed: the disassembler shows these as .byte
+    pub fn is_data(&self, prg_offset: usize) -> bool {
+        self.prg_flags(prg_offset) & (PRG_CODE | PRG_DATA) == PRG_DATA
+    }
+
+    pub fn stats(&self) -> CdlStats {
+        let count = |log: &[u8], flag: u8| log.iter().filter(|&&f| f & flag != 0).count();
+        CdlStats {
+            logging: self.logging,
+            prg_size: self.prg.len(),
+            prg_code: count(&self.prg, PRG_CODE),
+            prg_data: count(&self.prg, PRG_DATA | PRG_PCM),
+            prg_unused: self.prg.iter().filter(|&&f| f & (PRG_CODE | PRG_DATA | PRG_PCM) == 0).count(),
+            chr_size: self.chr.len(),
+            chr_rendered: count(&self.chr, CHR_RENDERED),
+            chr_read: count(&self.chr, CHR_READ),
+            chr_unused: self.chr.iter().filter(|&&f| f == 0).count(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if self.prg.is_empty() {
+            return Err("No ROM loaded".to_string());
+        }
+        let mut data = Vec::with_capacity(self.prg.len() + self.chr.len());
+        data.extend_from_slice(&self.prg);
+        data.extend_from_slice(&self.chr);
+        std::fs::write(path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    // Replaces the current log; the file has to match the loaded ROM's PRG + CHR size
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        if self.prg.is_empty() {
+            return Err("No ROM loaded".to_string());
+        }
+        let data = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let expected = self.prg.len() + self.chr.len();
+        if data.len() != expected {
+            return Err(format!("{} is {} bytes, the loaded ROM needs {} (PRG {} + CHR {})",
+                path.display(), data.len(), expected, self.prg.len(), self.chr.len()));
+        }
+        let (prg, chr) = data.split_at(self.prg.len());
+        self.prg.copy_from_slice(prg);
+        self.chr.copy_from_slice(chr);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LDA_ABSOLUTE: u8 = 0xAD;
+    const STA_ABSOLUTE: u8 = 0x8D;
+    const LDA_INDIRECT_Y: u8 = 0xB1;
+    const NOP: u8 = 0xEA;
+
+    fn log() -> CodeDataLog {
+        let mut log = CodeDataLog::new();
+        log.resize(0x8000, 0x2000);
+        log.set_logging(true);
+        log
+    }
+
+    // The CPU fetches every byte of the instruction; PRG ROM offset = CPU address - $8000
+    fn execute(log: &mut CodeDataLog, pc: u16, opcode: u8, operand: u16) {
+        log.begin_instruction(pc, opcode, operand);
+        let (mode, _, _) = Cpu6502::opcode_info(opcode);
+        for addr in pc..pc + 1 + operand_length(mode) {
+            log.log_prg_read(addr, addr as usize - 0x8000);
+        }
+    }
+
+    #[test]
+    fn code_and_data_reads() {
+        let mut log = log();
+        execute(&mut log, 0xC000, LDA_ABSOLUTE, 0x8010);
+        log.log_prg_read(0x8010, 0x0010);
+        for offset in 0x4000..0x4003 {
+            assert_eq!(log.prg_flags(offset), PRG_CODE | 0x08, "${:04X}", offset); // $C000 window
+        }
+        assert_eq!(log.prg_flags(0x0010), PRG_DATA); // $8000 window
+        assert!(log.is_data(0x0010));
+
+        // The window bits follow the latest access, the usage bits accumulate
+        execute(&mut log, 0xC003, LDA_ABSOLUTE, 0xE010);
+        log.log_prg_read(0xE010, 0x0010);
+        assert_eq!(log.prg_flags(0x0010), PRG_DATA | 0x0C);
+
+        // A store's target isn't data it reads
+        execute(&mut log, 0xC006, STA_ABSOLUTE, 0x8020);
+        log.log_prg_read(0x8020, 0x0020);
+        assert_eq!(log.prg_flags(0x0020), 0);
+    }
+
+    #[test]
+    fn indirect_marks() {
+        let mut log = log();
+        // JMP ($80FF) reads its pointer from $80FF and (page wrap) $8000, then lands on $E000
+        execute(&mut log, 0xC000, OPCODE_JMP_INDIRECT, 0x80FF);
+        log.log_prg_read(0x80FF, 0x00FF);
+        log.log_prg_read(0x8000, 0x0000);
+        log.log_prg_read(0x8100, 0x0100);
+        assert_eq!(log.prg_flags(0x00FF), PRG_DATA);
+        assert_eq!(log.prg_flags(0x0000), PRG_DATA);
+        assert_eq!(log.prg_flags(0x0100), 0);
+
+        execute(&mut log, 0xE000, NOP, 0);
+        assert_eq!(log.prg_flags(0x6000), PRG_CODE | PRG_INDIRECT_CODE | 0x0C);
+        // Only the instruction right after the jump is its target
+        execute(&mut log, 0xE001, LDA_INDIRECT_Y, 0x00);
+        assert_eq!(log.prg_flags(0x6001), PRG_CODE | 0x0C);
+
+        // LDA ($00),Y reading ROM at $A005
+        log.log_prg_read(0xA005, 0x2005);
+        assert_eq!(log.prg_flags(0x2005), PRG_DATA | PRG_INDIRECT_DATA | 0x04);
+        assert!(log.is_data(0x2005));
+    }
+
+    #[test]
+    fn samples_and_chr() {
+        let mut log = log();
+        log.log_sample(0x3000);
+        assert_eq!(log.prg_flags(0x3000), PRG_PCM);
+        log.log_chr(0x0010, CHR_RENDERED);
+        log.log_chr(0x0010, CHR_READ);
+        log.log_chr(0x0020, CHR_READ);
+        // Offsets past the ROM are ignored
+        log.log_chr(0x2000, CHR_READ);
+        log.log_prg_read(0x8000, 0x8000);
+
+        let stats = log.stats();
+        assert_eq!((stats.prg_data, stats.prg_code, stats.prg_unused), (1, 0, 0x7FFF));
+        assert_eq!((stats.chr_rendered, stats.chr_read, stats.chr_unused), (1, 2, 0x1FFE));
+    }
+}
@@ -0,0 +1,224 @@
+// Cheat codes.
+//   Game Genie: 6 or 8 letters; replaces what the CPU reads from one PRG ROM address ($8000-$FFFF),
+//               8-letter codes only when the original byte matches a compare value.
+//   Pro Action Replay style RAM codes: "AAAA:VV" or "AAAAVV" (hex); the value is written every frame
+//               to internal RAM ($0000-$07FF) or PRG RAM ($6000-$7FFF).
+// Cheat lists are stored per ROM, in a JSON file named after the ROM's CRC32.
+use crate::ramsearch::{self, ValueSize};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const GAME_GENIE_LETTERS: &[u8; 16] = b"APZLGITYEOXUKSVN";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum CheatEffect {
+    Rom { address: u16, value: u8, compare: Option<u8> },
+    Ram { address: u16, value: u8 },
+}
+
+// A Game Genie substitution applied to CPU reads of PRG ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomPatch {
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+impl RomPatch {
+    pub fn apply(&self, addr: u16, original: u8) -> Option<u8> {
+        (addr == self.address && self.compare.is_none_or(|c| c == original)).then_some(self.value)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cheat {
+    pub code: String, // Normalized: upper case Game Genie letters, or "AAAA:VV"
+    pub description: String,
+    pub enabled: bool,
+    pub effect: CheatEffect,
+}
+
+impl Cheat {
+    pub fn new(code: &str, description: &str) -> Result<Self, String> {
+        let (code, effect) = decode(code)?;
+        Ok(Self { code, description: description.trim().to_string(), enabled: true, effect })
+    }
+}
+
+// Returns the normalized code and what it does
+pub fn decode(code: &str) -> Result<(String, CheatEffect), String> {
+    let compact: String = code.chars().filter(|c| !c.is_whitespace() && *c != '-').collect::<String>().to_ascii_uppercase();
+    if compact.is_empty() {
+        return Err("Empty cheat code".to_string());
+    }
+    if compact.bytes().all(|c| GAME_GENIE_LETTERS.contains(&c)) && matches!(compact.len(), 6 | 8) {
+        return Ok((compact.clone(), decode_game_genie(&compact)));
+    }
+    let hex = compact.replace(':', "");
+    if hex.len() == 6 && hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+        let address = u16::from_str_radix(&hex[..4], 16).unwrap();
+        let value = u8::from_str_radix(&hex[4..], 16).unwrap();
+        if address >= 0x8000 {
+            return Err(format!("RAM code address ${:04X} is in ROM; use a Game Genie code instead", address));
+        }
+        ramsearch::check_ram_address(address, ValueSize::Byte)?;
+        return Ok((format!("{:04X}:{:02X}", address, value), CheatEffect::Ram { address, value }));
+    }
+    Err(format!("Not a Game Genie (6/8 letters) or RAM (AAAA:VV) code: {}", code.trim()))
+}
+
+fn decode_game_genie(code: &str) -> CheatEffect {
+    let n: Vec<u16> = code.bytes().map(|c| GAME_GENIE_LETTERS.iter().position(|&l| l == c).unwrap() as u16).collect();
+    let address = 0x8000
+        | ((n[3] & 7) << 12)
+        | ((n[5] & 7) << 8)
+        | ((n[4] & 8) << 8)
+        | ((n[2] & 7) << 4)
+        | ((n[1] & 8) << 4)
+        | (n[4] & 7)
+        | (n[3] & 8);
+    let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7);
+    if n.len() == 8 {
+        let compare = ((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8);
+        CheatEffect::Rom { address, value: (value | (n[7] & 8)) as u8, compare: Some(compare as u8) }
+    } else {
+        CheatEffect::Rom { address, value: (value | (n[5] & 8)) as u8, compare: None }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CheatList {
+    cheats: Vec<Cheat>,
+    path: Option<PathBuf>, // File for the loaded ROM
+}
+
+impl CheatList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Switch to the list stored for a ROM (empty if there is no file yet).
+    // On error the previous list stays active, so a broken file is never overwritten by a later save.
+    pub fn load_for_rom(&mut self, dir: &Path, rom_crc32: u32) -> Result<(), String> {
+        let path = dir.join(format!("{:08X}.json", rom_crc32));
+        let mut cheats = Vec::new();
+        if path.exists() {
+            let text = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let stored: Vec<Cheat> =
+                serde_json::from_str(&text).map_err(|e| format!("Invalid cheat file {}: {}", path.display(), e))?;
+            // Decode again so a hand-edited file can't disagree with its codes
+            for cheat in stored {
+                let mut decoded = Cheat::new(&cheat.code, &cheat.description)
+                    .map_err(|e| format!("Invalid cheat file {}: {}", path.display(), e))?;
+                decoded.enabled = cheat.enabled;
+                cheats.push(decoded);
+            }
+        }
+        self.cheats = cheats;
+        self.path = Some(path);
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else { return Err("No ROM loaded".to_string()) };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let text = serde_json::to_string_pretty(&self.cheats).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    // Adding a code that is already in the list updates its description and enables it
+    pub fn add(&mut self, code: &str, description: &str) -> Result<Cheat, String> {
+        let cheat = Cheat::new(code, description)?;
+        match self.cheats.iter_mut().find(|c| c.code == cheat.code) {
+            Some(existing) => *existing = cheat.clone(),
+            None => self.cheats.push(cheat.clone()),
+        }
+        self.save()?;
+        Ok(cheat)
+    }
+
+    pub fn remove(&mut self, code: &str) -> Result<(), String> {
+        let index = self.index_of(code)?;
+        self.cheats.remove(index);
+        self.save()
+    }
+
+    pub fn set_enabled(&mut self, code: &str, enabled: bool) -> Result<(), String> {
+        let index = self.index_of(code)?;
+        self.cheats[index].enabled = enabled;
+        self.save()
+    }
+
+    fn index_of(&self, code: &str) -> Result<usize, String> {
+        let (code, _) = decode(code)?;
+        self.cheats.iter().position(|c| c.code == code).ok_or_else(|| format!("No such cheat: {}", code))
+    }
+
+    pub fn all(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn rom_patches(&self) -> Vec<RomPatch> {
+        self.enabled()
+            .filter_map(|effect| match effect {
+                CheatEffect::Rom { address, value, compare } => Some(RomPatch { address, value, compare }),
+                CheatEffect::Ram { .. } => None,
+            })
+            .collect()
+    }
+
+    // (address, value) pairs to write every frame
+    pub fn ram_writes(&self) -> Vec<(u16, u8)> {
+        self.enabled()
+            .filter_map(|effect| match effect {
+                CheatEffect::Ram { address, value } => Some((address, value)),
+                CheatEffect::Rom { .. } => None,
+            })
+            .collect()
+    }
+
+    fn enabled(&self) -> impl Iterator<Item = CheatEffect> + '_ {
+        self.cheats.iter().filter(|c| c.enabled).map(|c| c.effect)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(code: &str) -> CheatEffect {
+        decode(code).unwrap().1
+    }
+
+    #[test]
+    fn six_letter_game_genie() {
+        // Example from the NESdev wiki
+        assert_eq!(effect("SXIOPO"), CheatEffect::Rom { address: 0x91D9, value: 0xAD, compare: None });
+        assert_eq!(decode("sxi-opo").unwrap().0, "SXIOPO");
+    }
+
+    #[test]
+    fn eight_letter_game_genie() {
+        // Same address as SXIOPO; the value's high bit moves to the 8th letter and the compare value
+        // comes from letters 6-8: compare = (7&7)<<4 | (Z&8)<<4 | (Z&7) | (O&8)
+        assert_eq!(effect("SXIOPOZA"), CheatEffect::Rom { address: 0x91D9, value: 0xA5, compare: Some(0x0A) });
+        assert_eq!(effect("SXIOPOZE"), CheatEffect::Rom { address: 0x91D9, value: 0xAD, compare: Some(0x0A) });
+
+        let patch = RomPatch { address: 0x91D9, value: 0xA5, compare: Some(0x0A) };
+        assert_eq!(patch.apply(0x91D9, 0x0A), Some(0xA5));
+        assert_eq!(patch.apply(0x91D9, 0x0B), None);
+        assert_eq!(patch.apply(0x91DA, 0x0A), None);
+    }
+
+    #[test]
+    fn ram_codes() {
+        assert_eq!(decode("0075:09").unwrap(), ("0075:09".to_string(), CheatEffect::Ram { address: 0x75, value: 9 }));
+        assert_eq!(effect("7f00ff"), CheatEffect::Ram { address: 0x7F00, value: 0xFF });
+        for code in ["2000:00", "4016:01", "0800:00", "8000:EA", "12345", "SXIOP"] {
+            assert!(decode(code).is_err(), "{} should be rejected", code);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use crate::savestate::{SaveState, StateReader, StateWriter};
 
 // Define button mapping (consistent with many emulators)
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -61,6 +62,23 @@ impl Controller {
         response
     }
 
+    // Same bit `read` would return, without advancing the shift register (for debuggers)
+    pub fn peek(&self) -> u8 {
+        if self.button_index > 7 {
+            return 1;
+        }
+        (self.button_states >> self.button_index) & 1
+    }
+
+    // All 8 buttons as a bitfield (bit 0 = A ... bit 7 = Right)
+    pub fn button_states(&self) -> u8 {
+        self.button_states
+    }
+
+    pub fn set_button_states(&mut self, states: u8) {
+        self.button_states = states;
+    }
+
     // Set the state of a specific button (called by frontend input handler)
     pub fn set_button_state(&mut self, button: Button, pressed: bool) {
         let bit = match button {
@@ -80,4 +98,21 @@ impl Controller {
             self.button_states &= !(1 << bit);
         }
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for Controller {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.begin_section(b"CTRL");
+        w.write_bool(self.strobe);
+        w.write_u8(self.button_index);
+        w.write_u8(self.button_states);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.expect_section(b"CTRL")?;
+        self.strobe = r.read_bool()?;
+        self.button_index = r.read_u8()?;
+        self.button_states = r.read_u8()?;
+        Ok(())
+    }
+}
@@ -3,6 +3,8 @@ use log;
 // use crate::bus::Bus; // Use Bus instead of Memory // Keep commented or adjust if Bus is directly used
 // use crate::bus::Bus; // ★★★ Use Bus directly ★★★ // Remove direct Bus dependency
 use crate::bus::BusAccess; // ★★★ 追加: bus.rs の BusAccess を使用 ★★★
+use crate::savestate::{SaveState, StateReader, StateWriter};
+use crate::trace::{self, CpuTracer};
 // use crate::debugger::Debugger; // Debugger integration can be added later
 
 // バス操作を表す Enum (削除)
@@ -78,12 +80,15 @@ pub const FLAG_OVERFLOW: u8 = 1 << 6;
 pub const FLAG_NEGATIVE: u8 = 1 << 7;
 
 // The 6502 CPU core
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Serialize)]
 pub struct Cpu6502 {
     pub registers: Registers,
     pub cycles: u8,
     nmi_pending: bool,
     brk_executed: bool,
+    pub total_cycles: u64, // CPU cycles since power-on (nestest.log's CYC)
+    #[serde(skip)]
+    pub tracer: Option<CpuTracer>, // Execution trace, see trace.rs (owned, so logging needs no lock)
 }
 
 // DEBUGフラグの設定
@@ -114,15 +119,17 @@ impl Cpu6502 {
             cycles: 0,
             nmi_pending: false,
             brk_executed: false,
+            total_cycles: 0,
+            tracer: None,
         }
     }
 
     // Reset the CPU state
     pub fn reset(&mut self, bus: &mut impl BusAccess) {
-        println!("CPU Reset started...");
+        log::debug!("CPU Reset started...");
         // Fetch the reset vector from memory addresses $FFFC and $FFFD
         let reset_vector = bus.read_u16(RESET_VECTOR_ADDR);
-        println!("[CPU Reset] Read reset vector ${:04X} from ${:04X}", reset_vector, RESET_VECTOR_ADDR);
+        log::debug!("[CPU Reset] Read reset vector ${:04X} from ${:04X}", reset_vector, RESET_VECTOR_ADDR);
         self.registers.program_counter = reset_vector;
 
         // Reset registers to initial state
@@ -135,9 +142,10 @@ impl Cpu6502 {
 
         // Reset cycle count. Reset typically takes 8 cycles.
         self.cycles = 8;
+        self.total_cycles = 7; // nestest.log starts counting at 7 after reset
         self.nmi_pending = false;
         self.brk_executed = false;
-        println!("CPU Reset complete: PC set to ${:04X}, Status: ${:02X}", self.registers.program_counter, self.registers.status);
+        log::debug!("CPU Reset complete: PC set to ${:04X}, Status: ${:02X}", self.registers.program_counter, self.registers.status);
     }
 
     // --- Restore Old Bus Access Helpers (if needed, though BusAccess is preferred) ---
@@ -184,6 +192,12 @@ impl Cpu6502 {
 
     // --- Restore Original Step Method ---
     pub fn step(&mut self, bus: &mut impl BusAccess) -> u8 {
+        let cycles = self.step_instruction(bus);
+        self.total_cycles += cycles as u64;
+        cycles
+    }
+
+    fn step_instruction(&mut self, bus: &mut impl BusAccess) -> u8 {
         // --- Add log BEFORE fetching opcode ---
         // println!("[CPU Step Start] PC=${:04X}", self.registers.program_counter); // Keep this log <-- Remove
 
@@ -200,6 +214,13 @@ impl Cpu6502 {
             return self.cycles;
         }
 
+        if self.tracer.is_some() {
+            let line = trace::format_nestest_line(self, bus);
+            if let Some(tracer) = self.tracer.as_mut() {
+                tracer.log(line);
+            }
+        }
+
         self.cycles = 0;
         let current_pc = self.registers.program_counter; // Store PC before incrementing
 
@@ -252,11 +273,10 @@ impl Cpu6502 {
     }
 
     // IRQが必要かチェックする関数
-    fn check_irq(&self, _bus: &impl BusAccess) -> bool {
+    fn check_irq(&self, bus: &impl BusAccess) -> bool {
         // ここでハードウェアIRQ信号をチェックする
         // NESでは通常、マッパーかAPUがIRQを生成
-        // 現在は単純に偽を返す
-        false
+        bus.irq_pending()
     }
 
     // IRQ処理を行う関数
@@ -284,6 +304,11 @@ impl Cpu6502 {
     // ダミーのデコード関数（実際の命令情報を返す必要がある）
     // TODO: Populate with all opcodes and correct cycle counts / page crossing info
     fn decode_opcode(&self, opcode: u8) -> (AddressingMode, u8, &'static str) {
+        Self::opcode_info(opcode)
+    }
+
+    // (addressing mode, base cycles, mnemonic); unofficial opcodes end in '*'
+    pub fn opcode_info(opcode: u8) -> (AddressingMode, u8, &'static str) {
         match opcode {
             // Official Opcodes (Partial List)
             0x00 => (AddressingMode::Implied, 7, "BRK"),
@@ -589,7 +614,7 @@ impl Cpu6502 {
             // --- Store Instructions ---
             0x85 | 0x95 | 0x8D | 0x9D | 0x99 | 0x81 | 0x91 => { // STA
                  if addr == 0x2007 {
-                    println!("[CPU STA] Attempting to write to $2007 with Data=${:02X}", self.registers.accumulator);
+                    log::trace!("[CPU STA] Attempting to write to $2007 with Data=${:02X}", self.registers.accumulator);
                  }
                  bus.write(addr, self.registers.accumulator);
             },
@@ -911,7 +936,7 @@ impl Cpu6502 {
             // KIL/HLT/JAM (Treated as NOP for now)
             0x02 | 0x12 | 0x22 | 0x32 | 0x42 | 0x52 | 0x62 | 0x72 |
             0x92 | 0xB2 | 0xD2 | 0xF2 => {
-                println!("WARN: Unofficial KIL/HLT opcode ${:02X} encountered (treated as NOP)", opcode);
+                log::warn!("WARN: Unofficial KIL/HLT opcode ${:02X} encountered (treated as NOP)", opcode);
                  // Halt emulation? For now, just act as NOP.
             }
 
@@ -944,13 +969,13 @@ impl Cpu6502 {
 
             // SRE (LSE) = LSR operand + EOR operand
             0x47 | 0x57 | 0x4F | 0x5F | 0x5B | 0x43 | 0x53 => {
-                println!("WARN: Unofficial SRE/LSE opcode ${:02X} encountered (treated as NOP)", opcode);
+                log::warn!("WARN: Unofficial SRE/LSE opcode ${:02X} encountered (treated as NOP)", opcode);
                  // Placeholder NOP
             }
 
             // RRA = ROR operand + ADC operand
             0x67 | 0x77 | 0x6F | 0x7F | 0x7B | 0x63 | 0x73 => {
-                println!("WARN: Unofficial RRA opcode ${:02X} encountered (treated as NOP)", opcode);
+                log::warn!("WARN: Unofficial RRA opcode ${:02X} encountered (treated as NOP)", opcode);
                  // Placeholder NOP
             }
 
@@ -997,7 +1022,7 @@ impl Cpu6502 {
             // --- End Unofficial Opcodes ---
 
             _ => {
-                println!("WARN: Unimplemented or unknown official opcode {:02X} encountered!", opcode);
+                log::warn!("WARN: Unimplemented or unknown official opcode {:02X} encountered!", opcode);
                 // Potentially halt or panic here depending on desired strictness
             }
         }
@@ -1051,7 +1076,7 @@ impl Cpu6502 {
     pub fn inspect(&self) -> InspectState {
         InspectState {
             registers: self.registers.clone(),
-            total_cycles: 0, // Placeholder for now, Bus should provide this
+            total_cycles: self.total_cycles,
         }
     }
 
@@ -1075,7 +1100,7 @@ impl Cpu6502 {
         self.cycles = 7;
         
         if DEBUG_PRINT {
-            println!("NMI triggered! PC set to ${:04X}", self.registers.program_counter);
+            log::trace!("NMI triggered! PC set to ${:04X}", self.registers.program_counter);
         }
         7
     }
@@ -1212,3 +1237,34 @@ impl Cpu6502 {
          (name, operand_bytes, mode_str)
      }
 }
+
+impl SaveState for Cpu6502 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.begin_section(b"CPU ");
+        w.write_u8(self.registers.accumulator);
+        w.write_u8(self.registers.x_register);
+        w.write_u8(self.registers.y_register);
+        w.write_u8(self.registers.stack_pointer);
+        w.write_u16(self.registers.program_counter);
+        w.write_u8(self.registers.status);
+        w.write_u8(self.cycles);
+        w.write_bool(self.nmi_pending);
+        w.write_bool(self.brk_executed);
+        w.write_u64(self.total_cycles);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.expect_section(b"CPU ")?;
+        self.registers.accumulator = r.read_u8()?;
+        self.registers.x_register = r.read_u8()?;
+        self.registers.y_register = r.read_u8()?;
+        self.registers.stack_pointer = r.read_u8()?;
+        self.registers.program_counter = r.read_u16()?;
+        self.registers.status = r.read_u8()?;
+        self.cycles = r.read_u8()?;
+        self.nmi_pending = r.read_bool()?;
+        self.brk_executed = r.read_bool()?;
+        self.total_cycles = r.read_u64()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,668 @@
+// Debug Adapter Protocol server for source-level debugging of ca65/cc65 homebrew.
+// Editors connect over TCP (e.g. VS Code's "debugServer" launch option) and drive the same debugger core as the UI.
+//
+// launch/attach arguments:
+//   dbgFile      - debug info written by `ld65 --dbgfile`, maps addresses to source lines
+//   program      - ROM to load first (launch only; attach debugs whatever is running)
+//   stopOnEntry  - stay stopped once configuration is done
+use crate::bus::DebugView;
+use crate::dbginfo::DebugInfo;
+use crate::debug_server::DebugServer;
+use crate::debugger::{self, PauseReason};
+use crate::emulator::Emulator;
+use crate::expr::{Env, Expr, Machine};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 4711;
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+const THREAD_ID: u64 = 1; // The CPU is the only thread
+const MAX_LINE_STEPS: usize = 10_000; // Instructions per source-level step before giving up
+const MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024; // Far beyond any real request; guards the body allocation
+
+// variablesReference values for the scopes
+const REGISTERS_REF: u64 = 1;
+const ZERO_PAGE_REF: u64 = 2;
+const STACK_REF: u64 = 3;
+
+// Distinct type so the app can manage it separately from the GDB server
+pub struct DapServer(DebugServer);
+
+impl DapServer {
+    // Listen on localhost only; one client at a time
+    pub fn start(emulator: Arc<Mutex<Emulator>>, port: u16) -> Result<Self, String> {
+        DebugServer::start("DAP", emulator, port, |stream, emulator, running| {
+            match Session::new(stream, emulator, running) {
+                Ok(mut session) => {
+                    if let Err(e) = session.run() {
+                        eprintln!("DAP session ended: {}", e);
+                    }
+                    session.cleanup();
+                }
+                Err(e) => eprintln!("DAP session failed to start: {}", e),
+            }
+        })
+        .map(Self)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.0.port()
+    }
+}
+
+// Messages are read on their own thread so the session can poll the emulator while waiting
+fn read_messages(stream: TcpStream, sender: Sender<Value>) {
+    let mut reader = BufReader::new(stream);
+    while let Ok(message) = read_message(&mut reader) {
+        if sender.send(message).is_err() {
+            return;
+        }
+    }
+}
+
+// "Content-Length: N\r\n\r\n" followed by N bytes of JSON
+fn read_message(reader: &mut impl BufRead) -> Result<Value, String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).map_err(|e| e.to_string())? == 0 {
+            return Err("connection closed".to_string());
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.unwrap_or(0);
+    if length > MAX_MESSAGE_SIZE {
+        return Err(format!("DAP message of {} bytes exceeds the {} byte limit", length, MAX_MESSAGE_SIZE));
+    }
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&body).map_err(|e| format!("Invalid DAP message: {}", e))
+}
+
+fn lock(emulator: &Arc<Mutex<Emulator>>) -> Result<MutexGuard<'_, Emulator>, String> {
+    emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))
+}
+
+struct Session {
+    stream: TcpStream,
+    messages: Receiver<Value>,
+    emulator: Arc<Mutex<Emulator>>,
+    running: Arc<AtomicBool>,
+    seq: u64,
+    debug_info: Option<DebugInfo>,
+    source_breakpoints: HashMap<String, Vec<u16>>, // Source path -> breakpoint addresses set for it
+    launched: bool,
+    configured: bool,
+    stop_on_entry: bool,
+    target_running: bool, // Waiting for the emulator to stop
+}
+
+impl Session {
+    fn new(stream: TcpStream, emulator: Arc<Mutex<Emulator>>, running: Arc<AtomicBool>) -> Result<Self, String> {
+        stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+        let reader = stream.try_clone().map_err(|e| e.to_string())?;
+        let (sender, messages) = mpsc::channel();
+        thread::Builder::new()
+            .name("dap-reader".to_string())
+            .spawn(move || read_messages(reader, sender))
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            stream,
+            messages,
+            emulator,
+            running,
+            seq: 1,
+            debug_info: None,
+            source_breakpoints: HashMap::new(),
+            launched: false,
+            configured: false,
+            stop_on_entry: false,
+            target_running: false,
+        })
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        while self.running.load(Ordering::SeqCst) {
+            match self.messages.recv_timeout(POLL_INTERVAL) {
+                Ok(message) => {
+                    if !self.handle(&message)? {
+                        return Ok(());
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => self.poll_stop()?,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    // Remove this client's breakpoints and let the game run again
+    fn cleanup(&mut self) {
+        if let Ok(mut emulator) = self.emulator.lock() {
+            {
+                let mut debugger = emulator.bus.debugger.borrow_mut();
+                for addr in self.source_breakpoints.drain().flat_map(|(_, addrs)| addrs) {
+                    let _ = debugger.remove_breakpoint(addr);
+                }
+            }
+            emulator.set_paused(false);
+        }
+        let _ = self.stream.shutdown(Shutdown::Both); // Also ends the reader thread
+    }
+
+    // Returns false when the client is done with the session
+    fn handle(&mut self, request: &Value) -> Result<bool, String> {
+        if request["type"] != "request" {
+            return Ok(true);
+        }
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsConditionalBreakpoints": true,
+                "supportsHitConditionalBreakpoints": true,
+                "supportsReadMemoryRequest": true,
+                "supportsWriteMemoryRequest": true,
+                "supportsSteppingGranularity": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" | "attach" => self.launch(command == "launch", args),
+            "configurationDone" => {
+                self.configured = true;
+                Ok(Value::Null)
+            }
+            "setBreakpoints" => self.set_breakpoints(args),
+            "setExceptionBreakpoints" => Ok(Value::Null),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CPU" }] })),
+            "stackTrace" => self.stack_trace(args),
+            "scopes" => Ok(json!({ "scopes": [
+                { "name": "Registers", "presentationHint": "registers", "variablesReference": REGISTERS_REF, "expensive": false },
+                { "name": "Zero Page", "variablesReference": ZERO_PAGE_REF, "expensive": false },
+                { "name": "Stack", "variablesReference": STACK_REF, "expensive": false },
+            ] })),
+            "variables" => self.variables(args),
+            "evaluate" => self.evaluate(args),
+            "readMemory" => self.read_memory(args),
+            "writeMemory" => self.write_memory(args),
+            "continue" => {
+                lock(&self.emulator)?.set_paused(false);
+                self.target_running = true;
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "pause" => {
+                lock(&self.emulator)?.halt();
+                Ok(Value::Null)
+            }
+            "next" | "stepIn" | "stepOut" => Ok(Value::Null), // Stepping happens after the response
+            "disconnect" | "terminate" => Ok(Value::Null),
+            _ => Err(format!("Unsupported request: {}", command)),
+        };
+        let succeeded = result.is_ok();
+        self.respond(request, result)?;
+
+        match command {
+            "launch" | "attach" if succeeded => {
+                self.send_event("initialized", Value::Null)?;
+                self.start_if_ready()?;
+            }
+            "configurationDone" => self.start_if_ready()?,
+            "next" | "stepIn" | "stepOut" => self.step(command, args)?,
+            "pause" if !self.target_running => self.send_stopped("pause", None)?,
+            "disconnect" | "terminate" => return Ok(false),
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn launch(&mut self, load_program: bool, args: &Value) -> Result<Value, String> {
+        let mut emulator = lock(&self.emulator)?;
+        if let Some(program) = args["program"].as_str().filter(|_| load_program) {
+            if emulator.bus.test_mode {
+                emulator.toggle_test_mode()?;
+            }
+            emulator.load_rom(program)?;
+        }
+        if let Some(path) = args["dbgFile"].as_str() {
+            self.debug_info = Some(DebugInfo::load(Path::new(path))?);
+            emulator.import_symbols(path)?; // Labels for stack frames and the disassembler
+        }
+        // Stay stopped while the client sends its breakpoints
+        emulator.halt();
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        self.launched = true;
+        Ok(Value::Null)
+    }
+
+    fn start_if_ready(&mut self) -> Result<(), String> {
+        if !(self.launched && self.configured) {
+            return Ok(());
+        }
+        if self.stop_on_entry {
+            return self.send_stopped("entry", None);
+        }
+        lock(&self.emulator)?.set_paused(false);
+        self.target_running = true;
+        Ok(())
+    }
+
+    // Replaces all breakpoints of one source file
+    fn set_breakpoints(&mut self, args: &Value) -> Result<Value, String> {
+        let path = args["source"]["path"].as_str().ok_or("setBreakpoints needs a source path")?;
+        let requested = args["breakpoints"].as_array().cloned().unwrap_or_default();
+        let shared = self.emulator.clone();
+        let emulator = lock(&shared)?;
+        let mut debugger = emulator.bus.debugger.borrow_mut();
+
+        for addr in self.source_breakpoints.remove(path).unwrap_or_default() {
+            let _ = debugger.remove_breakpoint(addr);
+        }
+
+        let file = self.debug_info.as_ref().and_then(|info| info.find_file(Path::new(path)));
+        let mut added = Vec::new();
+        let mut results = Vec::new();
+        for breakpoint in requested {
+            let line = breakpoint["line"].as_u64().unwrap_or(0) as u32;
+            let resolved = self
+                .debug_info
+                .as_ref()
+                .zip(file)
+                .and_then(|(info, file)| info.addresses_for_line(file, line));
+            let Some((actual, addresses)) = resolved else {
+                let message = if self.debug_info.is_none() { "No debug info loaded" } else { "No code at this line" };
+                results.push(json!({ "verified": false, "line": line, "message": message }));
+                continue;
+            };
+            let hit_target = match breakpoint["hitCondition"].as_str().map(str::trim).filter(|h| !h.is_empty()) {
+                Some(hits) => match hits.parse::<u32>() {
+                    Ok(n) => Some(n),
+                    Err(_) => {
+                        results.push(json!({ "verified": false, "line": line, "message": "Hit count must be a number" }));
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            let condition = breakpoint["condition"].as_str();
+            added.extend(&addresses);
+            match addresses.iter().try_for_each(|&addr| debugger.add_breakpoint(addr, condition, hit_target)) {
+                Ok(()) => results.push(json!({ "verified": true, "line": actual })),
+                Err(e) => results.push(json!({ "verified": false, "line": line, "message": e })),
+            }
+        }
+        self.source_breakpoints.insert(path.to_string(), added);
+        Ok(json!({ "breakpoints": results }))
+    }
+
+    // Frames are reconstructed from the return addresses JSR left on the stack
+    fn stack_trace(&self, args: &Value) -> Result<Value, String> {
+        let emulator = lock(&self.emulator)?;
+        let registers = emulator.bus.cpu.borrow().registers.clone();
+        let view = DebugView(&emulator.bus);
+        let calls = debugger::call_stack(&view, registers.stack_pointer);
+
+        let mut frames = Vec::new();
+        for (index, &addr) in std::iter::once(&registers.program_counter).chain(&calls).enumerate() {
+            // A frame runs inside the routine its caller's JSR jumped to
+            let routine = calls.get(index).map(|&jsr| u16::from_le_bytes([view.peek(jsr.wrapping_add(1)), view.peek(jsr.wrapping_add(2))]));
+            let name = routine.map_or_else(|| label_or_address(&emulator, addr), |entry| label_or_address(&emulator, entry));
+            let mut frame = json!({
+                "id": index,
+                "name": name,
+                "line": 0,
+                "column": 0,
+                "instructionPointerReference": format!("0x{:04X}", addr),
+            });
+            let location = self.debug_info.as_ref().and_then(|info| info.location(addr, emulator.prg_rom_offset(addr)));
+            if let Some(location) = location {
+                frame["source"] = json!({ "name": location.file.name, "path": location.file.path });
+                frame["line"] = json!(location.line);
+                frame["column"] = json!(1);
+            }
+            frames.push(frame);
+        }
+
+        let total = frames.len();
+        let start = (args["startFrame"].as_u64().unwrap_or(0) as usize).min(total);
+        let levels = args["levels"].as_u64().filter(|&l| l > 0).map_or(total, |l| l as usize);
+        let frames: Vec<Value> = frames.into_iter().skip(start).take(levels).collect();
+        Ok(json!({ "stackFrames": frames, "totalFrames": total }))
+    }
+
+    fn variables(&self, args: &Value) -> Result<Value, String> {
+        let emulator = lock(&self.emulator)?;
+        let variables = match args["variablesReference"].as_u64().unwrap_or(0) {
+            REGISTERS_REF => {
+                let r = emulator.bus.cpu.borrow().registers.clone();
+                let flags: String = "NV-BDIZC"
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| if r.status & (0x80 >> i) != 0 { c } else { c.to_ascii_lowercase() })
+                    .collect();
+                vec![
+                    register("A", format!("${:02X}", r.accumulator)),
+                    register("X", format!("${:02X}", r.x_register)),
+                    register("Y", format!("${:02X}", r.y_register)),
+                    register("SP", format!("${:02X}", r.stack_pointer)),
+                    json!({
+                        "name": "PC",
+                        "value": format!("${:04X}", r.program_counter),
+                        "variablesReference": 0,
+                        "memoryReference": format!("0x{:04X}", r.program_counter),
+                    }),
+                    register("P", format!("${:02X} {}", r.status, flags)),
+                ]
+            }
+            ZERO_PAGE_REF => memory_rows(&emulator, 0x0000),
+            STACK_REF => memory_rows(&emulator, 0x0100),
+            _ => Vec::new(),
+        };
+        Ok(json!({ "variables": variables }))
+    }
+
+    // Expressions use the breakpoint condition language
+    fn evaluate(&self, args: &Value) -> Result<Value, String> {
+        let expression = Expr::parse(args["expression"].as_str().unwrap_or_default())?;
+        let emulator = lock(&self.emulator)?;
+        let registers = emulator.bus.cpu.borrow().registers.clone();
+        let value = expression.evaluate(&Env { registers: &registers, machine: &DebugView(&emulator.bus), access: None });
+        let result = if value < 0 { value.to_string() } else { format!("${:X} ({})", value, value) };
+        Ok(json!({ "result": result, "variablesReference": 0 }))
+    }
+
+    fn read_memory(&self, args: &Value) -> Result<Value, String> {
+        let start = memory_address(args)?;
+        let count = args["count"].as_u64().unwrap_or(0).min(0x10000 - start as u64) as u32;
+        let emulator = lock(&self.emulator)?;
+        let bytes: Vec<u8> = (0..count).map(|i| emulator.bus.debug_read((start + i) as u16)).collect();
+        let unreadable = args["count"].as_u64().unwrap_or(0) - count as u64;
+        Ok(json!({ "address": format!("0x{:04X}", start), "data": base64_encode(&bytes), "unreadableBytes": unreadable }))
+    }
+
+    fn write_memory(&self, args: &Value) -> Result<Value, String> {
+        let start = memory_address(args)?;
+        let data = base64_decode(args["data"].as_str().unwrap_or_default()).ok_or("Invalid base64 data")?;
+        let mut emulator = lock(&self.emulator)?;
+        let written = data.len().min(0x10000 - start as usize);
+        for (i, &byte) in data.iter().take(written).enumerate() {
+            emulator.bus.debug_write((start as usize + i) as u16, byte);
+        }
+        Ok(json!({ "bytesWritten": written }))
+    }
+
+    // Source-level steps repeat instruction steps until execution reaches another line
+    fn step(&mut self, command: &str, args: &Value) -> Result<(), String> {
+        let shared = self.emulator.clone();
+        let mut emulator = lock(&shared)?;
+        let by_instruction = args["granularity"] == "instruction";
+        let start = if by_instruction { None } else { self.current_line(&emulator) };
+
+        let mut steps = 0;
+        let result = loop {
+            let result = match command {
+                "next" => emulator.step_over(),
+                "stepIn" => emulator.step_into(),
+                _ => emulator.step_out(),
+            };
+            steps += 1;
+            let stepped = matches!(&result, Ok(info) if matches!(info.reason, PauseReason::Step { completed: true }));
+            if !stepped || command == "stepOut" || start.is_none() || steps >= MAX_LINE_STEPS {
+                break result;
+            }
+            let line = self.current_line(&emulator);
+            if line.is_some() && line != start {
+                break result;
+            }
+        };
+        drop(emulator);
+
+        self.target_running = false;
+        match result {
+            Ok(info) => self.send_stop_reason(&info.reason),
+            Err(e) => self.send_event("output", json!({ "category": "stderr", "output": format!("{}\n", e) })),
+        }
+    }
+
+    fn current_line(&self, emulator: &Emulator) -> Option<(PathBuf, u32)> {
+        let pc = emulator.bus.cpu.borrow().registers.program_counter;
+        let location = self.debug_info.as_ref()?.location(pc, emulator.prg_rom_offset(pc))?;
+        Some((location.file.path.clone(), location.line))
+    }
+
+    // Report when the emulator stops on its own (breakpoint, watchpoint, pause from the UI)
+    fn poll_stop(&mut self) -> Result<(), String> {
+        if !self.target_running {
+            return Ok(());
+        }
+        let reason = {
+            let emulator = lock(&self.emulator)?;
+            match emulator.pause_info() {
+                Some(info) => info.reason,
+                None if emulator.paused => PauseReason::Halt,
+                None => return Ok(()),
+            }
+        };
+        self.target_running = false;
+        self.send_stop_reason(&reason)
+    }
+
+    fn send_stop_reason(&mut self, reason: &PauseReason) -> Result<(), String> {
+        match reason {
+            PauseReason::Breakpoint { .. } => self.send_stopped("breakpoint", None),
+            PauseReason::Watchpoint { address, access, .. } => {
+                let description = format!("Watchpoint: {:?} at ${:04X}", access, address);
+                self.send_stopped("data breakpoint", Some(description))
+            }
+            PauseReason::Step { completed: true } => self.send_stopped("step", None),
+            PauseReason::Step { completed: false } => self.send_stopped("step", Some("Step limit reached".to_string())),
+            PauseReason::Halt => self.send_stopped("pause", None),
+        }
+    }
+
+    fn send_stopped(&mut self, reason: &str, description: Option<String>) -> Result<(), String> {
+        let mut body = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+        if let Some(description) = description {
+            body["description"] = json!(description);
+        }
+        self.send_event("stopped", body)
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> Result<(), String> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response)
+    }
+
+    fn send_event(&mut self, event: &str, body: Value) -> Result<(), String> {
+        let mut message = json!({ "type": "event", "event": event });
+        if !body.is_null() {
+            message["body"] = body;
+        }
+        self.send(message)
+    }
+
+    fn send(&mut self, mut message: Value) -> Result<(), String> {
+        message["seq"] = json!(self.seq);
+        self.seq += 1;
+        let body = message.to_string();
+        write!(self.stream, "Content-Length: {}\r\n\r\n{}", body.len(), body).map_err(|e| e.to_string())
+    }
+}
+
+fn label_or_address(emulator: &Emulator, addr: u16) -> String {
+    match emulator.symbol_at(addr).filter(|s| !s.name.is_empty()) {
+        Some(symbol) => symbol.name.clone(),
+        None => format!("${:04X}", addr),
+    }
+}
+
+fn register(name: &str, value: String) -> Value {
+    json!({ "name": name, "value": value, "variablesReference": 0 })
+}
+
+// One page of memory as 16-byte rows
+fn memory_rows(emulator: &Emulator, page: u16) -> Vec<Value> {
+    (page..page + 0x100)
+        .step_by(16)
+        .map(|row| {
+            let bytes: Vec<String> = (row..row + 16).map(|addr| format!("{:02X}", emulator.bus.debug_read(addr))).collect();
+            json!({
+                "name": format!("${:04X}", row),
+                "value": bytes.join(" "),
+                "variablesReference": 0,
+                "memoryReference": format!("0x{:04X}", row),
+            })
+        })
+        .collect()
+}
+
+// memoryReference plus the optional offset
+fn memory_address(args: &Value) -> Result<u32, String> {
+    let reference = args["memoryReference"].as_str().unwrap_or_default().trim();
+    let base = match reference.strip_prefix("0x").or_else(|| reference.strip_prefix('$')) {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => reference.parse::<i64>(),
+    }
+    .map_err(|_| format!("Invalid memory reference: {}", reference))?;
+    let addr = base + args["offset"].as_i64().unwrap_or(0);
+    if !(0..=0xFFFF).contains(&addr) {
+        return Err(format!("Address out of range: {}", addr));
+    }
+    Ok(addr as u32)
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in text.bytes().filter(|&c| c != b'=' && !c.is_ascii_whitespace()) {
+        let value = BASE64.iter().position(|&b| b == c)? as u32;
+        bits = (bits << 6) | value;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    // Hands out at most `step` bytes per read, like a socket delivering a message in pieces
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.step.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    fn frame(body: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    #[test]
+    fn base64_padding() {
+        let cases: [(&[u8], &str); 5] =
+            [(b"", ""), (b"f", "Zg=="), (b"fo", "Zm8="), (b"foo", "Zm9v"), (b"foobar", "Zm9vYmFy")];
+        for (data, text) in cases {
+            assert_eq!(base64_encode(data), text);
+            assert_eq!(base64_decode(text).as_deref(), Some(data));
+        }
+        // Padding and whitespace are optional on input
+        assert_eq!(base64_decode("Zg").as_deref(), Some(&b"f"[..]));
+        assert_eq!(base64_decode("Zm9v\r\nYmFy").as_deref(), Some(&b"foobar"[..]));
+        assert_eq!(base64_decode("Zm9v!"), None);
+    }
+
+    #[test]
+    fn base64_round_trips_every_remainder() {
+        let data: Vec<u8> = (0..=255).collect();
+        for length in [0, 1, 2, 3, 4, 5, 254, 255, 256] {
+            let encoded = base64_encode(&data[..length]);
+            assert_eq!(encoded.len(), length.div_ceil(3) * 4);
+            assert_eq!(base64_decode(&encoded).as_deref(), Some(&data[..length]));
+        }
+    }
+
+    #[test]
+    fn messages_split_across_reads() {
+        let input = frame(r#"{"seq":1,"command":"initialize"}"#) + &frame(r#"{"seq":2,"command":"launch"}"#);
+        let mut reader = BufReader::with_capacity(4, Trickle { data: input.as_bytes(), step: 3 });
+        assert_eq!(read_message(&mut reader).unwrap()["command"], "initialize");
+        assert_eq!(read_message(&mut reader).unwrap()["seq"], 2);
+        assert!(read_message(&mut reader).is_err());
+    }
+
+    #[test]
+    fn headers_are_case_insensitive_and_may_repeat_blank_lines() {
+        let input = "\r\ncontent-length: 2\r\nContent-Type: application/json\r\n\r\n{}";
+        assert_eq!(read_message(&mut input.as_bytes()).unwrap(), json!({}));
+    }
+
+    #[test]
+    fn oversized_and_truncated_messages_are_errors() {
+        let input = format!("Content-Length: {}\r\n\r\n", MAX_MESSAGE_SIZE + 1);
+        let error = read_message(&mut input.as_bytes()).unwrap_err();
+        assert!(error.contains("exceeds"), "{}", error);
+
+        let truncated = "Content-Length: 10\r\n\r\n{}";
+        assert!(read_message(&mut truncated.as_bytes()).is_err());
+        assert!(read_message(&mut frame("{").as_bytes()).is_err());
+    }
+}
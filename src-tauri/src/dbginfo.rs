@@ -0,0 +1,326 @@
+// Debug info written by the cc65 linker (ld65 --dbgfile), used to map CPU addresses to source lines.
+//
+// The file is line based: a record type, a tab, then comma separated key=value pairs, e.g.
+//   file  id=0,name="src/main.s",size=1234,mtime=0x5F5E1000,mod=0
+//   line  id=3,file=0,line=42,type=1,span=7+8
+//   seg   id=0,name="CODE",start=0x008000,size=0x0200,addrsize=absolute,type=ro,oname="game.nes",ooffs=16
+//   span  id=7,seg=0,start=16,size=3
+//   sym   id=5,name="reset",addrsize=absolute,scope=0,def=12,val=0x8000,seg=0,type=lab
+// Record types that aren't needed for source mapping or labels are skipped.
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+const INES_HEADER_SIZE: usize = 16;
+
+// Line record types from ld65
+const LINE_ASM: u8 = 0;
+const LINE_EXTERNAL: u8 = 1; // C source compiled by cc65
+const LINE_MACRO: u8 = 2;
+
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,  // As passed to the assembler
+    pub path: PathBuf, // Resolved against the directory of the .dbg file
+}
+
+#[derive(Debug, Clone)]
+struct Line {
+    file: usize,
+    line: u32,
+    kind: u8,
+    spans: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    seg: usize,
+    start: u32,
+    size: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    start: u32,
+    ooffs: Option<usize>,
+}
+
+impl Segment {
+    // ooffs counts from the start of the output file, which begins with the iNES header
+    fn prg_offset(&self, offset_in_segment: u32) -> Option<usize> {
+        self.ooffs
+            .and_then(|o| o.checked_sub(INES_HEADER_SIZE))
+            .and_then(|o| o.checked_add(offset_in_segment as usize))
+    }
+}
+
+// Code emitted for one source line
+#[derive(Debug, Clone, Copy)]
+struct LineRange {
+    start: u32,
+    end: u32, // Exclusive
+    prg_offset: Option<usize>,
+    line: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SourceLocation<'a> {
+    pub file: &'a SourceFile,
+    pub line: u32,
+}
+
+// A label (sym record of type "lab")
+#[derive(Debug, Clone)]
+pub struct DebugSymbol {
+    pub name: String,
+    pub address: u16,
+    pub prg_offset: Option<usize>, // None outside PRG ROM (RAM, registers)
+}
+
+#[derive(Debug, Default)]
+pub struct DebugInfo {
+    pub files: Vec<SourceFile>,
+    pub symbols: Vec<DebugSymbol>,
+    lines: Vec<Line>,
+    ranges: Vec<LineRange>,
+}
+
+impl DebugInfo {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&text, base)
+    }
+
+    pub fn parse(text: &str, base: &Path) -> Result<Self, String> {
+        let mut files = HashMap::new();
+        let mut lines = HashMap::new();
+        let mut spans = HashMap::new();
+        let mut segments = HashMap::new();
+        let mut symbols = Vec::new();
+
+        for (number, record) in text.lines().enumerate() {
+            let Some((kind, fields)) = record.trim().split_once(char::is_whitespace) else { continue };
+            let fields = parse_fields(fields);
+            let err = |what: &str| format!("Line {}: {} record without {}", number + 1, kind, what);
+            let id = || fields.get("id").and_then(|v| parse_number(v)).ok_or_else(|| err("id"));
+            match kind {
+                "file" => {
+                    let name = fields.get("name").ok_or_else(|| err("name"))?.to_string();
+                    let path = normalize(&base.join(&name));
+                    files.insert(id()? as usize, SourceFile { name, path });
+                }
+                "line" => {
+                    let field = |key: &str| fields.get(key).and_then(|v| parse_number(v));
+                    lines.insert(
+                        id()? as usize,
+                        Line {
+                            file: field("file").ok_or_else(|| err("file"))? as usize,
+                            line: field("line").ok_or_else(|| err("line"))?,
+                            kind: field("type").unwrap_or(LINE_ASM as u32) as u8,
+                            spans: fields.get("span").map_or(Vec::new(), |v| parse_list(v)),
+                        },
+                    );
+                }
+                "span" => {
+                    let field = |key: &str| fields.get(key).and_then(|v| parse_number(v)).ok_or_else(|| err(key));
+                    spans.insert(id()? as usize, Span { seg: field("seg")? as usize, start: field("start")?, size: field("size")? });
+                }
+                "seg" => {
+                    let start = fields.get("start").and_then(|v| parse_number(v)).ok_or_else(|| err("start"))?;
+                    let ooffs = fields.get("ooffs").and_then(|v| parse_number(v)).map(|o| o as usize);
+                    segments.insert(id()? as usize, Segment { start, ooffs });
+                }
+                "sym" if fields.get("type") == Some(&"lab") => {
+                    let name = fields.get("name").ok_or_else(|| err("name"))?.to_string();
+                    let value = fields.get("val").and_then(|v| parse_number(v)).ok_or_else(|| err("val"))?;
+                    let seg = fields.get("seg").and_then(|v| parse_number(v)).map(|s| s as usize);
+                    symbols.push((name, value, seg));
+                }
+                _ => {}
+            }
+        }
+
+        // Ids are dense in practice, but don't rely on it
+        let mut file_ids: Vec<usize> = files.keys().copied().collect();
+        file_ids.sort_unstable();
+        let file_index: HashMap<usize, usize> = file_ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+        let files: Vec<SourceFile> = file_ids.iter().map(|id| files[id].clone()).collect();
+
+        let symbols = symbols
+            .into_iter()
+            .map(|(name, value, seg)| {
+                let segment = seg.and_then(|id| segments.get(&id));
+                let prg_offset = segment.and_then(|s| s.prg_offset(value.checked_sub(s.start)?));
+                DebugSymbol { name, address: value as u16, prg_offset }
+            })
+            .collect();
+
+        let mut info = DebugInfo { files, symbols, lines: Vec::new(), ranges: Vec::new() };
+        let mut line_ids: Vec<usize> = lines.keys().copied().collect();
+        line_ids.sort_unstable();
+        for id in line_ids {
+            let mut line = lines[&id].clone();
+            let Some(&file) = file_index.get(&line.file) else { continue };
+            line.file = file;
+            let index = info.lines.len();
+            for span in line.spans.iter().filter_map(|id| spans.get(id)) {
+                let Some(segment) = segments.get(&span.seg) else { continue };
+                if span.size == 0 {
+                    continue;
+                }
+                // Skip spans that don't fit in the CPU address space (corrupt or hand-edited files)
+                let Some((start, end)) = segment
+                    .start
+                    .checked_add(span.start)
+                    .and_then(|start| Some((start, start.checked_add(span.size)?)))
+                    .filter(|&(_, end)| end <= 0x10000)
+                else {
+                    continue;
+                };
+                let prg_offset = segment.prg_offset(span.start);
+                info.ranges.push(LineRange { start, end, prg_offset, line: index });
+            }
+            info.lines.push(line);
+        }
+        Ok(info)
+    }
+
+    // Find a loaded source file by the path an editor uses for it
+    pub fn find_file(&self, path: &Path) -> Option<usize> {
+        let path = normalize(path);
+        self.files.iter().position(|f| f.path == path).or_else(|| {
+            // Fall back to the longest matching path suffix (the build may have run elsewhere)
+            self.files
+                .iter()
+                .enumerate()
+                .filter_map(|(i, f)| {
+                    let common = f.path.components().rev().zip(path.components().rev()).take_while(|(a, b)| a == b).count();
+                    (common > 0 && common >= Path::new(&f.name).components().filter(|c| matches!(c, Component::Normal(_))).count())
+                        .then_some((common, i))
+                })
+                .max()
+                .map(|(_, i)| i)
+        })
+    }
+
+    // CPU addresses of the code generated for a source line.
+    // Lines without code resolve to the next line that has some; returns that line too.
+    pub fn addresses_for_line(&self, file: usize, line: u32) -> Option<(u32, Vec<u16>)> {
+        let actual = self
+            .ranges
+            .iter()
+            .map(|r| &self.lines[r.line])
+            .filter(|l| l.file == file && l.line >= line)
+            .map(|l| l.line)
+            .min()?;
+        let mut addresses: Vec<u16> = self
+            .ranges
+            .iter()
+            .filter(|r| self.lines[r.line].file == file && self.lines[r.line].line == actual)
+            .map(|r| r.start as u16)
+            .collect();
+        addresses.sort_unstable();
+        addresses.dedup();
+        Some((actual, addresses))
+    }
+
+    // Source line for a CPU address. prg_offset is where the address is currently mapped in PRG ROM,
+    // which tells apart banks that share a CPU address range.
+    pub fn location(&self, addr: u16, prg_offset: Option<usize>) -> Option<SourceLocation<'_>> {
+        let addr = addr as u32;
+        let candidates: Vec<&LineRange> = self.ranges.iter().filter(|r| (r.start..r.end).contains(&addr)).collect();
+        let in_bank: Vec<&LineRange> = match prg_offset {
+            Some(offset) => candidates
+                .iter()
+                .copied()
+                .filter(|r| r.prg_offset.is_none_or(|o| o + (addr - r.start) as usize == offset))
+                .collect(),
+            None => Vec::new(),
+        };
+        let candidates = if in_bank.is_empty() { candidates } else { in_bank };
+
+        // C lines over assembly over macro bodies, then the narrowest range
+        let priority = |kind: u8| match kind {
+            LINE_EXTERNAL => 0,
+            LINE_ASM => 1,
+            LINE_MACRO => 2,
+            _ => 3,
+        };
+        let best = candidates.into_iter().min_by_key(|r| (priority(self.lines[r.line].kind), r.end - r.start))?;
+        let line = &self.lines[best.line];
+        Some(SourceLocation { file: &self.files[line.file], line: line.line })
+    }
+}
+
+// key=value pairs; values may be quoted strings containing commas
+fn parse_fields(text: &str) -> HashMap<&str, &str> {
+    let mut fields = HashMap::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let Some((key, after)) = rest.split_once('=') else { break };
+        let (value, next) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            (&quoted[..end], quoted.get(end + 1..).unwrap_or(""))
+        } else {
+            after.split_once(',').map_or((after, ""), |(v, n)| (v, n))
+        };
+        fields.insert(key.trim(), value);
+        rest = next.trim_start_matches(',');
+    }
+    fields
+}
+
+fn parse_number(value: &str) -> Option<u32> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+// "4+5+9" -> [4, 5, 9]
+fn parse_list(value: &str) -> Vec<usize> {
+    value.split('+').filter_map(|v| parse_number(v).map(|n| n as usize)).collect()
+}
+
+// Lexically resolve "." and ".." so paths compare equal without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push("..");
+                }
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_lines_and_skips_spans_outside_the_address_space() {
+        let text = "\
+file\tid=0,name=\"main.s\",size=100,mtime=0,mod=0
+seg\tid=0,name=\"CODE\",start=0x008000,size=0x0200,addrsize=absolute,type=ro,oname=\"game.nes\",ooffs=16
+seg\tid=1,name=\"BAD\",start=0xFFFFFFF0,size=0x10,addrsize=absolute,type=ro,oname=\"game.nes\",ooffs=4
+span\tid=0,seg=0,start=4,size=3
+span\tid=1,seg=1,start=0x20,size=2
+span\tid=2,seg=0,start=0xFFFF0000,size=0x20000
+line\tid=0,file=0,line=10,span=0
+line\tid=1,file=0,line=11,span=1+2
+";
+        let info = DebugInfo::parse(text, Path::new("/src")).unwrap();
+        assert_eq!(info.addresses_for_line(0, 10), Some((10, vec![0x8004])));
+        assert_eq!(info.addresses_for_line(0, 11), None);
+
+        let location = info.location(0x8005, Some(5)).unwrap();
+        assert_eq!(location.line, 10);
+        assert!(info.location(0x8007, Some(7)).is_none());
+    }
+}
@@ -0,0 +1,79 @@
+// Localhost TCP listener shared by the GDB stub and the DAP server.
+// A background thread accepts one client at a time and hands the connection to the protocol's session.
+use crate::emulator::Emulator;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct DebugServer {
+    port: u16,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl DebugServer {
+    // `serve` runs one client session to completion; it should return once `running` is cleared.
+    // `name` is only used for log messages and the thread name.
+    pub fn start<F>(name: &'static str, emulator: Arc<Mutex<Emulator>>, port: u16, serve: F) -> Result<Self, String>
+    where
+        F: FnMut(TcpStream, Arc<Mutex<Emulator>>, Arc<AtomicBool>) + Send + 'static,
+    {
+        let listener = TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+            .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let handle = thread::Builder::new()
+            .name(format!("{}-server", name.to_ascii_lowercase()))
+            .spawn(move || accept_loop(name, listener, emulator, thread_running, serve))
+            .map_err(|e| format!("Failed to start {} server thread: {}", name, e))?;
+        println!("{} server listening on 127.0.0.1:{}", name, port);
+
+        Ok(Self { port, running, handle: Some(handle) })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    // Also ends the connected client's session
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for DebugServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn accept_loop<F>(name: &str, listener: TcpListener, emulator: Arc<Mutex<Emulator>>, running: Arc<AtomicBool>, mut serve: F)
+where
+    F: FnMut(TcpStream, Arc<Mutex<Emulator>>, Arc<AtomicBool>),
+{
+    while running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                println!("{} client connected from {}", name, addr);
+                serve(stream, emulator.clone(), running.clone());
+                println!("{} client disconnected", name);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+            Err(e) => {
+                eprintln!("{} server accept failed: {}", name, e);
+                return;
+            }
+        }
+    }
+}
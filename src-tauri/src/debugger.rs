@@ -1,26 +1,377 @@
-use std::collections::HashSet;
+use crate::cpu::{InspectState, Registers};
+use crate::expr::{Env, Expr, Machine};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Breakpoint {
+    pub address: u16,
+    pub enabled: bool,
+    pub condition: Option<Expr>, // 真のときだけ止まる
+    pub hit_target: Option<u32>, // 条件成立がこの回数に達してから止まる
+    pub hit_count: u32,
+}
+
+// 監視対象のアドレス空間
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchSpace {
+    Cpu,
+    Vram,    // PPU $0000-$3EFF (パターンテーブル / ネームテーブル)
+    Palette, // PPU $3F00-$3FFF
+    Oam,     // スプライト RAM $00-$FF
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessKind {
+    Read,
+    Write,
+    Execute,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Watchpoint {
+    pub id: u32,
+    pub space: WatchSpace,
+    pub start: u16,
+    pub end: u16, // 終端を含む
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool, // CPU 空間のみ
+    pub enabled: bool,
+    pub condition: Option<Expr>,
+    pub hit_target: Option<u32>,
+    pub hit_count: u32,
+}
+
+impl Watchpoint {
+    fn matches(&self, space: WatchSpace, kind: AccessKind, addr: u16) -> bool {
+        self.enabled
+            && self.space == space
+            && (self.start..=self.end).contains(&addr)
+            && match kind {
+                AccessKind::Read => self.read,
+                AccessKind::Write => self.write,
+                AccessKind::Execute => self.execute,
+            }
+    }
+}
+
+// フロントエンドから受け取るウォッチポイントの設定
+#[derive(Debug, Clone, Deserialize)]
+pub struct WatchpointSpec {
+    pub space: WatchSpace,
+    pub start: u16,
+    pub end: Option<u16>, // 省略時は start の1バイトのみ
+    #[serde(default)]
+    pub read: bool,
+    #[serde(default)]
+    pub write: bool,
+    #[serde(default)]
+    pub execute: bool,
+    pub condition: Option<String>,
+    pub hit_target: Option<u32>,
+}
+
+// なぜ実行が止まったか
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PauseReason {
+    Breakpoint { address: u16 },
+    Watchpoint {
+        id: u32,
+        space: WatchSpace,
+        access: AccessKind,
+        address: u16,
+        value: u8,
+        pc: u16, // アクセスした命令のアドレス
+        scanline: isize,
+        dot: usize,
+    },
+    // ステップ実行の完了 (completed が false なら上限に達して打ち切り)
+    Step { completed: bool },
+    // 外部デバッガ (GDB) からの中断要求
+    Halt,
+}
+
+// 停止時にフロントエンドへ通知する内容
+#[derive(Clone, Serialize)]
+pub struct PauseInfo {
+    pub reason: PauseReason,
+    pub cpu: InspectState,
+    pub scanline: isize,
+    pub dot: usize,
+    pub frame: u64,
+}
 
 pub struct Debugger {
-    breakpoints: HashSet<u16>, // ブレークポイントを保持するセット
+    breakpoints: BTreeMap<u16, Breakpoint>, // アドレス順に保持
+    watchpoints: Vec<Watchpoint>,
+    next_watchpoint_id: u32,
+    // 再開直後、停止したアドレスの命令を一度だけ素通りさせる
+    skip_once: Option<u16>,
+    // 巻き戻しの再生中はブレークポイントとウォッチポイントを無視する
+    suspended: bool,
+    // 実行中の命令の開始時のレジスタ (ウォッチポイントの報告と条件式用)
+    instruction_regs: Registers,
+    // 命令の実行中にヒットしたウォッチポイント（命令の完了後に停止する）
+    watch_hit: Option<PauseReason>,
 }
 
 impl Debugger {
     pub fn new() -> Self {
         Self {
-            breakpoints: HashSet::new(),
+            breakpoints: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            next_watchpoint_id: 1,
+            skip_once: None,
+            suspended: false,
+            instruction_regs: Registers::default(),
+            watch_hit: None,
+        }
+    }
+
+    // 既存のブレークポイントなら有効化して条件を置き換える
+    pub fn add_breakpoint(&mut self, addr: u16, condition: Option<&str>, hit_target: Option<u32>) -> Result<(), String> {
+        let condition = parse_condition(condition)?;
+        let breakpoint = self.breakpoints.entry(addr).or_insert(Breakpoint {
+            address: addr,
+            enabled: true,
+            condition: None,
+            hit_target: None,
+            hit_count: 0,
+        });
+        breakpoint.enabled = true;
+        breakpoint.condition = condition;
+        breakpoint.hit_target = hit_target;
+        Ok(())
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) -> Result<(), String> {
+        self.breakpoints
+            .remove(&addr)
+            .map(|_| ())
+            .ok_or_else(|| format!("No breakpoint at ${:04X}", addr))
+    }
+
+    pub fn set_breakpoint_enabled(&mut self, addr: u16, enabled: bool) -> Result<(), String> {
+        let breakpoint = self
+            .breakpoints
+            .get_mut(&addr)
+            .ok_or_else(|| format!("No breakpoint at ${:04X}", addr))?;
+        breakpoint.enabled = enabled;
+        Ok(())
+    }
+
+    pub fn breakpoints(&self) -> Vec<Breakpoint> {
+        self.breakpoints.values().cloned().collect()
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    // ウォッチポイントを追加して ID を返す
+    pub fn add_watchpoint(&mut self, spec: WatchpointSpec) -> Result<u32, String> {
+        let end = spec.end.unwrap_or(spec.start);
+        if spec.start > end {
+            return Err(format!("Invalid range: ${:04X}-${:04X}", spec.start, end));
+        }
+        if spec.execute && spec.space != WatchSpace::Cpu {
+            return Err("Execute watchpoints are only available on the CPU address space".to_string());
+        }
+        if !(spec.read || spec.write || spec.execute) {
+            return Err("Watchpoint needs at least one of read, write or execute".to_string());
+        }
+        let condition = parse_condition(spec.condition.as_deref())?;
+        let id = self.next_watchpoint_id;
+        self.next_watchpoint_id += 1;
+        self.watchpoints.push(Watchpoint {
+            id,
+            space: spec.space,
+            start: spec.start,
+            end,
+            read: spec.read,
+            write: spec.write,
+            execute: spec.execute,
+            enabled: true,
+            condition,
+            hit_target: spec.hit_target,
+            hit_count: 0,
+        });
+        Ok(id)
+    }
+
+    pub fn remove_watchpoint(&mut self, id: u32) -> Result<(), String> {
+        let index = self
+            .watchpoints
+            .iter()
+            .position(|wp| wp.id == id)
+            .ok_or_else(|| format!("No watchpoint with id {}", id))?;
+        self.watchpoints.remove(index);
+        Ok(())
+    }
+
+    pub fn set_watchpoint_enabled(&mut self, id: u32, enabled: bool) -> Result<(), String> {
+        let watchpoint = self
+            .watchpoints
+            .iter_mut()
+            .find(|wp| wp.id == id)
+            .ok_or_else(|| format!("No watchpoint with id {}", id))?;
+        watchpoint.enabled = enabled;
+        Ok(())
+    }
+
+    pub fn watchpoints(&self) -> Vec<Watchpoint> {
+        self.watchpoints.clone()
+    }
+
+    // 何も設定されていなければ実行ループでのチェックを省略できる
+    pub fn is_active(&self) -> bool {
+        !self.suspended && (!self.breakpoints.is_empty() || !self.watchpoints.is_empty() || self.skip_once.is_some())
+    }
+
+    // 停止中の状態 (skip_once やヒット数) を変えずにチェックを止める
+    pub fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+    }
+
+    // バスアクセス毎に呼ばれるので軽く判定する
+    pub fn is_watching(&self) -> bool {
+        !self.suspended && !self.watchpoints.is_empty()
+    }
+
+    // 再開時に呼ぶ: 現在の PC のブレークポイントで即座に止まらないようにする
+    pub fn resume_from(&mut self, pc: u16) {
+        self.skip_once = Some(pc);
+    }
+
+    // 命令の実行前に呼ぶ: ブレークポイントと実行ウォッチポイントをチェック
+    pub fn check_breakpoint(&mut self, registers: &Registers, machine: &dyn Machine) -> Option<PauseReason> {
+        self.instruction_regs = registers.clone();
+        let pc = registers.program_counter;
+        if self.skip_once.take() == Some(pc) {
+            return None;
+        }
+        let env = Env { registers, machine, access: None };
+        if let Some(breakpoint) = self.breakpoints.get_mut(&pc).filter(|bp| bp.enabled) {
+            if should_stop(&breakpoint.condition, breakpoint.hit_target, &mut breakpoint.hit_count, &env) {
+                return Some(PauseReason::Breakpoint { address: pc });
+            }
+        }
+        self.hit(WatchSpace::Cpu, AccessKind::Execute, pc, 0, machine)
+    }
+
+    // バスからのメモリアクセス通知。停止は命令の完了後 (take_watch_hit)
+    pub fn on_access(&mut self, space: WatchSpace, kind: AccessKind, addr: u16, value: u8, machine: &dyn Machine) {
+        if self.watch_hit.is_none() {
+            self.watch_hit = self.hit(space, kind, addr, value, machine);
         }
     }
 
-    pub fn add_breakpoint(&mut self, addr: u16) {
-        self.breakpoints.insert(addr);
+    pub fn take_watch_hit(&mut self) -> Option<PauseReason> {
+        self.watch_hit.take()
     }
 
-    pub fn remove_breakpoint(&mut self, addr: u16) {
-        self.breakpoints.remove(&addr);
+    fn hit(&mut self, space: WatchSpace, kind: AccessKind, addr: u16, value: u8, machine: &dyn Machine) -> Option<PauseReason> {
+        let env = Env { registers: &self.instruction_regs, machine, access: Some((addr, value)) };
+        let watchpoint = self
+            .watchpoints
+            .iter_mut()
+            .filter(|wp| wp.matches(space, kind, addr))
+            .find_map(|wp| should_stop(&wp.condition, wp.hit_target, &mut wp.hit_count, &env).then_some(wp))?;
+        let (scanline, dot) = machine.ppu_position();
+        Some(PauseReason::Watchpoint {
+            id: watchpoint.id,
+            space,
+            access: kind,
+            address: addr,
+            value,
+            pc: self.instruction_regs.program_counter,
+            scanline,
+            dot,
+        })
     }
+}
+
+// スタックに積まれた JSR の戻りアドレスから呼び出し履歴を推測する (内側から順に JSR 命令のアドレス)
+// JSR は「戻り先 - 1」を積むので、その 2 バイト前が JSR のオペコードかどうかで判定する
+pub fn call_stack(machine: &dyn Machine, stack_pointer: u8) -> Vec<u16> {
+    const OPCODE_JSR: u8 = 0x20;
+    let mut calls = Vec::new();
+    let mut offset = stack_pointer as u16 + 1;
+    while offset < 0xFF {
+        let low = machine.peek(0x0100 + offset) as u16;
+        let high = machine.peek(0x0100 + offset + 1) as u16;
+        let jsr = ((high << 8) | low).wrapping_sub(2);
+        if machine.peek(jsr) == OPCODE_JSR {
+            calls.push(jsr);
+            offset += 2;
+        } else {
+            offset += 1;
+        }
+    }
+    calls
+}
+
+fn parse_condition(condition: Option<&str>) -> Result<Option<Expr>, String> {
+    match condition.map(str::trim).filter(|c| !c.is_empty()) {
+        Some(source) => Expr::parse(source).map(Some),
+        None => Ok(None),
+    }
+}
+
+// 条件が成立した回数を数え、目標回数に達していれば止まる
+fn should_stop(condition: &Option<Expr>, hit_target: Option<u32>, hit_count: &mut u32, env: &Env) -> bool {
+    if condition.as_ref().is_some_and(|c| !c.is_true(env)) {
+        return false;
+    }
+    *hit_count += 1;
+    hit_target.is_none_or(|target| *hit_count >= target)
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{Bus, DebugView};
+
+    #[test]
+    fn suspending_keeps_the_stop_state() {
+        let bus = Bus::new();
+        let machine = DebugView(&bus);
+        let registers = Registers { program_counter: 0x8000, ..Registers::default() };
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x8000, None, None).unwrap();
+        debugger
+            .add_watchpoint(WatchpointSpec {
+                space: WatchSpace::Cpu,
+                start: 0x0300,
+                end: None,
+                read: false,
+                write: true,
+                execute: false,
+                condition: None,
+                hit_target: None,
+            })
+            .unwrap();
+        debugger.resume_from(0x8000);
+
+        debugger.set_suspended(true);
+        assert!(!debugger.is_active());
+        assert!(!debugger.is_watching());
+        debugger.set_suspended(false);
+        assert!(debugger.is_watching());
 
-    // 実行中のアドレスがブレークポイントに達したかをチェック
-    pub fn check_breakpoint(&self, pc: u16) -> bool {
-        self.breakpoints.contains(&pc)
+        // 再生前の resume_from が残っているので 1 回目は素通りする
+        assert!(debugger.check_breakpoint(&registers, &machine).is_none());
+        assert!(debugger.check_breakpoint(&registers, &machine).is_some());
+        assert_eq!(debugger.breakpoints()[0].hit_count, 1);
     }
 }
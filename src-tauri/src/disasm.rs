@@ -0,0 +1,178 @@
+// 6502 disassembler for the debugger: decodes memory into structured instructions.
+// Works on the live CPU address space (through a read function) or directly on a PRG ROM bank.
+use crate::cpu::{AddressingMode, Cpu6502};
+use crate::trace::operand_length;
+use serde::Serialize;
+use std::collections::HashMap;
+
+// Address -> symbol name
+pub type Labels = HashMap<u16, String>;
+
+// One per byte of the address space; larger requests are clamped to this
+pub const MAX_INSTRUCTIONS: usize = 0x10000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Instruction {
+    pub address: u16,
+    pub prg_offset: Option<usize>, // Position in PRG ROM when the address is ROM-backed
+    pub bytes: Vec<u8>,
+    pub mnemonic: &'static str,
+    pub operand: String,        // Formatted, with labels substituted
+    pub target: Option<u16>,    // Branch / JMP / JSR destination
+    pub unofficial: bool,
+    pub label: Option<String>,  // Label on this instruction's own address
+    pub comment: Option<String>,
+}
+
+impl Instruction {
+    // "LDA #$00" / "BNE loop"
+    pub fn text(&self) -> String {
+        if self.operand.is_empty() {
+            self.mnemonic.to_string()
+        } else {
+            format!("{} {}", self.mnemonic, self.operand)
+        }
+    }
+}
+
+fn is_control_flow(mnemonic: &str) -> bool {
+    matches!(mnemonic, "JMP" | "JSR")
+}
+
+// Decode the instruction at `address`; `read` must not have side effects
+pub fn decode(read: &impl Fn(u16) -> u8, address: u16, labels: &Labels) -> Instruction {
+    let opcode = read(address);
+    let (mode, _, name) = Cpu6502::opcode_info(opcode);
+    let (mnemonic, unofficial) = match name.strip_suffix('*') {
+        Some(name) => (name, true),
+        None => (name, name == "???"),
+    };
+
+    let len = operand_length(mode);
+    let bytes: Vec<u8> = (0..=len).map(|i| read(address.wrapping_add(i))).collect();
+    let b1 = bytes.get(1).copied().unwrap_or(0);
+    let abs = u16::from_le_bytes([b1, bytes.get(2).copied().unwrap_or(0)]);
+
+    let name_of = |addr: u16, width: usize| match labels.get(&addr) {
+        Some(label) => label.clone(),
+        None if width == 2 => format!("${:02X}", addr),
+        None => format!("${:04X}", addr),
+    };
+
+    let mut target = None;
+    let operand = match mode {
+        AddressingMode::Implied => String::new(),
+        AddressingMode::Accumulator => "A".to_string(),
+        AddressingMode::Immediate => format!("#${:02X}", b1),
+        AddressingMode::ZeroPage => name_of(b1 as u16, 2),
+        AddressingMode::ZeroPageX => format!("{},X", name_of(b1 as u16, 2)),
+        AddressingMode::ZeroPageY => format!("{},Y", name_of(b1 as u16, 2)),
+        AddressingMode::Relative => {
+            let dest = address.wrapping_add(2).wrapping_add(b1 as i8 as u16);
+            target = Some(dest);
+            name_of(dest, 4)
+        }
+        AddressingMode::Absolute => {
+            if is_control_flow(mnemonic) {
+                target = Some(abs);
+            }
+            name_of(abs, 4)
+        }
+        AddressingMode::AbsoluteX => format!("{},X", name_of(abs, 4)),
+        AddressingMode::AbsoluteY => format!("{},Y", name_of(abs, 4)),
+        AddressingMode::Indirect => format!("({})", name_of(abs, 4)),
+        AddressingMode::IndexedIndirect => format!("({},X)", name_of(b1 as u16, 2)),
+        AddressingMode::IndirectIndexed => format!("({}),Y", name_of(b1 as u16, 2)),
+    };
+
+    Instruction {
+        address,
+        prg_offset: None,
+        bytes,
+        mnemonic,
+        operand,
+        target,
+        unofficial,
+        label: labels.get(&address).cloned(),
+        comment: None,
+    }
+}
+
+// A byte known not to be code (from the code/data log), shown as ".byte $XX"
+pub fn data_byte(read: &impl Fn(u16) -> u8, address: u16, labels: &Labels) -> Instruction {
+    let value = read(address);
+    Instruction {
+        address,
+        prg_offset: None,
+        bytes: vec![value],
+        mnemonic: ".byte",
+        operand: format!("${:02X}", value),
+        target: None,
+        unofficial: false,
+        label: labels.get(&address).cloned(),
+        comment: None,
+    }
+}
+
+// `count` consecutive instructions starting at `start`; addresses for which `is_data` holds are emitted as data bytes
+pub fn disassemble(
+    read: &impl Fn(u16) -> u8,
+    start: u16,
+    count: usize,
+    labels: &Labels,
+    is_data: &impl Fn(u16) -> bool,
+) -> Vec<Instruction> {
+    let count = count.min(MAX_INSTRUCTIONS);
+    let mut out = Vec::with_capacity(count);
+    let mut address = start;
+    for _ in 0..count {
+        let instruction = if is_data(address) { data_byte(read, address, labels) } else { decode(read, address, labels) };
+        address = address.wrapping_add(instruction.bytes.len() as u16);
+        out.push(instruction);
+    }
+    out
+}
+
+// Offset of a PRG ROM bank, after checking that it exists and fits in the address space at `base`
+pub fn prg_bank_start(prg_len: usize, bank: usize, bank_size: usize, base: u16) -> Result<usize, String> {
+    if bank_size == 0 || !bank_size.is_power_of_two() {
+        return Err(format!("Invalid bank size: {}", bank_size));
+    }
+    if bank_size > 0x10000 - base as usize {
+        return Err(format!("A {} byte bank doesn't fit at ${:04X}", bank_size, base));
+    }
+    bank.checked_mul(bank_size)
+        .filter(|start| start.checked_add(bank_size).is_some_and(|end| end <= prg_len))
+        .ok_or_else(|| format!("PRG bank {} is out of range ({} bytes of PRG ROM)", bank, prg_len))
+}
+
+// Disassemble one PRG ROM bank as if it were mapped at `base`, whatever is mapped there right now.
+// `is_data` takes a PRG ROM offset.
+pub fn disassemble_prg_bank(
+    prg: &[u8],
+    bank: usize,
+    bank_size: usize,
+    base: u16,
+    count: usize,
+    labels: &Labels,
+    is_data: &impl Fn(usize) -> bool,
+) -> Result<Vec<Instruction>, String> {
+    let bank_start = prg_bank_start(prg.len(), bank, bank_size, base)?;
+    let data = &prg[bank_start..bank_start + bank_size];
+    let read = |addr: u16| data[(addr.wrapping_sub(base) as usize) % bank_size];
+
+    let mut out = Vec::new();
+    let mut address = base;
+    while out.len() < count && (address.wrapping_sub(base) as usize) < bank_size {
+        let offset = bank_start + address.wrapping_sub(base) as usize;
+        let mut instruction = if is_data(offset) { data_byte(&read, address, labels) } else { decode(&read, address, labels) };
+        instruction.prg_offset = Some(offset);
+        let next = address.wrapping_add(instruction.bytes.len() as u16);
+        out.push(instruction);
+        if next < address {
+            break; // Wrapped past $FFFF
+        }
+        address = next;
+    }
+    Ok(out)
+}
@@ -0,0 +1,161 @@
+// Emulation thread: runs the emulator at the console's own frame rate and pushes every frame
+// (plus the audio produced with it) to a sink, instead of the frontend polling for frames.
+// Input arrives over a channel so key events never wait on a running frame.
+use crate::apu;
+use crate::debugger::PauseInfo;
+use crate::emulator::{Emulator, HostFrame, TURBO_TIME_BUDGET};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Give up catching up after falling this many frames behind (e.g. after a debugger pause)
+const MAX_FRAMES_BEHIND: u32 = 4;
+
+pub enum EmuInput {
+    Key { code: String, pressed: bool },
+}
+
+pub struct FrameOutput {
+    pub frame_number: u32,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>, // RGBA
+    pub audio: Vec<f32>, // Mono, apu::SAMPLE_RATE
+    pub paused: Option<PauseInfo>, // The debugger stopped execution during this frame
+}
+
+impl FrameOutput {
+    // [u32 frame number][u16 width][u16 height] then RGBA pixels, little endian
+    pub fn encode_frame(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(8 + self.pixels.len());
+        out.extend_from_slice(&self.frame_number.to_le_bytes());
+        out.extend_from_slice(&(self.width as u16).to_le_bytes());
+        out.extend_from_slice(&(self.height as u16).to_le_bytes());
+        out.extend_from_slice(&self.pixels);
+        out
+    }
+}
+
+// [u32 sample rate] then f32 samples, little endian
+pub fn encode_audio(samples: &[f32]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + samples.len() * 4);
+    out.extend_from_slice(&apu::SAMPLE_RATE.to_le_bytes());
+    for sample in samples {
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+    out
+}
+
+pub struct EmulationThread {
+    input_tx: Sender<EmuInput>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EmulationThread {
+    pub fn spawn<F>(emulator: Arc<Mutex<Emulator>>, sink: F) -> Self
+    where
+        F: FnMut(FrameOutput) + Send + 'static,
+    {
+        let (input_tx, input_rx) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let handle = thread::Builder::new()
+            .name("emulation".to_string())
+            .spawn(move || run_loop(emulator, input_rx, thread_running, sink))
+            .expect("failed to spawn emulation thread");
+
+        Self { input_tx, running, handle: Some(handle) }
+    }
+
+    pub fn send_input(&self, input: EmuInput) -> Result<(), String> {
+        self.input_tx.send(input).map_err(|_| "Emulation thread has stopped".to_string())
+    }
+
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for EmulationThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run_loop<F>(emulator: Arc<Mutex<Emulator>>, input_rx: Receiver<EmuInput>, running: Arc<AtomicBool>, mut sink: F)
+where
+    F: FnMut(FrameOutput),
+{
+    let mut frame_number: u32 = 0;
+    let mut next_deadline = Instant::now();
+    let mut shown_pixels: Vec<u8> = Vec::new(); // Last frame handed to the sink
+
+    while running.load(Ordering::SeqCst) {
+        let Some(mut emu) = lock(&emulator) else { return };
+        for input in input_rx.try_iter() {
+            match input {
+                EmuInput::Key { code, pressed } => emu.handle_key_event(&code, pressed),
+            }
+        }
+        let frame_rate = emu.frame_rate();
+
+        // Emulate one frame per lock; commands from the UI get in between frames, even in turbo
+        let host_frame = emu.begin_host_frame();
+        let start = Instant::now();
+        let mut emulated = 0;
+        let result = loop {
+            let last = match host_frame {
+                HostFrame::Idle => break emu.get_frame(),
+                HostFrame::Frames(count) => emulated + 1 >= count,
+                HostFrame::Turbo => start.elapsed() >= TURBO_TIME_BUDGET,
+            };
+            let result = emu.run_host_subframe(last);
+            emulated += 1;
+            if last || result.is_err() || emu.paused {
+                break result;
+            }
+            drop(emu);
+            let Some(next) = lock(&emulator) else { return };
+            emu = next;
+        };
+        let audio = emu.take_audio_samples();
+        let paused = emu.take_pause_event();
+        drop(emu);
+
+        match result {
+            // Nothing to push while paused or in slow motion unless a command changed the picture
+            Ok(frame) if frame.pixels == shown_pixels && audio.is_empty() && paused.is_none() => {}
+            Ok(frame) => {
+                frame_number = frame_number.wrapping_add(1);
+                shown_pixels.clone_from(&frame.pixels);
+                sink(FrameOutput { frame_number, width: frame.width, height: frame.height, pixels: frame.pixels, audio, paused });
+            }
+            Err(e) => eprintln!("Frame execution error: {}", e),
+        }
+
+        let period = Duration::from_secs_f64(1.0 / frame_rate);
+        next_deadline += period;
+        let now = Instant::now();
+        if next_deadline > now {
+            thread::sleep(next_deadline - now);
+        } else if now - next_deadline > period * MAX_FRAMES_BEHIND {
+            next_deadline = now;
+        }
+    }
+}
+
+fn lock(emulator: &Mutex<Emulator>) -> Option<MutexGuard<'_, Emulator>> {
+    match emulator.lock() {
+        Ok(emu) => Some(emu),
+        Err(_) => {
+            eprintln!("Emulator mutex poisoned, stopping emulation thread");
+            None
+        }
+    }
+}
@@ -1,10 +1,26 @@
-use crate::bus::Bus;
+use crate::bus::{Bus, DebugView, MemorySpace};
 use crate::bus::BusAccess;
 use crate::cartridge::Cartridge;
 use crate::cpu::Cpu6502;
+use crate::debugger::{AccessKind, PauseInfo, PauseReason};
+use crate::disasm::{self, Instruction};
+use crate::cdl::CdlStats;
+use crate::cheats::{Cheat, CheatList};
+use crate::ppuview::{self, NametableView, PatternSource, SpriteView};
+use crate::ramsearch::{self, Comparison, Freeze, RamSearch, SearchResults, ValueSize};
+use crate::symbols::{Symbol, SymbolTable};
+use crate::fds::{self, FdsDisk, FdsDiskInfo, FdsMapper};
+use crate::patch;
+use crate::region::{GameDatabase, Region};
+use crate::rewind::{self, FrameInput, PlaybackFrame, RewindBuffer};
+use crate::savestate::{self, SaveState, StateReader, StateWriter};
+use serde::Serialize;
 use crate::ppu::{FrameData, Ppu};
 use crate::NesRom;
 use std::sync::atomic::AtomicU32;
+use std::path::{Path, PathBuf};
+use crate::trace::CpuTracer;
+use std::io::Write;
 use std::println;
 
 #[derive(Debug)]
@@ -27,6 +43,66 @@ pub struct Emulator {
     test_mode: bool,
     frame_complete: bool,
     irq_cooldown: bool, // Add IRQ cooldown flag
+    pub fds_bios_path: Option<String>, // disksys.rom, required for .fds images
+    rom_crc32: Option<u32>, // CRC32 of the loaded (patched) image, checked by save states
+    pub rewind_enabled: bool,
+    rewinding: bool, // Rewind key held: run_frame plays backwards
+    rewind: RewindBuffer,
+    // Speed control (applied by begin_host_frame)
+    speed: f64,
+    pub turbo: bool,
+    pub paused: bool,
+    frame_accumulator: f64,
+    region_override: Option<Region>, // None: use detected_region
+    detected_region: Region, // Header, then game database, then file name
+    game_database: GameDatabase,
+    pub symbols: SymbolTable, // Labels and comments shown by the disassembler
+    ram_search: RamSearch,
+    freezes: Vec<Freeze>, // Written back every frame
+    cheats: CheatList,
+    pub cheat_dir: Option<PathBuf>, // Where per-ROM cheat lists are kept; None: a "cheats" folder next to the ROM
+    pause_reason: Option<PauseReason>, // Set when the debugger stopped execution
+    pause_event_pending: bool, // The stop hasn't been reported to the frontend yet
+    frame_in_progress: bool, // A debugger stop left the current frame unfinished
+}
+
+pub const SAVE_STATE_SLOTS: u8 = 10;
+pub const MIN_SPEED: f64 = 0.1;
+pub const MAX_SPEED: f64 = 8.0;
+// Step over / step out / run-to give up after this much emulated time
+const MAX_STEP_FRAMES: u64 = 300;
+const OPCODE_JSR: u8 = 0x20;
+const OPCODE_RTS: u8 = 0x60;
+const OPCODE_RTI: u8 = 0x40;
+// Time a turbo host frame may spend emulating before the picture is handed back
+pub const TURBO_TIME_BUDGET: std::time::Duration = std::time::Duration::from_millis(14);
+
+// What one host display frame does (see emu_thread::run_loop)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostFrame {
+    Idle,        // No ROM, paused, or slow motion between frames: the picture stays as it is
+    Frames(u32), // Emulate this many frames, rendering only the last one
+    Turbo,       // Emulate frames until TURBO_TIME_BUDGET has passed
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpeedStatus {
+    pub speed: f64,
+    pub turbo: bool,
+    pub paused: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RegionStatus {
+    pub region: Region,
+    pub auto: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SaveSlotInfo {
+    pub slot: u8,
+    pub exists: bool,
+    pub modified_unix_secs: Option<u64>,
 }
 
 impl Emulator {
@@ -44,37 +120,84 @@ impl Emulator {
             test_mode: false,
             frame_complete: false,
             irq_cooldown: false, // Initialize IRQ cooldown
+            fds_bios_path: None,
+            rom_crc32: None,
+            rewind_enabled: true,
+            rewinding: false,
+            rewind: RewindBuffer::new(rewind::DEFAULT_SNAPSHOT_INTERVAL, rewind::DEFAULT_MEMORY_BUDGET),
+            speed: 1.0,
+            turbo: false,
+            paused: false,
+            frame_accumulator: 0.0,
+            region_override: None,
+            detected_region: Region::default(),
+            game_database: GameDatabase::load_default(),
+            symbols: SymbolTable::new(),
+            ram_search: RamSearch::new(),
+            freezes: Vec::new(),
+            cheats: CheatList::new(),
+            cheat_dir: None,
+            pause_reason: None,
+            pause_event_pending: false,
+            frame_in_progress: false,
         }
     }
 
     pub fn load_rom(&mut self, file_path: &str) -> Result<(), String> {
-        println!("ROM loading: {}", file_path);
-        let nes_rom = NesRom::from_file(file_path)
+        self.load_rom_with_patch(file_path, None)
+    }
+
+    // Load a ROM, soft-patching it with an IPS/UPS/BPS file first.
+    // Without an explicit patch, a patch with the same stem next to the ROM is applied if present.
+    pub fn load_rom_with_patch(&mut self, file_path: &str, patch_path: Option<&str>) -> Result<(), String> {
+        log::debug!("ROM loading: {}", file_path);
+        let image = NesRom::read_patched_file(Path::new(file_path), patch_path.map(Path::new))
             .map_err(|e| format!("ROM read error: {}", e))?;
 
-        let prg_rom = nes_rom.prg_rom.clone(); // Clone data to pass ownership
-        let chr_rom = nes_rom.chr_rom.clone();
-        let mapper_id = nes_rom.mapper_id;
-        let mirroring_flags = nes_rom.mirroring.into_flags(); // Get mirroring flags
-        
-        let cartridge = Cartridge::new(
-            prg_rom,
-            chr_rom,
-            mapper_id,
-            mirroring_flags,
-        )?; // Propagate error from Cartridge::new
+        // Persist pending disk writes of a previously inserted disk
+        self.flush_disk_writes();
+
+        let (cartridge, detected_region) = if fds::is_fds_image(Path::new(file_path), &image) {
+            // The disk system only exists for the Famicom
+            (self.create_fds_cartridge(file_path, &image)?, Some(Region::Ntsc))
+        } else {
+            let nes_rom = NesRom::from_bytes(&image).map_err(|e| format!("ROM read error: {}", e))?;
+
+            let prg_rom = nes_rom.prg_rom.clone(); // Clone data to pass ownership
+            let chr_rom = nes_rom.chr_rom.clone();
+            let mapper_id = nes_rom.mapper_id;
+            let mirroring_flags = nes_rom.mirroring.into_flags(); // Get mirroring flags
+            let detected_region = nes_rom
+                .region
+                .or_else(|| self.game_database.region(&nes_rom.prg_rom, &nes_rom.chr_rom));
+
+            let cartridge = Cartridge::new(
+                prg_rom,
+                chr_rom,
+                mapper_id,
+                mirroring_flags,
+            )?; // Propagate error from Cartridge::new
+            (cartridge, detected_region)
+        };
         
+        self.detected_region = detected_region
+            .or_else(|| Region::from_file_name(Path::new(file_path)))
+            .unwrap_or_default();
+        let region = self.region_override.unwrap_or(self.detected_region);
+        log::debug!("Region: {}", region.name());
+        self.bus.set_region(region);
+
         {
-            println!("Inserting cartridge into Bus");
+            log::debug!("Inserting cartridge into Bus");
             self.bus.insert_cartridge(cartridge);
             
             // Reset CPU
-            println!("CPU/PPU reset");
+            log::debug!("CPU/PPU reset");
             self.bus.reset();
             
             // Check CPU state after reset
             let cpu_state = self.bus.get_cpu_state();
-            println!("ROM read after CPU state: PC=${:04X}, A=${:02X}, X=${:02X}, Y=${:02X}, SP=${:02X}, status=${:02X}",
+            log::debug!("ROM read after CPU state: PC=${:04X}, A=${:02X}, X=${:02X}, Y=${:02X}, SP=${:02X}, status=${:02X}",
                     cpu_state.registers.program_counter,
                     cpu_state.registers.accumulator,
                     cpu_state.registers.x_register,
@@ -83,24 +206,196 @@ impl Emulator {
                     cpu_state.registers.status);
             
             if cpu_state.registers.stack_pointer != 0xFD {
-                println!("Warning: ROM read after stack pointer is not correct: ${:02X}", 
+                log::warn!("Warning: ROM read after stack pointer is not correct: ${:02X}", 
                          cpu_state.registers.stack_pointer);
                 self.bus.get_cpu_state_mut().registers.stack_pointer = 0xFD;
-                println!("Stack pointer corrected to 0xFD");
+                log::debug!("Stack pointer corrected to 0xFD");
             }
             
             // Check after test mode setting
             let cpu_state_after = self.bus.get_cpu_state();
-            println!("CPU state after test mode setting: SP=${:02X}", 
+            log::debug!("CPU state after test mode setting: SP=${:02X}", 
                     cpu_state_after.registers.stack_pointer);
         }
         
         self.is_running = true;
         self.rom_loaded = true;
         self.rom_path = Some(file_path.to_string());
+        self.rom_crc32 = Some(patch::crc32(&image));
+        self.rewind.clear();
+        self.rewinding = false;
+        self.frame_in_progress = false;
+        self.ram_search.reset();
+        self.freezes.clear();
+        if let Err(e) = self.load_cheats(file_path) {
+            eprintln!("Cheats not loaded: {}", e);
+        }
+        // A breakpoint stop belongs to the previous game
+        if self.pause_reason.take().is_some() {
+            self.paused = false;
+        }
+        self.pause_event_pending = false;
         Ok(())
     }
 
+    fn create_fds_cartridge(&self, file_path: &str, image: &[u8]) -> Result<Cartridge, String> {
+        let bios_path = self
+            .fds_bios_path
+            .as_ref()
+            .ok_or("FDS BIOS (disksys.rom) is not set")?;
+        let bios = std::fs::read(bios_path)
+            .map_err(|e| format!("Failed to read FDS BIOS {}: {}", bios_path, e))?;
+
+        let disk = FdsDisk::load(image, Some(fds::diff_path_for(Path::new(file_path))))?;
+        Cartridge::new_fds(bios, disk)
+    }
+
+    pub fn set_fds_bios_path(&mut self, path: &str) -> Result<(), String> {
+        let size = std::fs::metadata(path)
+            .map_err(|e| format!("Failed to read FDS BIOS {}: {}", path, e))?
+            .len();
+        if size as usize != fds::FDS_BIOS_SIZE {
+            return Err(format!("FDS BIOS must be {} bytes, got {} bytes", fds::FDS_BIOS_SIZE, size));
+        }
+        self.fds_bios_path = Some(path.to_string());
+        Ok(())
+    }
+
+    // Run `f` against the disk system, failing if the loaded game is not an FDS image
+    fn with_disk_system<R>(&self, f: impl FnOnce(&mut FdsMapper) -> Result<R, String>) -> Result<R, String> {
+        let cart = self.bus.cartridge().ok_or("No ROM loaded")?;
+        let mut cart = cart.lock().map_err(|e| format!("Failed to lock cartridge: {}", e))?;
+        let fds = cart.disk_system().ok_or("The loaded game is not a Famicom Disk System image")?;
+        f(fds)
+    }
+
+    pub fn fds_disk_info(&self) -> Result<FdsDiskInfo, String> {
+        self.with_disk_system(|fds| Ok(fds.disk_info()))
+    }
+
+    pub fn fds_eject_disk(&mut self) -> Result<(), String> {
+        self.with_disk_system(|fds| fds.eject_disk())
+    }
+
+    pub fn fds_insert_disk(&mut self, side: usize) -> Result<(), String> {
+        self.with_disk_system(|fds| fds.insert_disk(side))
+    }
+
+    pub fn fds_switch_side(&mut self) -> Result<(), String> {
+        self.with_disk_system(|fds| fds.switch_side())
+    }
+
+    // Write back any unsaved FDS disk changes to the diff file
+    pub fn flush_disk_writes(&mut self) {
+        if let Some(cart) = self.bus.cartridge() {
+            if let Some(fds) = cart.lock().unwrap().disk_system() {
+                if let Err(e) = fds.flush_disk() {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+    }
+
+    // Serialize the whole machine (CPU, RAM, PPU, controllers, DMA, mapper)
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        savestate::write_header(&mut w, self.rom_crc32.unwrap_or(0));
+        w.begin_section(b"EMU ");
+        w.write_u64(self.cycles_this_frame);
+        w.write_u32(self.frame_count.load(std::sync::atomic::Ordering::SeqCst));
+        w.write_bool(self.frame_complete);
+        self.bus.save_state(&mut w);
+        w.into_inner()
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        self.restore_state(data)?;
+        // The rewind history belongs to the previous timeline
+        self.rewind.clear();
+        self.frame_in_progress = false;
+        Ok(())
+    }
+
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), String> {
+        if !self.rom_loaded {
+            return Err("No ROM loaded".to_string());
+        }
+        let mut r = StateReader::new(data);
+        let header = savestate::read_header(&mut r)?;
+        let rom_crc32 = self.rom_crc32.unwrap_or(0);
+        if header.rom_crc32 != rom_crc32 {
+            return Err(format!(
+                "Save state was made for a different ROM (CRC32 {:08X}, loaded ROM is {:08X})",
+                header.rom_crc32, rom_crc32
+            ));
+        }
+
+        // Restore into a copy first so a corrupt state cannot leave the machine half-loaded
+        let backup = self.save_state();
+        let result = self.load_state_sections(&mut r).and_then(|_| {
+            if r.is_at_end() {
+                Ok(())
+            } else {
+                Err("Save state has trailing data".to_string())
+            }
+        });
+        if let Err(e) = result {
+            let mut r = StateReader::new(&backup);
+            savestate::read_header(&mut r)?;
+            self.load_state_sections(&mut r)?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn load_state_sections(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.expect_section(b"EMU ")?;
+        self.cycles_this_frame = r.read_u64()?;
+        self.frame_count.store(r.read_u32()?, std::sync::atomic::Ordering::SeqCst);
+        self.frame_complete = r.read_bool()?;
+        self.bus.load_state(r)
+    }
+
+    // Slot files live next to the ROM: game.nes -> game.ss0 ... game.ss9
+    fn state_slot_path(&self, slot: u8) -> Result<PathBuf, String> {
+        if slot >= SAVE_STATE_SLOTS {
+            return Err(format!("Invalid save slot {} (0-{})", slot, SAVE_STATE_SLOTS - 1));
+        }
+        let rom_path = self.rom_path.as_ref().ok_or("No ROM loaded")?;
+        Ok(Path::new(rom_path).with_extension(format!("ss{}", slot)))
+    }
+
+    pub fn save_state_to_slot(&self, slot: u8) -> Result<(), String> {
+        let path = self.state_slot_path(slot)?;
+        std::fs::write(&path, self.save_state())
+            .map_err(|e| format!("Failed to write save state {}: {}", path.display(), e))?;
+        log::info!("State saved to slot {} ({})", slot, path.display());
+        Ok(())
+    }
+
+    pub fn load_state_from_slot(&mut self, slot: u8) -> Result<(), String> {
+        let path = self.state_slot_path(slot)?;
+        let data = std::fs::read(&path)
+            .map_err(|e| format!("Failed to read save state {}: {}", path.display(), e))?;
+        self.load_state(&data)?;
+        log::info!("State loaded from slot {} ({})", slot, path.display());
+        Ok(())
+    }
+
+    pub fn list_state_slots(&self) -> Result<Vec<SaveSlotInfo>, String> {
+        (0..SAVE_STATE_SLOTS)
+            .map(|slot| {
+                let metadata = std::fs::metadata(self.state_slot_path(slot)?).ok();
+                let modified_unix_secs = metadata
+                    .as_ref()
+                    .and_then(|m| m.modified().ok())
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs());
+                Ok(SaveSlotInfo { slot, exists: metadata.is_some(), modified_unix_secs })
+            })
+            .collect()
+    }
+
     pub fn handle_key_event(&mut self, key_code: &str, pressed: bool) {
         let btn = match key_code {
             "KeyZ" => Some(crate::controller::Button::A),
@@ -124,38 +419,474 @@ impl Emulator {
             return Ok(FrameData::default());
         }
 
-        let max_cycles: u32 = 30000; // Prevent infinite loops
+        if self.rewinding {
+            return self.rewind_one_frame();
+        }
+        // A frame resumed after a debugger stop was already recorded when it started
+        if self.rewind_enabled && !self.frame_in_progress {
+            self.record_rewind_frame();
+        }
+        self.emulate_frame()
+    }
+
+    // Called once per host display frame: applies pause, speed multiplier and turbo.
+    // The caller runs the frames one at a time with run_host_subframe so other users of the emulator
+    // can get in between them.
+    pub fn begin_host_frame(&mut self) -> HostFrame {
+        if !self.rom_loaded {
+            return HostFrame::Idle;
+        }
+        if self.bus.test_mode || self.rewinding {
+            return HostFrame::Frames(1);
+        }
+        if self.paused {
+            return HostFrame::Idle;
+        }
+        if self.turbo {
+            return HostFrame::Turbo;
+        }
+
+        self.frame_accumulator += self.speed;
+        let frames = self.frame_accumulator.floor() as u32;
+        self.frame_accumulator -= frames as f64;
+        // Slow motion: keep showing the previous frame
+        if frames == 0 { HostFrame::Idle } else { HostFrame::Frames(frames) }
+    }
+
+    // One emulated frame of a host frame; frames that won't be shown skip pixel output
+    pub fn run_host_subframe(&mut self, render: bool) -> Result<FrameData, String> {
+        if self.bus.test_mode {
+            return self.get_ppu_test_frame();
+        }
+        self.bus.ppu.borrow_mut().skip_rendering = !render;
+        let result = self.run_frame();
+        self.bus.ppu.borrow_mut().skip_rendering = false;
+        result
+    }
+
+    // Frames per second of the emulated console, used to pace the emulation thread
+    pub fn frame_rate(&self) -> f64 {
+        self.bus.region().frame_rate()
+    }
+
+    // nestest-format CPU trace to a writer (None stops tracing)
+    pub fn set_trace_output(&mut self, output: Option<Box<dyn Write + Send>>) {
+        let tracer = output.map(CpuTracer::to_writer);
+        self.bus.cpu.borrow_mut().tracer = tracer;
+    }
+
+    // Keep only the newest `capacity` trace lines in memory for the debugger UI
+    pub fn start_trace_ring(&mut self, capacity: usize) {
+        self.bus.cpu.borrow_mut().tracer = Some(CpuTracer::ring(capacity));
+    }
+
+    pub fn trace_lines(&self, max: usize) -> Vec<String> {
+        match &self.bus.cpu.borrow().tracer {
+            Some(tracer) => tracer.recent_lines(max),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn region_status(&self) -> RegionStatus {
+        RegionStatus { region: self.bus.region(), auto: self.region_override.is_none() }
+    }
+
+    // Force a region (None = automatic). Takes effect immediately and for later ROM loads.
+    pub fn set_region_override(&mut self, region: Option<Region>) {
+        self.region_override = region;
+        let region = region.unwrap_or(self.detected_region);
+        if region != self.bus.region() {
+            self.bus.set_region(region);
+            // Snapshots taken under the old timing can't be replayed frame-exactly
+            self.rewind.clear();
+        }
+    }
+
+    // Replace the game database used for region detection; returns the number of games.
+    // Applies to ROMs loaded afterwards.
+    pub fn load_game_database(&mut self, path: &str) -> Result<usize, String> {
+        self.game_database = GameDatabase::load(Path::new(path))?;
+        Ok(self.game_database.len())
+    }
+
+    // Audio produced since the last call (mono, apu::SAMPLE_RATE).
+    // Nothing is returned while rewinding since the replayed audio would play backwards in chunks.
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+        let samples = self.bus.take_audio_samples();
+        if self.rewinding {
+            return Vec::new();
+        }
+        samples
+    }
+
+    pub fn set_speed(&mut self, multiplier: f64) -> Result<(), String> {
+        if !(MIN_SPEED..=MAX_SPEED).contains(&multiplier) {
+            return Err(format!("Speed must be between {}x and {}x", MIN_SPEED, MAX_SPEED));
+        }
+        self.speed = multiplier;
+        self.frame_accumulator = 0.0;
+        Ok(())
+    }
+
+    pub fn speed_status(&self) -> SpeedStatus {
+        SpeedStatus { speed: self.speed, turbo: self.turbo, paused: self.paused }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if !paused {
+            self.resume_debugger();
+        }
+        self.paused = paused;
+    }
+
+    // Run exactly one frame and stay paused afterwards
+    pub fn frame_advance(&mut self) -> Result<FrameData, String> {
+        self.resume_debugger();
+        self.paused = true;
+        self.rewinding = false;
+        self.run_frame()
+    }
+
+    // --- Debugger ---
+
+    fn resume_debugger(&mut self) {
+        let pc = self.bus.cpu.borrow().registers.program_counter;
+        // Only stops before executing `pc` would fire again at once; after a read/write
+        // watchpoint the next instruction hasn't been checked yet, so don't skip it
+        let stopped_at_pc = match self.pause_reason.take() {
+            Some(PauseReason::Breakpoint { address }) => address == pc,
+            Some(PauseReason::Watchpoint { access: AccessKind::Execute, address, .. }) => address == pc,
+            _ => false,
+        };
+        if stopped_at_pc {
+            self.bus.debugger.borrow_mut().resume_from(pc);
+        }
+        self.pause_event_pending = false;
+    }
+
+    fn pause_for_debugger(&mut self, reason: PauseReason) {
+        self.paused = true;
+        self.turbo = false;
+        self.pause_reason = Some(reason);
+        self.pause_event_pending = true;
+    }
+
+    fn check_breakpoints(&mut self) -> Option<PauseReason> {
+        let registers = self.bus.cpu.borrow().registers.clone();
+        self.bus.debugger.borrow_mut().check_breakpoint(&registers, &DebugView(&self.bus))
+    }
+
+    // Why and where the debugger stopped execution, if it did
+    pub fn pause_info(&self) -> Option<PauseInfo> {
+        let reason = self.pause_reason.clone()?;
+        let (scanline, dot) = self.bus.ppu_position();
+        let frame = self.bus.ppu.borrow().frame_counter;
+        Some(PauseInfo { reason, cpu: self.bus.get_cpu_state(), scanline, dot, frame })
+    }
+
+    // Stop on request from an external debugger; an existing stop keeps its reason
+    pub fn halt(&mut self) {
+        if self.pause_reason.is_none() {
+            self.pause_for_debugger(PauseReason::Halt);
+        }
+    }
+
+    // Step into: execute exactly one instruction
+    pub fn step_into(&mut self) -> Result<PauseInfo, String> {
+        self.run_until(|_| true)
+    }
+
+    // Step over: a JSR and the whole subroutine it calls count as one step
+    pub fn step_over(&mut self) -> Result<PauseInfo, String> {
+        let (pc, sp) = self.pc_and_sp();
+        if self.bus.debug_read(pc) != OPCODE_JSR {
+            return self.step_into();
+        }
+        let return_addr = pc.wrapping_add(3);
+        // Recursive calls come back to the same address deeper in the stack
+        self.run_until(move |emu| {
+            let (pc, current_sp) = emu.pc_and_sp();
+            pc == return_addr && current_sp >= sp
+        })
+    }
+
+    // Step out: run until an RTS/RTI at the current stack depth has returned
+    pub fn step_out(&mut self) -> Result<PauseInfo, String> {
+        let (pc, start_sp) = self.pc_and_sp();
+        let mut returning = matches!(self.bus.debug_read(pc), OPCODE_RTS | OPCODE_RTI);
+        self.run_until(move |emu| {
+            if returning {
+                return true;
+            }
+            // Calls and interrupts made from here push below the starting stack pointer
+            let (pc, sp) = emu.pc_and_sp();
+            returning = matches!(emu.bus.debug_read(pc), OPCODE_RTS | OPCODE_RTI) && sp >= start_sp;
+            false
+        })
+    }
+
+    // Run until the PPU enters `scanline` (the pre-render line is the last one, as in traces)
+    pub fn step_to_scanline(&mut self, scanline: isize) -> Result<PauseInfo, String> {
+        let lines = self.bus.region().scanlines_per_frame();
+        if !(0..lines).contains(&scanline) {
+            return Err(format!("Scanline must be between 0 and {}", lines - 1));
+        }
+        let mut previous = self.bus.ppu_position().0;
+        self.run_until(move |emu| {
+            let current = emu.bus.ppu_position().0;
+            let entered = current == scanline && previous != scanline;
+            previous = current;
+            entered
+        })
+    }
+
+    pub fn step_to_next_frame(&mut self) -> Result<PauseInfo, String> {
+        let start_frame = self.bus.ppu.borrow().frame_counter;
+        self.run_until(move |emu| emu.bus.ppu.borrow().frame_counter != start_frame)
+    }
+
+    // Run until PC reaches `addr` (before that instruction executes)
+    pub fn run_to_address(&mut self, addr: u16) -> Result<PauseInfo, String> {
+        self.run_until(move |emu| emu.pc_and_sp().0 == addr)
+    }
+
+    fn pc_and_sp(&self) -> (u16, u8) {
+        let cpu = self.bus.cpu.borrow();
+        (cpu.registers.program_counter, cpu.registers.stack_pointer)
+    }
+
+    // Execute instructions until `done` returns true after one of them, leaving the emulator paused.
+    // Breakpoints and watchpoints still stop execution on the way.
+    fn run_until(&mut self, mut done: impl FnMut(&mut Self) -> bool) -> Result<PauseInfo, String> {
+        if !self.rom_loaded {
+            return Err("No ROM loaded".to_string());
+        }
+        self.paused = true;
+        self.rewinding = false;
+        // Don't stop on the breakpoint we are standing on
+        let (pc, _) = self.pc_and_sp();
+        self.bus.debugger.borrow_mut().resume_from(pc);
+
+        let max_cycles = self.bus.region().cpu_cycles_per_frame() as u64 * MAX_STEP_FRAMES;
+        let mut cycles: u64 = 0;
+        let reason = loop {
+            if let Some(reason) = self.check_breakpoints() {
+                break reason;
+            }
+            // Record each frame the steps start, like run_frame does, so rewind positions stay one per frame
+            if self.rewind_enabled && !self.frame_in_progress {
+                self.record_rewind_frame();
+            }
+            self.frame_in_progress = true;
+            cycles += self.execute_instruction() as u64;
+            // Stepping stops mid-frame; the next run_frame simply runs to the end of the current one
+            if self.bus.is_frame_complete() {
+                self.bus.reset_frame_complete();
+                self.frame_in_progress = false;
+            }
+            let hit = self.bus.debugger.borrow_mut().take_watch_hit();
+            if let Some(reason) = hit {
+                break reason;
+            }
+            if done(self) {
+                break PauseReason::Step { completed: true };
+            }
+            if cycles >= max_cycles {
+                break PauseReason::Step { completed: false };
+            }
+        };
+
+        self.pause_reason = Some(reason);
+        self.pause_event_pending = false; // Reported through the return value
+        self.pause_info().ok_or_else(|| "Debugger state unavailable".to_string())
+    }
+
+    // The pause info once per stop, for pushing to the frontend
+    pub fn take_pause_event(&mut self) -> Option<PauseInfo> {
+        if !std::mem::take(&mut self.pause_event_pending) {
+            return None;
+        }
+        self.pause_info()
+    }
+
+    // --- Rewind ---
+
+    fn current_input(&self) -> FrameInput {
+        FrameInput {
+            controller1: self.bus.controller1.borrow().button_states(),
+            controller2: self.bus.controller2.borrow().button_states(),
+        }
+    }
+
+    fn apply_input(&self, input: FrameInput) {
+        self.bus.controller1.borrow_mut().set_button_states(input.controller1);
+        self.bus.controller2.borrow_mut().set_button_states(input.controller2);
+    }
+
+    fn record_rewind_frame(&mut self) {
+        if self.rewind.wants_snapshot() {
+            let state = self.save_state();
+            self.rewind.push_snapshot(state);
+        }
+        let input = self.current_input();
+        self.rewind.record_frame(input);
+    }
+
+    pub fn configure_rewind(&mut self, enabled: bool, interval_frames: u32, memory_budget_bytes: usize) {
+        self.rewind_enabled = enabled;
+        self.rewind.configure(interval_frames, memory_budget_bytes);
+        if !enabled {
+            self.rewind.clear();
+            self.rewinding = false;
+        }
+    }
+
+    // Hold/release the rewind key: while held, run_frame steps backwards one frame per call
+    pub fn set_rewinding(&mut self, active: bool) {
+        self.rewinding = active && self.rewind_enabled;
+    }
+
+    pub fn is_rewinding(&self) -> bool {
+        self.rewinding
+    }
+
+    // Go back `frames` frames and return the picture of the frame we land on
+    pub fn rewind_step(&mut self, frames: u32) -> Result<FrameData, String> {
+        if !self.rom_loaded {
+            return Err("No ROM loaded".to_string());
+        }
+        let mut frame = self.bus.get_ppu_frame();
+        for _ in 0..frames.max(1) {
+            frame = self.rewind_one_frame()?;
+        }
+        Ok(frame)
+    }
+
+    fn rewind_one_frame(&mut self) -> Result<FrameData, String> {
+        let position = self.rewind.position();
+        // The picture for a position is produced by emulating from an earlier snapshot
+        let can_rewind = self.rewind.oldest_position().is_some_and(|oldest| position > oldest + 1);
+        if !can_rewind {
+            return Ok(self.bus.get_ppu_frame());
+        }
+        let target = position - 1;
+        let live_input = self.current_input();
+
+        let frame = match self.rewind.take_playback_frame(target) {
+            Some(cached) => {
+                self.restore_state(&cached.state)?;
+                cached.frame
+            }
+            None => self.replay_to(target)?,
+        };
+
+        self.rewind.truncate_to(target);
+        self.frame_in_progress = false;
+        self.bus.set_ppu_frame(frame.clone());
+        // Keep the buttons the player is holding right now
+        self.apply_input(live_input);
+        Ok(frame)
+    }
+
+    // Re-emulate from the closest snapshot up to `target`, caching the frames in between
+    fn replay_to(&mut self, target: u64) -> Result<FrameData, String> {
+        let (start, state) = self
+            .rewind
+            .snapshot_before(target - 1)
+            .ok_or("Rewind snapshot is missing")?;
+        self.restore_state(&state)?;
+
+        // Frames that already ran once must not stop at breakpoints or watchpoints again
+        self.bus.debugger.borrow_mut().set_suspended(true);
+        let result = self.replay_frames(start, target);
+        self.bus.debugger.borrow_mut().set_suspended(false);
+        result
+    }
+
+    fn replay_frames(&mut self, start: u64, target: u64) -> Result<FrameData, String> {
+        let mut playback = Vec::new();
+        let mut frame = FrameData::default();
+        for position in start..target {
+            let input = self.rewind.input_at(position).ok_or("Rewind input log is incomplete")?;
+            self.apply_input(input);
+            frame = self.emulate_frame()?;
+            if position + 1 < target {
+                playback.push(PlaybackFrame { position: position + 1, state: self.save_state(), frame: frame.clone() });
+            }
+        }
+        self.rewind.set_playback(playback);
+        Ok(frame)
+    }
+
+    // One CPU instruction (or interrupt) and the PPU dots, APU/mapper clocks and NMI edge that go with it
+    fn execute_instruction(&mut self) -> u32 {
+        if self.bus.cdl.borrow().is_logging() {
+            let pc = self.bus.cpu.borrow().registers.program_counter;
+            let opcode = self.bus.debug_read(pc);
+            let operand = u16::from_le_bytes([self.bus.debug_read(pc.wrapping_add(1)), self.bus.debug_read(pc.wrapping_add(2))]);
+            self.bus.cdl.borrow_mut().begin_instruction(pc, opcode, operand);
+        }
+        let step_cycles = {
+            // Get raw pointer to bus
+            let bus_ptr = &mut self.bus as *mut Bus;
+            // Get mutable reference to CPU within the bus
+            let mut cpu_ref = self.bus.cpu.borrow_mut();
+            // Call step unsafely, passing the dereferenced bus pointer
+            unsafe { cpu_ref.step(&mut *bus_ptr) as u32 }
+        };
+        self.bus.total_cycles += step_cycles as u64;
+
+        // PPUをCPUサイクルの3倍（PALは3.2倍）ステップさせる
+        for _ in 0..self.bus.ppu_dots_for(step_cycles as u64) {
+            self.bus.step_ppu();
+        }
+
+        // APUとマッパー（FDSタイマー/ディスクドライブなど）もCPUサイクル分進める
+        self.bus.clock_peripherals(step_cycles as u64);
+
+        // NMIチェック
+        let current_nmi_line = self.bus.ppu.borrow().nmi_line_low;
+        if !current_nmi_line && self.bus.prev_nmi_line { // Falling edge
+            if self.bus.ppu.borrow().ctrl.generate_nmi() {
+                self.bus.cpu.borrow_mut().trigger_nmi();
+            }
+        }
+        self.bus.prev_nmi_line = current_nmi_line; // Update previous state
+        step_cycles
+    }
+
+    fn emulate_frame(&mut self) -> Result<FrameData, String> {
+        let max_cycles: u32 = self.bus.region().cpu_cycles_per_frame() + 256; // Prevent infinite loops
         let mut total_cycles: u32 = 0;
         let mut frame_complete = false;
+        self.apply_freezes();
 
         while !frame_complete && total_cycles < max_cycles {
-            let step_cycles = {
-                // Get raw pointer to bus
-                let bus_ptr = &mut self.bus as *mut Bus;
-                // Get mutable reference to CPU within the bus
-                let mut cpu_ref = self.bus.cpu.borrow_mut();
-                // Call step unsafely, passing the dereferenced bus pointer
-                unsafe { cpu_ref.step(&mut *bus_ptr) as u32 }
-            };
-            total_cycles += step_cycles;
-
-            // PPUをCPUサイクルの3倍ステップさせる
-            for _ in 0..(step_cycles * 3) {
-                self.bus.step_ppu();
+            let debugging = self.bus.debugger.borrow().is_active();
+            if debugging {
+                if let Some(reason) = self.check_breakpoints() {
+                    // Stop before the instruction runs; the rest of the frame continues on resume
+                    self.pause_for_debugger(reason);
+                    self.frame_in_progress = true;
+                    break;
+                }
             }
+            total_cycles += self.execute_instruction();
 
-            // NMIチェック
-            let current_nmi_line = self.bus.ppu.borrow().nmi_line_low;
-            if !current_nmi_line && self.bus.prev_nmi_line { // Falling edge
-                if self.bus.ppu.borrow().ctrl.generate_nmi() {
-                    self.bus.cpu.borrow_mut().trigger_nmi();
+            // Read/write watchpoints stop after the accessing instruction has finished
+            if debugging {
+                let hit = self.bus.debugger.borrow_mut().take_watch_hit();
+                if let Some(reason) = hit {
+                    self.pause_for_debugger(reason);
+                    self.frame_in_progress = true;
+                    break;
                 }
             }
-            self.bus.prev_nmi_line = current_nmi_line; // Update previous state
 
             frame_complete = self.bus.is_frame_complete();
             if frame_complete {
                 self.bus.reset_frame_complete();
+                self.frame_in_progress = false;
                 break;
             }
         }
@@ -211,11 +942,11 @@ impl Emulator {
         //          start_cpu_state.registers.y_register,
         //          start_cpu_state.registers.stack_pointer);
 
-        const TARGET_CYCLES_PER_FRAME: u64 = 29780; // NTSC
+        let target_cycles_per_frame = self.bus.region().cpu_cycles_per_frame() as u64;
         let mut cycles_executed: u64 = 0;
         self.frame_complete = false;
 
-        while cycles_executed < TARGET_CYCLES_PER_FRAME {
+        while cycles_executed < target_cycles_per_frame {
             let step_cycles = self.bus.clock(); // Bus::clock returns cycles executed by CPU
 
             cycles_executed += step_cycles;
@@ -245,25 +976,281 @@ impl Emulator {
         self.bus.get_ppu_test_frame()
     }
 
+    // Disassemble the CPU address space as currently mapped (reads have no side effects).
+    // Bytes the code/data log has only seen read as data are shown as .byte.
+    pub fn disassemble(&self, start: u16, count: usize) -> Vec<Instruction> {
+        let labels = {
+            let cart = self.bus.cartridge();
+            let cart = cart.as_ref().map(|c| c.lock().unwrap());
+            self.symbols.labels(&|addr| cart.as_ref()?.prg_rom_offset(addr))
+        };
+        let read = |addr: u16| self.bus.debug_read(addr);
+        let cdl = self.bus.cdl.borrow();
+        let is_data = |addr: u16| self.prg_rom_offset(addr).is_some_and(|o| cdl.is_data(o));
+        let mut instructions = disasm::disassemble(&read, start, count, &labels, &is_data);
+        for instruction in &mut instructions {
+            instruction.prg_offset = self.prg_rom_offset(instruction.address);
+            instruction.comment = self.symbol_at(instruction.address).and_then(|s| s.comment.clone());
+        }
+        instructions
+    }
+
+    // Where a CPU address is currently mapped in PRG ROM
+    pub fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.bus.cartridge()?.lock().unwrap().prg_rom_offset(addr)
+    }
+
+    // Disassemble a PRG ROM bank as if mapped at `base`, regardless of the current mapping
+    pub fn disassemble_prg_bank(&self, bank: usize, bank_size: usize, base: u16, count: usize) -> Result<Vec<Instruction>, String> {
+        let cart = self.bus.cartridge().ok_or("No ROM loaded")?;
+        let cart = cart.lock().unwrap();
+        let bank_start = disasm::prg_bank_start(cart.prg_rom().len(), bank, bank_size, base)?;
+        let labels = self.symbols.bank_labels(bank_start, bank_size, base);
+        let cdl = self.bus.cdl.borrow();
+        let mut instructions =
+            disasm::disassemble_prg_bank(cart.prg_rom(), bank, bank_size, base, count, &labels, &|o| cdl.is_data(o))?;
+        for instruction in &mut instructions {
+            instruction.comment = self.symbols.at(instruction.address, instruction.prg_offset).and_then(|s| s.comment.clone());
+        }
+        Ok(instructions)
+    }
+
+    // Label an address; in PRG ROM the label belongs to the bank that is mapped there now
+    pub fn set_label(&mut self, addr: u16, name: Option<String>) {
+        let prg_offset = self.prg_rom_offset(addr);
+        match name.filter(|n| !n.is_empty()) {
+            Some(name) => self.symbols.insert(Symbol::new(&name, Some(addr), prg_offset, None)),
+            None => self.symbols.remove(addr, prg_offset),
+        }
+    }
+
+    // Symbols from a ca65 .dbg, FCEUX .nl or Mesen .mlb file
+    pub fn import_symbols(&mut self, path: &str) -> Result<usize, String> {
+        self.symbols.import_file(Path::new(path))
+    }
+
+    pub fn symbol_at(&self, addr: u16) -> Option<&Symbol> {
+        self.symbols.at(addr, self.prg_rom_offset(addr))
+    }
+
+    // CPU address of a label, e.g. for setting a breakpoint on it
+    pub fn resolve_symbol(&self, name: &str) -> Result<u16, String> {
+        let symbol = self.symbols.find(name).ok_or_else(|| format!("Unknown label: {}", name))?;
+        symbol
+            .cpu_address(&|addr| self.prg_rom_offset(addr))
+            .ok_or_else(|| format!("Label {} is in PRG bank {}, which isn't mapped", name, symbol.bank.unwrap_or_default()))
+    }
+
+    // Code/data logging (FCEUX .cdl); the log is cleared when a ROM is loaded
+    pub fn set_cdl_logging(&mut self, logging: bool) -> Result<(), String> {
+        if logging && !self.rom_loaded {
+            return Err("No ROM loaded".to_string());
+        }
+        self.bus.cdl.get_mut().set_logging(logging);
+        Ok(())
+    }
+
+    pub fn reset_cdl(&mut self) {
+        self.bus.cdl.get_mut().reset();
+    }
+
+    pub fn save_cdl(&self, path: &str) -> Result<(), String> {
+        self.bus.cdl.borrow().save(Path::new(path))
+    }
+
+    pub fn load_cdl(&mut self, path: &str) -> Result<(), String> {
+        self.bus.cdl.get_mut().load(Path::new(path))
+    }
+
+    pub fn cdl_stats(&self) -> CdlStats {
+        self.bus.cdl.borrow().stats()
+    }
+
+    // RAM search over internal RAM and PRG RAM; every call below is relative to the last snapshot
+    pub fn start_ram_search(&mut self, size: ValueSize, signed: bool) {
+        self.ram_search.start(&DebugView(&self.bus), size, signed);
+    }
+
+    pub fn reset_ram_search(&mut self) {
+        self.ram_search.reset();
+    }
+
+    pub fn snapshot_ram_search(&mut self) {
+        self.ram_search.snapshot(&DebugView(&self.bus));
+    }
+
+    // Keep candidates that compare true against `constant`, or against their previous value when None
+    pub fn filter_ram_search(&mut self, comparison: Comparison, constant: Option<i32>) {
+        self.ram_search.filter(&DebugView(&self.bus), comparison, constant);
+    }
+
+    pub fn ram_search_results(&self, limit: usize) -> SearchResults {
+        self.ram_search.results(&DebugView(&self.bus), limit)
+    }
+
+    // Rewrite an address every frame; without a value it keeps what is there now.
+    // The width defaults to the one of the current RAM search.
+    pub fn freeze(&mut self, address: u16, value: Option<i32>, size: Option<ValueSize>) -> Result<Freeze, String> {
+        let size = size.unwrap_or(self.ram_search.size());
+        ramsearch::check_ram_address(address, size)?;
+        let value = match value {
+            Some(value) => ramsearch::encode_value(value, size)?,
+            None => {
+                let lo = self.bus.debug_read(address);
+                let hi = if size == ValueSize::Word { self.bus.debug_read(address.wrapping_add(1)) } else { 0 };
+                u16::from_le_bytes([lo, hi])
+            }
+        };
+        let freeze = Freeze { address, value, size };
+        self.freezes.retain(|f| f.address != address);
+        self.freezes.push(freeze);
+        self.apply_freezes();
+        Ok(freeze)
+    }
+
+    pub fn unfreeze(&mut self, address: u16) {
+        self.freezes.retain(|f| f.address != address);
+    }
+
+    pub fn freezes(&self) -> &[Freeze] {
+        &self.freezes
+    }
+
+    // Frozen addresses and RAM cheat codes, written at the start of every frame
+    fn apply_freezes(&mut self) {
+        for freeze in &self.freezes {
+            let [lo, hi] = freeze.value.to_le_bytes();
+            self.bus.debug_write(freeze.address, lo);
+            if freeze.size == ValueSize::Word {
+                self.bus.debug_write(freeze.address.wrapping_add(1), hi);
+            }
+        }
+        for (address, value) in self.cheats.ram_writes() {
+            self.bus.debug_write(address, value);
+        }
+    }
+
+    fn load_cheats(&mut self, rom_path: &str) -> Result<(), String> {
+        let dir = match &self.cheat_dir {
+            Some(dir) => dir.clone(),
+            None => Path::new(rom_path).parent().unwrap_or(Path::new("")).join("cheats"),
+        };
+        let result = self.cheats.load_for_rom(&dir, self.rom_crc32.unwrap_or(0));
+        self.bus.set_rom_patches(self.cheats.rom_patches());
+        result
+    }
+
+    // Game Genie or RAM code; saved to the loaded ROM's cheat file right away
+    pub fn add_cheat(&mut self, code: &str, description: &str) -> Result<Cheat, String> {
+        if !self.rom_loaded {
+            return Err("No ROM loaded".to_string());
+        }
+        let cheat = self.cheats.add(code, description)?;
+        self.bus.set_rom_patches(self.cheats.rom_patches());
+        Ok(cheat)
+    }
+
+    pub fn remove_cheat(&mut self, code: &str) -> Result<(), String> {
+        self.cheats.remove(code)?;
+        self.bus.set_rom_patches(self.cheats.rom_patches());
+        Ok(())
+    }
+
+    pub fn set_cheat_enabled(&mut self, code: &str, enabled: bool) -> Result<(), String> {
+        self.cheats.set_enabled(code, enabled)?;
+        self.bus.set_rom_patches(self.cheats.rom_patches());
+        Ok(())
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        self.cheats.all()
+    }
+
+    // Memory editor: ranges of any address space, read without side effects
+    pub fn read_memory(&self, space: MemorySpace, start: usize, length: usize) -> Result<Vec<u8>, String> {
+        self.check_memory_range(space, start, length)?;
+        Ok((start..start + length).map(|addr| self.bus.peek_memory(space, addr)).collect())
+    }
+
+    pub fn write_memory(&mut self, space: MemorySpace, start: usize, data: &[u8]) -> Result<(), String> {
+        self.check_memory_range(space, start, data.len())?;
+        // Writing the I/O registers would scroll the PPU, start DMA, key off APU channels and so on
+        if space == MemorySpace::Cpu && start < 0x6000 && start + data.len() > 0x2000 {
+            return Err("$2000-$5FFF are I/O registers; edit PPU memory through the VRAM, palette or OAM spaces".to_string());
+        }
+        for (i, &byte) in data.iter().enumerate() {
+            self.bus.poke_memory(space, start + i, byte);
+        }
+        Ok(())
+    }
+
+    pub fn memory_size(&self, space: MemorySpace) -> usize {
+        self.bus.memory_size(space)
+    }
+
+    fn check_memory_range(&self, space: MemorySpace, start: usize, length: usize) -> Result<(), String> {
+        let size = self.bus.memory_size(space);
+        if start.checked_add(length).is_none_or(|end| end > size) {
+            return Err(format!("${:X}+{} is outside {:?} memory (${:X} bytes)", start, length, space, size));
+        }
+        Ok(())
+    }
+
+    // Pattern tables as 128x128 images drawn with one of the eight palettes
+    pub fn pattern_tables(&self, source: PatternSource, palette: u8) -> Result<Vec<FrameData>, String> {
+        ppuview::pattern_tables(&self.bus, source, palette)
+    }
+
+    // All four nametables as one image, plus the scroll position of every line of the last frame
+    pub fn nametables(&self) -> NametableView {
+        ppuview::nametables(&self.bus)
+    }
+
+    // OAM decoded into sprites, with the lines of the last frame that had more than 8
+    pub fn sprites(&self) -> SpriteView {
+        ppuview::sprites(&self.bus)
+    }
+
+    // Hexdump with the labels that fall on each row
+    pub fn debug_memory_dump(&self, start_addr: u16, length: u16) {
+        let end = start_addr as u32 + length as u32;
+        for row in (start_addr as u32..end).step_by(16) {
+            let addresses = (row..end.min(row + 16)).map(|a| a as u16);
+            let bytes: Vec<String> = addresses.clone().map(|a| format!("{:02X}", self.bus.debug_read(a))).collect();
+            let names: Vec<String> = addresses
+                .filter_map(|a| self.symbol_at(a).filter(|s| !s.name.is_empty()).map(|s| format!("${:04X} {}", a, s.name)))
+                .collect();
+            if names.is_empty() {
+                println!("${:04X}: {}", row, bytes.join(" "));
+            } else {
+                println!("${:04X}: {:<47}  ; {}", row, bytes.join(" "), names.join(", "));
+            }
+        }
+    }
+
     pub fn debug_disassemble_range(&self, start_addr: u16, num_instructions: u16) {
-        let mut addr = start_addr;
-        for _ in 0..num_instructions {
-            let opcode = self.bus.read(addr);
-            println!("${:04X}: {:02X}", addr, opcode);
-            addr = addr.wrapping_add(1);
+        for instruction in self.disassemble(start_addr, num_instructions as usize) {
+            let bytes: Vec<String> = instruction.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            let label = instruction.label.as_deref().map_or(String::new(), |l| format!("{}:", l));
+            let mut notes: Vec<&str> = instruction.comment.iter().map(String::as_str).collect();
+            if instruction.unofficial {
+                notes.push("unofficial");
+            }
+            let notes = if notes.is_empty() { String::new() } else { format!(" ; {}", notes.join(", ")) };
+            println!("${:04X}: {:<9} {:<12} {}{}", instruction.address, bytes.join(" "), label, instruction.text(), notes);
         }
     }
 
     pub fn reset(&mut self) {
         self.bus.reset();
-        println!("CPU reset. PC starting at: {:#04X}", self.bus.get_cpu_state().registers.program_counter);
+        log::debug!("CPU reset. PC starting at: {:#04X}", self.bus.get_cpu_state().registers.program_counter);
     }
 
     pub fn toggle_test_mode(&mut self) -> Result<(), String> {
-        println!("Toggle test mode command received (Emulator wrapper)");
+        log::debug!("Toggle test mode command received (Emulator wrapper)");
         self.bus.toggle_test_mode();
         let is_test_mode = self.bus.test_mode;
-        println!("Test mode toggled via Emulator wrapper. New state: {}", if is_test_mode { "Enabled" } else { "Disabled" });
+        log::debug!("Test mode toggled via Emulator wrapper. New state: {}", if is_test_mode { "Enabled" } else { "Disabled" });
         Ok(())
     }
 
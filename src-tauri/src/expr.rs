@@ -0,0 +1,502 @@
+// Debugger condition expressions, e.g. `A == $20 && [$0300] > 5 && scanline < 240`.
+//
+// Numbers: 32, $20, 0x20, %00100000
+// Registers: a x y sp pc p, flags: c z i d b v n (0 or 1)
+// PPU / timing: scanline, dot (alias cycle), frame
+// Watchpoint access: address, value
+// Memory: [addr] reads a byte, {addr} a little-endian word (no read side effects)
+// Mapper: prgbank(addr) is the 8KB PRG ROM bank mapped at addr (-1 if none)
+// Operators (C precedence): ! ~ - (unary), * / %, + -, << >>, < <= > >=, == !=, &, ^, |, &&, ||
+use crate::cpu::Registers;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+// What expressions can look at besides the CPU registers
+pub trait Machine {
+    fn peek(&self, addr: u16) -> u8;
+    fn ppu_position(&self) -> (isize, usize);
+    fn frame(&self) -> u64;
+    fn prg_bank(&self, addr: u16) -> Option<usize>;
+}
+
+pub struct Env<'a> {
+    pub registers: &'a Registers,
+    pub machine: &'a dyn Machine,
+    pub access: Option<(u16, u8)>, // Address and value of the access that triggered a watchpoint
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Var {
+    A,
+    X,
+    Y,
+    Sp,
+    Pc,
+    P,
+    Flag(u8),
+    Scanline,
+    Dot,
+    Frame,
+    Address,
+    Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum UnaryOp {
+    Not,
+    BitNot,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(i64),
+    Var(Var),
+    Read8(Box<Node>),
+    Read16(Box<Node>),
+    PrgBank(Box<Node>),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+}
+
+// A parsed expression; keeps its source text for display
+#[derive(Clone)]
+pub struct Expr {
+    source: String,
+    root: Node,
+}
+
+impl fmt::Debug for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Expr({})", self.source)
+    }
+}
+
+impl Serialize for Expr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let root = parser.binary(0)?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(format!("Unexpected '{}' in expression", token));
+        }
+        Ok(Self { source: source.trim().to_string(), root })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn evaluate(&self, env: &Env) -> i64 {
+        eval(&self.root, env)
+    }
+
+    // Non-zero means true
+    pub fn is_true(&self, env: &Env) -> bool {
+        self.evaluate(env) != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Op(op) => write!(f, "{}", op),
+        }
+    }
+}
+
+// Longest first so "<=" wins over "<"
+const OPERATORS: [&str; 26] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "+", "-", "*", "/", "%", "&", "|", "^", "!", "~", "(",
+    ")", "[", "]", "{", "}",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    loop {
+        rest = rest.trim_start();
+        let Some(c) = rest.chars().next() else { break };
+
+        let radix = match c {
+            '$' => Some((16, 1)),
+            // `%` is modulo after an operand and a binary prefix elsewhere
+            '%' if rest[1..].starts_with(['0', '1']) && !follows_operand(&tokens) => Some((2, 1)),
+            '0' if rest[1..].starts_with(['x', 'X']) => Some((16, 2)),
+            '0'..='9' => Some((10, 0)),
+            _ => None,
+        };
+        if let Some((radix, prefix)) = radix {
+            let digits: String = rest[prefix..].chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '_').collect();
+            let value = i64::from_str_radix(&digits.replace('_', ""), radix)
+                .map_err(|_| format!("Invalid number '{}'", &rest[..prefix + digits.len()]))?;
+            tokens.push(Token::Number(value));
+            rest = &rest[prefix + digits.len()..];
+            continue;
+        }
+
+        if c.is_ascii_alphabetic() || c == '_' {
+            let ident: String = rest.chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '_').collect();
+            rest = &rest[ident.len()..];
+            tokens.push(Token::Ident(ident.to_ascii_lowercase()));
+            continue;
+        }
+
+        let op = OPERATORS
+            .iter()
+            .find(|op| rest.starts_with(**op))
+            .ok_or_else(|| format!("Unexpected character '{}' in expression", c))?;
+        tokens.push(Token::Op(op));
+        rest = &rest[op.len()..];
+    }
+    if tokens.is_empty() {
+        return Err("Empty expression".to_string());
+    }
+    Ok(tokens)
+}
+
+fn follows_operand(tokens: &[Token]) -> bool {
+    matches!(tokens.last(), Some(Token::Number(_) | Token::Ident(_) | Token::Op(")" | "]" | "}")))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+// Binary operators by precedence level, loosest first
+const PRECEDENCE: [&[(&str, BinaryOp)]; 10] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[("|", BinaryOp::BitOr)],
+    &[("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+    &[("<", BinaryOp::Lt), ("<=", BinaryOp::Le), (">", BinaryOp::Gt), (">=", BinaryOp::Ge)],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Rem)],
+];
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Op(found)) if found == op => Ok(()),
+            Some(token) => Err(format!("Expected '{}' but found '{}'", op, token)),
+            None => Err(format!("Expected '{}' at end of expression", op)),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Node, String> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = self.peek_op() {
+            let Some(&(_, binary_op)) = PRECEDENCE[level].iter().find(|(symbol, _)| *symbol == op) else { break };
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = Node::Binary(binary_op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        let op = match self.peek_op() {
+            Some("!") => UnaryOp::Not,
+            Some("~") => UnaryOp::BitNot,
+            Some("-") => UnaryOp::Neg,
+            _ => return self.primary(),
+        };
+        self.pos += 1;
+        Ok(Node::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Node::Number(value)),
+            Some(Token::Op("(")) => {
+                let node = self.binary(0)?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Some(Token::Op("[")) => {
+                let node = self.binary(0)?;
+                self.expect("]")?;
+                Ok(Node::Read8(Box::new(node)))
+            }
+            Some(Token::Op("{")) => {
+                let node = self.binary(0)?;
+                self.expect("}")?;
+                Ok(Node::Read16(Box::new(node)))
+            }
+            Some(Token::Ident(name)) if name == "prgbank" => {
+                self.expect("(")?;
+                let arg = Box::new(self.binary(0)?);
+                self.expect(")")?;
+                Ok(Node::PrgBank(arg))
+            }
+            Some(Token::Ident(name)) => variable(&name).map(Node::Var),
+            Some(token) => Err(format!("Unexpected '{}' in expression", token)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn variable(name: &str) -> Result<Var, String> {
+    Ok(match name {
+        "a" => Var::A,
+        "x" => Var::X,
+        "y" => Var::Y,
+        "sp" | "s" => Var::Sp,
+        "pc" => Var::Pc,
+        "p" => Var::P,
+        "c" => Var::Flag(0x01),
+        "z" => Var::Flag(0x02),
+        "i" => Var::Flag(0x04),
+        "d" => Var::Flag(0x08),
+        "b" => Var::Flag(0x10),
+        "v" => Var::Flag(0x40),
+        "n" => Var::Flag(0x80),
+        "scanline" => Var::Scanline,
+        "dot" | "cycle" => Var::Dot,
+        "frame" => Var::Frame,
+        "address" | "addr" => Var::Address,
+        "value" => Var::Value,
+        _ => return Err(format!("Unknown variable '{}'", name)),
+    })
+}
+
+fn eval(node: &Node, env: &Env) -> i64 {
+    let r = env.registers;
+    match node {
+        Node::Number(value) => *value,
+        Node::Var(var) => match var {
+            Var::A => r.accumulator as i64,
+            Var::X => r.x_register as i64,
+            Var::Y => r.y_register as i64,
+            Var::Sp => r.stack_pointer as i64,
+            Var::Pc => r.program_counter as i64,
+            Var::P => r.status as i64,
+            Var::Flag(mask) => (r.status & mask != 0) as i64,
+            Var::Scanline => env.machine.ppu_position().0 as i64,
+            Var::Dot => env.machine.ppu_position().1 as i64,
+            Var::Frame => env.machine.frame() as i64,
+            Var::Address => env.access.map_or(-1, |(addr, _)| addr as i64),
+            Var::Value => env.access.map_or(-1, |(_, value)| value as i64),
+        },
+        Node::Read8(addr) => env.machine.peek(eval(addr, env) as u16) as i64,
+        Node::Read16(addr) => {
+            let addr = eval(addr, env) as u16;
+            u16::from_le_bytes([env.machine.peek(addr), env.machine.peek(addr.wrapping_add(1))]) as i64
+        }
+        Node::PrgBank(addr) => env.machine.prg_bank(eval(addr, env) as u16).map_or(-1, |bank| bank as i64),
+        Node::Unary(op, operand) => {
+            let value = eval(operand, env);
+            match op {
+                UnaryOp::Not => (value == 0) as i64,
+                UnaryOp::BitNot => !value,
+                UnaryOp::Neg => value.wrapping_neg(),
+            }
+        }
+        Node::Binary(BinaryOp::And, left, right) => (eval(left, env) != 0 && eval(right, env) != 0) as i64,
+        Node::Binary(BinaryOp::Or, left, right) => (eval(left, env) != 0 || eval(right, env) != 0) as i64,
+        Node::Binary(op, left, right) => {
+            let (l, r) = (eval(left, env), eval(right, env));
+            match op {
+                BinaryOp::Mul => l.wrapping_mul(r),
+                BinaryOp::Div => l.checked_div(r).unwrap_or(0),
+                BinaryOp::Rem => l.checked_rem(r).unwrap_or(0),
+                BinaryOp::Add => l.wrapping_add(r),
+                BinaryOp::Sub => l.wrapping_sub(r),
+                BinaryOp::Shl => l.wrapping_shl(r as u32),
+                BinaryOp::Shr => l.wrapping_shr(r as u32),
+                BinaryOp::Lt => (l < r) as i64,
+                BinaryOp::Le => (l <= r) as i64,
+                BinaryOp::Gt => (l > r) as i64,
+                BinaryOp::Ge => (l >= r) as i64,
+                BinaryOp::Eq => (l == r) as i64,
+                BinaryOp::Ne => (l != r) as i64,
+                BinaryOp::BitAnd => l & r,
+                BinaryOp::BitXor => l ^ r,
+                BinaryOp::BitOr => l | r,
+                BinaryOp::And | BinaryOp::Or => unreachable!(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestMachine;
+
+    impl Machine for TestMachine {
+        // Each byte reads back as the low byte of its address
+        fn peek(&self, addr: u16) -> u8 {
+            addr as u8
+        }
+
+        fn ppu_position(&self) -> (isize, usize) {
+            (241, 5)
+        }
+
+        fn frame(&self) -> u64 {
+            60
+        }
+
+        fn prg_bank(&self, addr: u16) -> Option<usize> {
+            (addr >= 0x8000).then(|| (addr as usize - 0x8000) / 0x2000)
+        }
+    }
+
+    fn registers() -> Registers {
+        Registers {
+            accumulator: 0x20,
+            x_register: 3,
+            y_register: 0xFF,
+            stack_pointer: 0xFD,
+            program_counter: 0xC000,
+            status: 0x83, // N, Z, C
+        }
+    }
+
+    fn eval_with(source: &str, access: Option<(u16, u8)>) -> i64 {
+        let registers = registers();
+        let env = Env { registers: &registers, machine: &TestMachine, access };
+        Expr::parse(source).unwrap().evaluate(&env)
+    }
+
+    fn eval(source: &str) -> i64 {
+        eval_with(source, None)
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(eval("32"), 32);
+        assert_eq!(eval("$20"), 0x20);
+        assert_eq!(eval("0x2_0"), 0x20);
+        assert_eq!(eval("%00100000"), 0x20);
+        assert_eq!(eval("7 % 4"), 3);
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7);
+        assert_eq!(eval("(1 + 2) * 3"), 9);
+        assert_eq!(eval("1 << 2 + 1"), 8);
+        assert_eq!(eval("1 | 2 ^ 3 & 6"), 1); // 1 | (2 ^ (3 & 6))
+        assert_eq!(eval("1 + 1 == 2 && 3 > 2"), 1);
+        assert_eq!(eval("0 && 1 || 1"), 1);
+        assert_eq!(eval("10 - 4 - 3"), 3);
+        assert_eq!(eval("1 < 2 == 1"), 1);
+    }
+
+    #[test]
+    fn unary_operators() {
+        assert_eq!(eval("-5 + 2"), -3);
+        assert_eq!(eval("!0"), 1);
+        assert_eq!(eval("!!7"), 1);
+        assert_eq!(eval("~0"), -1);
+        assert_eq!(eval("-(2 * 3)"), -6);
+    }
+
+    #[test]
+    fn division_by_zero_is_zero() {
+        assert_eq!(eval("5 / 0"), 0);
+        assert_eq!(eval("5 % 0"), 0);
+    }
+
+    #[test]
+    fn registers_and_flags() {
+        assert_eq!(eval("A == $20 && x == 3 && Y == 255"), 1);
+        assert_eq!(eval("pc"), 0xC000);
+        assert_eq!(eval("sp"), 0xFD);
+        assert_eq!(eval("p"), 0x83);
+        assert_eq!(eval("n + z + c"), 3);
+        assert_eq!(eval("v + d + i"), 0);
+    }
+
+    #[test]
+    fn machine_state() {
+        assert_eq!(eval("scanline"), 241);
+        assert_eq!(eval("dot == cycle"), 1);
+        assert_eq!(eval("frame"), 60);
+        assert_eq!(eval("prgbank($A000)"), 1);
+        assert_eq!(eval("prgbank($6000)"), -1);
+    }
+
+    #[test]
+    fn memory_reads() {
+        assert_eq!(eval("[$0312]"), 0x12);
+        assert_eq!(eval("{$0312}"), 0x1312);
+        assert_eq!(eval("{$FFFF}"), 0x00FF); // High byte wraps to $0000
+        assert_eq!(eval("[$0300 + x]"), 3);
+    }
+
+    #[test]
+    fn watchpoint_access() {
+        assert_eq!(eval_with("address == $2007 && value == $3F", Some((0x2007, 0x3F))), 1);
+        assert_eq!(eval("address"), -1);
+        assert_eq!(eval("value"), -1);
+    }
+
+    #[test]
+    fn parse_errors() {
+        for source in ["", "1 +", "(1", "[$00", "foo", "1 2", "$", "a @ 1", "bank(0)", "prgbank $8000"] {
+            assert!(Expr::parse(source).is_err(), "{:?} should not parse", source);
+        }
+        assert_eq!(Expr::parse("  a == 1 ").unwrap().source(), "a == 1");
+    }
+}
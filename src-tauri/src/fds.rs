@@ -0,0 +1,1166 @@
+// Famicom Disk System support: RAM adapter, disk drive and expansion audio.
+// The BIOS (disksys.rom) is supplied by the user and mapped at $E000-$FFFF.
+use crate::cartridge::Mapper;
+use crate::patch;
+use crate::savestate::{StateReader, StateWriter};
+use crate::Mirroring;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const FDS_BIOS_SIZE: usize = 8 * 1024;
+const FDS_HEADER_SIZE: usize = 16;
+const FDS_SIDE_SIZE: usize = 65500;
+const PRG_RAM_SIZE: usize = 32 * 1024;
+const CHR_RAM_SIZE: usize = 8 * 1024;
+
+// Drive-level layout of a side: gaps and CRCs that the .fds format strips out
+const LEADING_GAP_BYTES: usize = 28300 / 8;
+const BLOCK_GAP_BYTES: usize = 976 / 8;
+const GAPPED_SIDE_SIZE: usize = 80 * 1024;
+
+// Timing in CPU cycles
+const BYTE_TRANSFER_CYCLES: u32 = 150; // ~96.4 kbit/s serial transfer
+const HEAD_RETURN_CYCLES: u32 = 50000; // Head moving back to the start of the disk
+const DISK_SWAP_DELAY_CYCLES: u32 = 1_789_773; // Keep the drive empty for ~1s so games notice a swap
+
+// Is this a disk image? (fwNES header or raw image starting with the disk info block)
+pub fn is_fds_image(path: &Path, data: &[u8]) -> bool {
+    let has_extension = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("fds"));
+    has_extension || data.starts_with(b"FDS\x1a") || data.get(1..15) == Some(b"*NINTENDO-HVC*")
+}
+
+// Disk writes are stored as an IPS diff next to the image, leaving the original untouched
+pub fn diff_path_for(image_path: &Path) -> PathBuf {
+    image_path.with_extension("fds.ips")
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FdsDiskInfo {
+    pub side_count: usize,
+    pub current_side: Option<usize>, // None while ejected
+    pub pending_side: Option<usize>, // Side waiting to be inserted after a swap
+}
+
+pub struct FdsDisk {
+    original_image: Vec<u8>, // Raw .fds contents (without fwNES header) as loaded
+    sides: Vec<Vec<u8>>,     // Drive-level sides with gaps and CRCs
+    diff_path: Option<PathBuf>,
+    dirty: bool,
+}
+
+impl FdsDisk {
+    // Parse an .fds image. If a diff file exists, previous disk writes are applied on top.
+    pub fn load(image: &[u8], diff_path: Option<PathBuf>) -> Result<Self, String> {
+        let raw = if image.starts_with(b"FDS\x1a") {
+            &image[FDS_HEADER_SIZE.min(image.len())..]
+        } else {
+            image
+        };
+        if raw.len() < FDS_SIDE_SIZE {
+            return Err(format!("FDS image is too small ({} bytes)", raw.len()));
+        }
+        let original_image = raw.to_vec();
+
+        let working_image = match &diff_path {
+            Some(p) if p.is_file() => {
+                log::info!("Applying FDS save diff: {}", p.display());
+                patch::apply_patch_file(&original_image, p)?
+            }
+            _ => original_image.clone(),
+        };
+
+        let mut sides = working_image
+            .chunks(FDS_SIDE_SIZE)
+            .filter(|side| side.len() == FDS_SIDE_SIZE)
+            .map(add_gaps)
+            .collect::<Vec<_>>();
+        // Give every side the same length so the head position stays valid across disk swaps
+        let side_size = sides.iter().map(Vec::len).max().unwrap_or(0);
+        for side in &mut sides {
+            side.resize(side_size, 0);
+        }
+
+        log::debug!("FDS image loaded: {} side(s)", sides.len());
+        Ok(Self { original_image, sides, diff_path, dirty: false })
+    }
+
+    pub fn side_count(&self) -> usize {
+        self.sides.len()
+    }
+
+    // Length of each drive-level side (all sides are padded to the same size)
+    fn side_size(&self) -> usize {
+        self.sides.first().map_or(0, Vec::len)
+    }
+
+    // Convert the drive-level sides back to .fds format and store the difference
+    pub fn flush(&mut self) -> Result<(), String> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.dirty = false;
+        let Some(diff_path) = &self.diff_path else {
+            return Ok(());
+        };
+
+        let mut image: Vec<u8> = self.sides.iter().flat_map(|side| remove_gaps(side)).collect();
+        image.resize(image.len().max(self.original_image.len()), 0);
+        let diff = patch::create_ips(&self.original_image, &image);
+        fs::write(diff_path, diff)
+            .map_err(|e| format!("Failed to write FDS diff {}: {}", diff_path.display(), e))?;
+        log::info!("FDS disk writes saved to {}", diff_path.display());
+        Ok(())
+    }
+}
+
+// Insert the leading gap, gap-end marks, CRC placeholders and inter-block gaps
+fn add_gaps(side: &[u8]) -> Vec<u8> {
+    let mut gapped = vec![0u8; LEADING_GAP_BYTES];
+    let mut pos = 0;
+
+    while pos < side.len() {
+        let block_length = match side[pos] {
+            1 => 56, // Disk info
+            2 => 2,  // File amount
+            3 => 16, // File header
+            4 if pos >= 3 => 1 + side[pos - 3] as usize + ((side[pos - 2] as usize) << 8), // File data
+            _ => break, // End of the used area
+        };
+        let end = (pos + block_length).min(side.len());
+        gapped.push(0x80); // Gap end mark
+        gapped.extend_from_slice(&side[pos..end]);
+        gapped.extend_from_slice(&[0x4D, 0x62]); // CRC (not verified by the emulated drive)
+        gapped.extend(std::iter::repeat_n(0, BLOCK_GAP_BYTES));
+        pos = end;
+    }
+
+    gapped.resize(gapped.len().max(GAPPED_SIDE_SIZE), 0);
+    gapped
+}
+
+// Inverse of add_gaps: walk the blocks and keep only their contents
+fn remove_gaps(gapped: &[u8]) -> Vec<u8> {
+    let mut side = Vec::with_capacity(FDS_SIDE_SIZE);
+    let mut pos = 0;
+    let mut last_file_size = 0usize;
+
+    loop {
+        // Skip the gap up to the gap end mark
+        while pos < gapped.len() && gapped[pos] == 0 {
+            pos += 1;
+        }
+        if pos >= gapped.len() || gapped[pos] != 0x80 {
+            break;
+        }
+        pos += 1;
+
+        let block_length = match gapped.get(pos) {
+            Some(1) => 56,
+            Some(2) => 2,
+            Some(3) => 16,
+            Some(4) => 1 + last_file_size,
+            _ => break,
+        };
+        if pos + block_length > gapped.len() {
+            break;
+        }
+        if gapped[pos] == 3 {
+            last_file_size = gapped[pos + 13] as usize | ((gapped[pos + 14] as usize) << 8);
+        }
+        side.extend_from_slice(&gapped[pos..pos + block_length]);
+        pos += block_length + 2; // Skip CRC
+    }
+
+    side.resize(FDS_SIDE_SIZE, 0);
+    side
+}
+
+pub struct FdsMapper {
+    bios: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr_ram: Vec<u8>,
+    disk: FdsDisk,
+    audio: FdsAudio,
+    mirroring: Mirroring,
+
+    // $4023
+    disk_registers_enabled: bool,
+    sound_registers_enabled: bool,
+
+    // Timer IRQ ($4020-$4022)
+    irq_reload: u16,
+    irq_counter: u16,
+    irq_repeat: bool,
+    irq_enabled: bool,
+    timer_irq: bool,
+
+    // Drive state ($4024/$4025)
+    current_side: Option<usize>,
+    pending_side: Option<usize>,
+    swap_delay: u32,
+    motor_on: bool,
+    reset_transfer: bool,
+    read_mode: bool,
+    crc_control: bool,
+    previous_crc_control: bool,
+    disk_ready: bool,
+    disk_irq_enabled: bool,
+    disk_irq: bool,
+    write_data: u8,
+    read_data: u8,
+    transfer_complete: bool,
+    end_of_head: bool,
+    scanning_disk: bool,
+    gap_ended: bool,
+    disk_position: usize,
+    delay: u32,
+    crc_accumulator: u16,
+    wrote_to_disk: bool,
+    external_output: u8,
+}
+
+impl FdsMapper {
+    pub fn new(bios: Vec<u8>, disk: FdsDisk) -> Result<Self, String> {
+        if bios.len() != FDS_BIOS_SIZE {
+            return Err(format!(
+                "FDS BIOS must be {} bytes, got {} bytes",
+                FDS_BIOS_SIZE,
+                bios.len()
+            ));
+        }
+        if disk.side_count() == 0 {
+            return Err("FDS image contains no disk sides".to_string());
+        }
+
+        Ok(Self {
+            bios,
+            prg_ram: vec![0; PRG_RAM_SIZE],
+            chr_ram: vec![0; CHR_RAM_SIZE],
+            disk,
+            audio: FdsAudio::new(),
+            mirroring: Mirroring::Vertical,
+            disk_registers_enabled: true,
+            sound_registers_enabled: true,
+            irq_reload: 0,
+            irq_counter: 0,
+            irq_repeat: false,
+            irq_enabled: false,
+            timer_irq: false,
+            current_side: Some(0),
+            pending_side: None,
+            swap_delay: 0,
+            motor_on: false,
+            reset_transfer: false,
+            read_mode: true,
+            crc_control: false,
+            previous_crc_control: false,
+            disk_ready: false,
+            disk_irq_enabled: false,
+            disk_irq: false,
+            write_data: 0,
+            read_data: 0,
+            transfer_complete: false,
+            end_of_head: true,
+            scanning_disk: false,
+            gap_ended: false,
+            disk_position: 0,
+            delay: 0,
+            crc_accumulator: 0,
+            wrote_to_disk: false,
+            external_output: 0,
+        })
+    }
+
+    pub fn disk_info(&self) -> FdsDiskInfo {
+        FdsDiskInfo {
+            side_count: self.disk.side_count(),
+            current_side: self.current_side,
+            pending_side: self.pending_side,
+        }
+    }
+
+    pub fn eject_disk(&mut self) -> Result<(), String> {
+        self.current_side = None;
+        self.pending_side = None;
+        self.swap_delay = 0;
+        self.disk.flush()
+    }
+
+    // Eject the current disk and insert `side` once the drive has been empty for a moment
+    pub fn insert_disk(&mut self, side: usize) -> Result<(), String> {
+        if side >= self.disk.side_count() {
+            return Err(format!(
+                "Disk side {} does not exist (image has {} sides)",
+                side,
+                self.disk.side_count()
+            ));
+        }
+        self.disk.flush()?;
+        if self.current_side.is_none() {
+            self.current_side = Some(side);
+            self.pending_side = None;
+        } else {
+            self.current_side = None;
+            self.pending_side = Some(side);
+            self.swap_delay = DISK_SWAP_DELAY_CYCLES;
+        }
+        Ok(())
+    }
+
+    // Flip the current disk over (side A <-> side B)
+    pub fn switch_side(&mut self) -> Result<(), String> {
+        let side = self
+            .current_side
+            .or(self.pending_side)
+            .ok_or("No disk is inserted")?;
+        let flipped = side ^ 1;
+        if flipped >= self.disk.side_count() {
+            return Err("This disk has no other side".to_string());
+        }
+        self.insert_disk(flipped)
+    }
+
+    pub fn flush_disk(&mut self) -> Result<(), String> {
+        self.disk.flush()
+    }
+
+    fn is_disk_inserted(&self) -> bool {
+        self.current_side.is_some()
+    }
+
+    fn peek_register(&self, addr: u16) -> u8 {
+        match addr {
+            0x4030 if self.disk_registers_enabled => {
+                let mut value = 0;
+                if self.timer_irq {
+                    value |= 0x01;
+                }
+                if self.transfer_complete {
+                    value |= 0x02;
+                }
+                // Bit 4 (CRC error) is never set: the emulated drive does not verify CRCs
+                if self.end_of_head {
+                    value |= 0x40;
+                }
+                value
+            }
+            0x4031 if self.disk_registers_enabled => self.read_data,
+            0x4032 if self.disk_registers_enabled => {
+                let mut value = 0x40; // Open bus
+                if !self.is_disk_inserted() {
+                    value |= 0x01 | 0x04; // Not inserted, not writable
+                }
+                if !self.is_disk_inserted() || !self.scanning_disk {
+                    value |= 0x02; // Not ready
+                }
+                value
+            }
+            0x4033 if self.disk_registers_enabled => 0x80, // Battery good
+            0x4040..=0x4097 if self.sound_registers_enabled => self.audio.read_register(addr),
+            _ => 0x40, // Open bus
+        }
+    }
+
+    // Reading the status/data registers acknowledges pending IRQs
+    fn acknowledge_read(&mut self, addr: u16) {
+        if !self.disk_registers_enabled {
+            return;
+        }
+        match addr {
+            0x4030 => {
+                self.transfer_complete = false;
+                self.timer_irq = false;
+                self.disk_irq = false;
+            }
+            0x4031 => {
+                self.transfer_complete = false;
+                self.disk_irq = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        if !self.disk_registers_enabled && (0x4024..=0x4026).contains(&addr) {
+            return;
+        }
+        match addr {
+            0x4020 => self.irq_reload = (self.irq_reload & 0xFF00) | data as u16,
+            0x4021 => self.irq_reload = (self.irq_reload & 0x00FF) | ((data as u16) << 8),
+            0x4022 => {
+                self.irq_repeat = data & 0x01 != 0;
+                self.irq_enabled = data & 0x02 != 0 && self.disk_registers_enabled;
+                if self.irq_enabled {
+                    self.irq_counter = self.irq_reload;
+                } else {
+                    self.timer_irq = false;
+                }
+            }
+            0x4023 => {
+                self.disk_registers_enabled = data & 0x01 != 0;
+                self.sound_registers_enabled = data & 0x02 != 0;
+                if !self.disk_registers_enabled {
+                    self.irq_enabled = false;
+                    self.timer_irq = false;
+                    self.disk_irq = false;
+                }
+            }
+            0x4024 => {
+                self.write_data = data;
+                self.transfer_complete = false;
+                self.disk_irq = false;
+            }
+            0x4025 => {
+                let was_writing = !self.read_mode;
+                self.motor_on = data & 0x01 != 0;
+                self.reset_transfer = data & 0x02 != 0;
+                self.read_mode = data & 0x04 != 0;
+                self.mirroring = if data & 0x08 != 0 { Mirroring::Horizontal } else { Mirroring::Vertical };
+                self.crc_control = data & 0x10 != 0;
+                self.disk_ready = data & 0x40 != 0;
+                self.disk_irq_enabled = data & 0x80 != 0;
+                self.disk_irq = false;
+
+                // Persist writes once the BIOS is done writing
+                if self.wrote_to_disk && (was_writing && self.read_mode || !self.motor_on) {
+                    self.wrote_to_disk = false;
+                    if let Err(e) = self.disk.flush() {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+            0x4026 => self.external_output = data,
+            0x4040..=0x4097 if self.sound_registers_enabled => self.audio.write_register(addr, data),
+            _ => {}
+        }
+    }
+
+    fn clock_timer_irq(&mut self) {
+        if !self.irq_enabled {
+            return;
+        }
+        if self.irq_counter == 0 {
+            self.timer_irq = true;
+            self.irq_counter = self.irq_reload;
+            if !self.irq_repeat {
+                self.irq_enabled = false;
+            }
+        } else {
+            self.irq_counter -= 1;
+        }
+    }
+
+    fn update_crc(&mut self, value: u8) {
+        let mut bit = 0x01u16;
+        while bit <= 0x80 {
+            let carry = self.crc_accumulator & 1 != 0;
+            self.crc_accumulator >>= 1;
+            if carry {
+                self.crc_accumulator ^= 0x8408;
+            }
+            if value as u16 & bit != 0 {
+                self.crc_accumulator ^= 0x8000;
+            }
+            bit <<= 1;
+        }
+    }
+
+    fn clock_drive(&mut self) {
+        if self.pending_side.is_some() {
+            if self.swap_delay > 0 {
+                self.swap_delay -= 1;
+            } else {
+                self.current_side = self.pending_side.take();
+            }
+        }
+
+        let Some(side) = self.current_side else {
+            self.end_of_head = true;
+            self.scanning_disk = false;
+            return;
+        };
+        if !self.motor_on {
+            self.end_of_head = true;
+            self.scanning_disk = false;
+            return;
+        }
+        if self.reset_transfer && !self.scanning_disk {
+            return;
+        }
+        if self.end_of_head {
+            // The head travels back to the start of the disk
+            self.delay = HEAD_RETURN_CYCLES;
+            self.end_of_head = false;
+            self.disk_position = 0;
+            self.gap_ended = false;
+            return;
+        }
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+
+        self.scanning_disk = true;
+        let mut need_irq = self.disk_irq_enabled;
+
+        if self.read_mode {
+            let data = self.disk.sides[side][self.disk_position];
+            if !self.previous_crc_control {
+                self.update_crc(data);
+            }
+            if !self.disk_ready {
+                self.gap_ended = false;
+                self.crc_accumulator = 0;
+            } else if data != 0 && !self.gap_ended {
+                // Gap end mark: the next byte is the first byte of the block
+                self.gap_ended = true;
+                need_irq = false;
+            }
+            if self.gap_ended {
+                self.transfer_complete = true;
+                self.read_data = data;
+                if need_irq {
+                    self.disk_irq = true;
+                }
+            }
+        } else {
+            let mut data = 0;
+            if !self.crc_control {
+                self.transfer_complete = true;
+                data = self.write_data;
+                if need_irq {
+                    self.disk_irq = true;
+                }
+            }
+            if !self.disk_ready {
+                data = 0;
+            }
+            if !self.crc_control {
+                self.update_crc(data);
+            } else {
+                if !self.previous_crc_control {
+                    self.update_crc(0);
+                    self.update_crc(0);
+                }
+                data = self.crc_accumulator as u8;
+                self.crc_accumulator >>= 8;
+            }
+            self.disk.sides[side][self.disk_position] = data;
+            self.disk.dirty = true;
+            self.wrote_to_disk = true;
+            self.gap_ended = false;
+        }
+
+        self.previous_crc_control = self.crc_control;
+        self.disk_position += 1;
+        if self.disk_position >= self.disk.sides[side].len() {
+            self.motor_on = false;
+            self.end_of_head = true;
+        } else {
+            self.delay = BYTE_TRANSFER_CYCLES;
+        }
+    }
+}
+
+impl Mapper for FdsMapper {
+    fn read_prg(&self, addr: u16) -> u8 {
+        match addr {
+            0x4020..=0x40FF => self.peek_register(addr),
+            0x6000..=0xDFFF => self.prg_ram[(addr - 0x6000) as usize],
+            0xE000..=0xFFFF => self.bios[(addr - 0xE000) as usize],
+            _ => 0,
+        }
+    }
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x4020..=0x40FF => {
+                let value = self.peek_register(addr);
+                self.acknowledge_read(addr);
+                value
+            }
+            _ => self.read_prg(addr),
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4020..=0x40FF => self.write_register(addr, data),
+            0x6000..=0xDFFF => self.prg_ram[(addr - 0x6000) as usize] = data,
+            _ => {} // BIOS is read-only
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr_ram[(addr & 0x1FFF) as usize]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        self.chr_ram[(addr & 0x1FFF) as usize] = data;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    // Only the BIOS is ROM; disk programs live in PRG RAM
+    fn prg_rom(&self) -> &[u8] {
+        &self.bios
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        (addr >= 0xE000).then(|| (addr - 0xE000) as usize)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.bios
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr_ram
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr_ram
+    }
+
+    // $6000-$DFFF
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn cpu_clock(&mut self) {
+        self.clock_timer_irq();
+        self.audio.clock();
+        self.clock_drive();
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.timer_irq || self.disk_irq
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn disk_system(&mut self) -> Option<&mut FdsMapper> {
+        Some(self)
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.prg_ram);
+        w.write_bytes(&self.chr_ram);
+        w.write_u8(self.mirroring.to_state_code());
+        w.write_bool(self.disk_registers_enabled);
+        w.write_bool(self.sound_registers_enabled);
+        w.write_u16(self.irq_reload);
+        w.write_u16(self.irq_counter);
+        w.write_bool(self.irq_repeat);
+        w.write_bool(self.irq_enabled);
+        w.write_bool(self.timer_irq);
+
+        // Disk contents are included so that writes made after the state was taken are undone
+        w.write_u32(self.disk.sides.len() as u32);
+        for side in &self.disk.sides {
+            w.write_bytes(side);
+        }
+        w.write_i32(self.current_side.map_or(-1, |s| s as i32));
+        w.write_i32(self.pending_side.map_or(-1, |s| s as i32));
+        w.write_u32(self.swap_delay);
+        w.write_bool(self.motor_on);
+        w.write_bool(self.reset_transfer);
+        w.write_bool(self.read_mode);
+        w.write_bool(self.crc_control);
+        w.write_bool(self.previous_crc_control);
+        w.write_bool(self.disk_ready);
+        w.write_bool(self.disk_irq_enabled);
+        w.write_bool(self.disk_irq);
+        w.write_u8(self.write_data);
+        w.write_u8(self.read_data);
+        w.write_bool(self.transfer_complete);
+        w.write_bool(self.end_of_head);
+        w.write_bool(self.scanning_disk);
+        w.write_bool(self.gap_ended);
+        w.write_u32(self.disk_position as u32);
+        w.write_u32(self.delay);
+        w.write_u16(self.crc_accumulator);
+        w.write_bool(self.wrote_to_disk);
+        w.write_u8(self.external_output);
+
+        self.audio.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_bytes_into(&mut self.prg_ram)?;
+        r.read_bytes_into(&mut self.chr_ram)?;
+        self.mirroring = Mirroring::from_state_code(r.read_u8()?)?;
+        self.disk_registers_enabled = r.read_bool()?;
+        self.sound_registers_enabled = r.read_bool()?;
+        self.irq_reload = r.read_u16()?;
+        self.irq_counter = r.read_u16()?;
+        self.irq_repeat = r.read_bool()?;
+        self.irq_enabled = r.read_bool()?;
+        self.timer_irq = r.read_bool()?;
+
+        let side_count = r.read_u32()? as usize;
+        if side_count != self.disk.sides.len() {
+            return Err(format!(
+                "Save state has {} disk sides, but the inserted disk has {}",
+                side_count,
+                self.disk.sides.len()
+            ));
+        }
+        let side_size = self.disk.side_size();
+        let mut sides = Vec::with_capacity(side_count);
+        for _ in 0..side_count {
+            let side = r.read_vec()?;
+            if side.len() != side_size {
+                return Err(format!(
+                    "Save state has a {} byte disk side, but the inserted disk's sides are {} bytes",
+                    side.len(),
+                    side_size
+                ));
+            }
+            sides.push(side);
+        }
+        // Only rewrite the diff file when the state actually changes the disk
+        if sides != self.disk.sides {
+            self.disk.sides = sides;
+            self.disk.dirty = true;
+        }
+        let side_from_state = |value: i32| (value >= 0).then_some(value as usize);
+        self.current_side = side_from_state(r.read_i32()?);
+        self.pending_side = side_from_state(r.read_i32()?);
+        if self.pending_side.is_some_and(|side| side >= self.disk.sides.len()) {
+            return Err("Save state has an invalid pending disk side".to_string());
+        }
+        self.swap_delay = r.read_u32()?;
+        self.motor_on = r.read_bool()?;
+        self.reset_transfer = r.read_bool()?;
+        self.read_mode = r.read_bool()?;
+        self.crc_control = r.read_bool()?;
+        self.previous_crc_control = r.read_bool()?;
+        self.disk_ready = r.read_bool()?;
+        self.disk_irq_enabled = r.read_bool()?;
+        self.disk_irq = r.read_bool()?;
+        self.write_data = r.read_u8()?;
+        self.read_data = r.read_u8()?;
+        self.transfer_complete = r.read_bool()?;
+        self.end_of_head = r.read_bool()?;
+        self.scanning_disk = r.read_bool()?;
+        self.gap_ended = r.read_bool()?;
+        self.disk_position = r.read_u32()? as usize;
+        if self.current_side.is_some_and(|side| side >= self.disk.sides.len()) {
+            return Err("Save state has an invalid disk side".to_string());
+        }
+        if (self.current_side.is_some() || self.pending_side.is_some()) && self.disk_position >= side_size {
+            return Err("Save state has an invalid disk position".to_string());
+        }
+        self.delay = r.read_u32()?;
+        self.crc_accumulator = r.read_u16()?;
+        self.wrote_to_disk = r.read_bool()?;
+        self.external_output = r.read_u8()?;
+
+        self.audio.load_state(r)
+    }
+}
+
+// --- FDS expansion audio: one wavetable channel with a frequency modulator ---
+const MOD_TABLE_STEPS: [i32; 8] = [0, 1, 2, 4, 0, -4, -2, -1]; // Entry 4 resets the counter
+const MASTER_VOLUME_TABLE: [u32; 4] = [36, 24, 17, 14]; // 2/2, 2/3, 2/4, 2/5
+
+#[derive(Default)]
+struct FdsEnvelope {
+    speed: u8,
+    gain: u8,
+    increase: bool,
+    disabled: bool, // Direct gain mode
+    timer: u32,
+    frequency: u16,
+}
+
+impl FdsEnvelope {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.speed);
+        w.write_u8(self.gain);
+        w.write_bool(self.increase);
+        w.write_bool(self.disabled);
+        w.write_u32(self.timer);
+        w.write_u16(self.frequency);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.speed = r.read_u8()?;
+        self.gain = r.read_u8()?;
+        self.increase = r.read_bool()?;
+        self.disabled = r.read_bool()?;
+        self.timer = r.read_u32()?;
+        self.frequency = r.read_u16()?;
+        Ok(())
+    }
+
+    fn write_control(&mut self, data: u8, master_speed: u8) {
+        self.speed = data & 0x3F;
+        self.increase = data & 0x40 != 0;
+        self.disabled = data & 0x80 != 0;
+        self.reset_timer(master_speed);
+        if self.disabled {
+            self.gain = self.speed;
+        }
+    }
+
+    fn reset_timer(&mut self, master_speed: u8) {
+        self.timer = 8 * (self.speed as u32 + 1) * master_speed as u32;
+    }
+
+    // Returns true when the gain changed
+    fn tick(&mut self, master_speed: u8) -> bool {
+        if self.disabled || master_speed == 0 {
+            return false;
+        }
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.reset_timer(master_speed);
+            if self.increase && self.gain < 32 {
+                self.gain += 1;
+            } else if !self.increase && self.gain > 0 {
+                self.gain -= 1;
+            }
+            return true;
+        }
+        false
+    }
+}
+
+struct FdsAudio {
+    wave_table: [u8; 64],
+    wave_write_enabled: bool,
+    wave_halted: bool,
+    envelopes_halted: bool,
+    wave_position: usize,
+    wave_accumulator: u16,
+    master_volume: usize,
+    master_env_speed: u8,
+    volume: FdsEnvelope,
+
+    mod_env: FdsEnvelope,
+    mod_table: [u8; 64],
+    mod_position: usize,
+    mod_counter: i32, // 7-bit signed
+    mod_accumulator: u16,
+    mod_halted: bool,
+    mod_output: i32,
+
+    last_output: u8,
+}
+
+impl FdsAudio {
+    fn new() -> Self {
+        Self {
+            wave_table: [0; 64],
+            wave_write_enabled: false,
+            wave_halted: true,
+            envelopes_halted: false,
+            wave_position: 0,
+            wave_accumulator: 0,
+            master_volume: 0,
+            master_env_speed: 0xE8,
+            volume: FdsEnvelope::default(),
+            mod_env: FdsEnvelope::default(),
+            mod_table: [0; 64],
+            mod_position: 0,
+            mod_counter: 0,
+            mod_accumulator: 0,
+            mod_halted: true,
+            mod_output: 0,
+            last_output: 0,
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.wave_table);
+        w.write_bool(self.wave_write_enabled);
+        w.write_bool(self.wave_halted);
+        w.write_bool(self.envelopes_halted);
+        w.write_u8(self.wave_position as u8);
+        w.write_u16(self.wave_accumulator);
+        w.write_u8(self.master_volume as u8);
+        w.write_u8(self.master_env_speed);
+        self.volume.save_state(w);
+        self.mod_env.save_state(w);
+        w.write_bytes(&self.mod_table);
+        w.write_u8(self.mod_position as u8);
+        w.write_i32(self.mod_counter);
+        w.write_u16(self.mod_accumulator);
+        w.write_bool(self.mod_halted);
+        w.write_i32(self.mod_output);
+        w.write_u8(self.last_output);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_bytes_into(&mut self.wave_table)?;
+        self.wave_write_enabled = r.read_bool()?;
+        self.wave_halted = r.read_bool()?;
+        self.envelopes_halted = r.read_bool()?;
+        self.wave_position = (r.read_u8()? & 0x3F) as usize;
+        self.wave_accumulator = r.read_u16()?;
+        self.master_volume = (r.read_u8()? & 0x03) as usize;
+        self.master_env_speed = r.read_u8()?;
+        self.volume.load_state(r)?;
+        self.mod_env.load_state(r)?;
+        r.read_bytes_into(&mut self.mod_table)?;
+        self.mod_table.iter_mut().for_each(|entry| *entry &= 0x07);
+        self.mod_position = (r.read_u8()? & 0x3F) as usize;
+        self.mod_counter = r.read_i32()?;
+        self.mod_accumulator = r.read_u16()?;
+        self.mod_halted = r.read_bool()?;
+        self.mod_output = r.read_i32()?;
+        self.last_output = r.read_u8()?;
+        Ok(())
+    }
+
+    fn read_register(&self, addr: u16) -> u8 {
+        match addr {
+            0x4040..=0x407F => self.wave_table[(addr - 0x4040) as usize] | 0x40,
+            0x4090 => self.volume.gain | 0x40,
+            0x4092 => self.mod_env.gain | 0x40,
+            _ => 0x40,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4040..=0x407F if self.wave_write_enabled => self.wave_table[(addr - 0x4040) as usize] = data & 0x3F,
+            0x4080 => self.volume.write_control(data, self.master_env_speed),
+            0x4082 => self.volume.frequency = (self.volume.frequency & 0x0F00) | data as u16,
+            0x4083 => {
+                self.volume.frequency = (self.volume.frequency & 0x00FF) | (((data & 0x0F) as u16) << 8);
+                self.envelopes_halted = data & 0x40 != 0;
+                self.wave_halted = data & 0x80 != 0;
+                if self.wave_halted {
+                    self.wave_position = 0;
+                    self.wave_accumulator = 0;
+                }
+                if self.envelopes_halted {
+                    self.volume.reset_timer(self.master_env_speed);
+                    self.mod_env.reset_timer(self.master_env_speed);
+                }
+            }
+            0x4084 => self.mod_env.write_control(data, self.master_env_speed),
+            0x4085 => self.set_mod_counter((data & 0x7F) as i32),
+            0x4086 => self.mod_env.frequency = (self.mod_env.frequency & 0x0F00) | data as u16,
+            0x4087 => {
+                self.mod_env.frequency = (self.mod_env.frequency & 0x00FF) | (((data & 0x0F) as u16) << 8);
+                self.mod_halted = data & 0x80 != 0;
+                if self.mod_halted {
+                    self.mod_accumulator = 0;
+                }
+            }
+            // The mod table can only be written while the modulator is halted
+            0x4088 if self.mod_halted => {
+                self.mod_table[self.mod_position] = data & 0x07;
+                self.mod_table[(self.mod_position + 1) & 0x3F] = data & 0x07;
+                self.mod_position = (self.mod_position + 2) & 0x3F;
+            }
+            0x4089 => {
+                self.master_volume = (data & 0x03) as usize;
+                self.wave_write_enabled = data & 0x80 != 0;
+            }
+            0x408A => self.master_env_speed = data,
+            _ => {}
+        }
+    }
+
+    fn set_mod_counter(&mut self, value: i32) {
+        // Wrap into the 7-bit signed range
+        self.mod_counter = if value >= 64 {
+            value - 128
+        } else if value < -64 {
+            value + 128
+        } else {
+            value
+        };
+    }
+
+    // Pitch offset applied by the modulator (nesdev "FDS audio" algorithm)
+    fn update_mod_output(&mut self) {
+        let pitch = self.volume.frequency as i32;
+        let mut temp = self.mod_counter * self.mod_env.gain as i32;
+        let remainder = temp & 0x0F;
+        temp >>= 4;
+        if remainder > 0 && temp & 0x80 == 0 {
+            temp += if self.mod_counter < 0 { -1 } else { 2 };
+        }
+        if temp >= 192 {
+            temp -= 256;
+        } else if temp < -64 {
+            temp += 256;
+        }
+        temp *= pitch;
+        let remainder = temp & 0x3F;
+        temp >>= 6;
+        if remainder >= 32 {
+            temp += 1;
+        }
+        self.mod_output = temp;
+    }
+
+    fn clock(&mut self) {
+        if !self.wave_halted && !self.envelopes_halted {
+            self.volume.tick(self.master_env_speed);
+            if self.mod_env.tick(self.master_env_speed) {
+                self.update_mod_output();
+            }
+        }
+
+        if !self.mod_halted && self.mod_env.frequency > 0 {
+            let (sum, overflow) = self.mod_accumulator.overflowing_add(self.mod_env.frequency);
+            self.mod_accumulator = sum;
+            if overflow {
+                let entry = self.mod_table[self.mod_position] as usize;
+                if entry == 4 {
+                    self.set_mod_counter(0);
+                } else {
+                    self.set_mod_counter(self.mod_counter + MOD_TABLE_STEPS[entry]);
+                }
+                self.mod_position = (self.mod_position + 1) & 0x3F;
+                self.update_mod_output();
+            }
+        }
+
+        if self.wave_halted {
+            self.wave_position = 0;
+        } else {
+            let step = self.volume.frequency as i32 + self.mod_output;
+            if step > 0 && !self.wave_write_enabled {
+                let (sum, overflow) = self.wave_accumulator.overflowing_add(step as u16);
+                self.wave_accumulator = sum;
+                if overflow {
+                    self.wave_position = (self.wave_position + 1) & 0x3F;
+                }
+            }
+        }
+
+        // The output holds its last value while the wavetable is writable
+        if !self.wave_write_enabled {
+            let level = self.volume.gain.min(32) as u32 * MASTER_VOLUME_TABLE[self.master_volume];
+            self.last_output = ((self.wave_table[self.wave_position] as u32 * level) / 1152) as u8;
+        }
+    }
+
+    // Normalized output level (0.0 - 1.0)
+    fn output(&self) -> f32 {
+        self.last_output as f32 / 63.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One side with a disk info block, a file amount block, and one file (header + 3 data bytes)
+    fn side() -> Vec<u8> {
+        let mut side = vec![1];
+        side.extend_from_slice(b"*NINTENDO-HVC*");
+        side.resize(56, 0);
+        side.extend_from_slice(&[2, 1]);
+        let mut header = vec![3, 0, 0, b'F', b'I', b'L', b'E', b'0', b'0', b'0', 0x00, 0x60, 3, 0, 0];
+        header.resize(16, 0);
+        header[13] = 3; // File size (little endian)
+        header[14] = 0;
+        side.extend_from_slice(&header);
+        side.extend_from_slice(&[4, 0xAA, 0xBB, 0xCC]);
+        side.resize(FDS_SIDE_SIZE, 0);
+        side
+    }
+
+    #[test]
+    fn gaps_round_trip() {
+        let side = side();
+        let gapped = add_gaps(&side);
+        assert_eq!(gapped.len(), GAPPED_SIDE_SIZE);
+        assert!(gapped[..LEADING_GAP_BYTES].iter().all(|&b| b == 0));
+        assert_eq!(gapped[LEADING_GAP_BYTES], 0x80);
+        assert_eq!(gapped[LEADING_GAP_BYTES + 1], 1);
+        assert_eq!(remove_gaps(&gapped), side);
+    }
+
+    #[test]
+    fn disk_writes_round_trip_through_the_ips_diff() {
+        let dir = std::env::temp_dir().join(format!("fds-diff-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let diff_path = dir.join("disk.fds.ips");
+        let _ = fs::remove_file(&diff_path);
+
+        let mut image = side();
+        image.extend(side());
+        let mut disk = FdsDisk::load(&image, Some(diff_path.clone())).unwrap();
+        assert_eq!(disk.side_count(), 2);
+
+        // Overwrite the second side's file data as the drive would
+        // Each block is a gap end mark, the block, a CRC and a gap
+        let file_data = LEADING_GAP_BYTES + [56, 2, 16].iter().map(|len| 1 + len + 2 + BLOCK_GAP_BYTES).sum::<usize>() + 1;
+        assert_eq!(disk.sides[1][file_data], 4);
+        disk.sides[1][file_data + 1] = 0x12;
+        disk.dirty = true;
+        disk.flush().unwrap();
+        assert!(!disk.dirty);
+
+        let reloaded = FdsDisk::load(&image, Some(diff_path.clone())).unwrap();
+        assert_eq!(reloaded.sides, disk.sides);
+        assert_eq!(reloaded.original_image, image);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn mapper() -> FdsMapper {
+        let mut image = side();
+        image.extend(side());
+        FdsMapper::new(vec![0; FDS_BIOS_SIZE], FdsDisk::load(&image, None).unwrap()).unwrap()
+    }
+
+    fn state_of(mapper: &FdsMapper) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        mapper.save_state(&mut w);
+        w.into_inner()
+    }
+
+    #[test]
+    fn state_round_trip_leaves_the_disk_clean() {
+        let mut mapper = mapper();
+        let state = state_of(&mapper);
+        let mut r = StateReader::new(&state);
+        mapper.load_state(&mut r).unwrap();
+        assert!(r.is_at_end());
+        assert!(!mapper.disk.dirty);
+        assert_eq!(state_of(&mapper), state);
+    }
+
+    #[test]
+    fn truncated_or_resized_states_are_rejected() {
+        let mut mapper = mapper();
+        let state = state_of(&mapper);
+        assert!(mapper.load_state(&mut StateReader::new(&state[..state.len() - 1])).is_err());
+
+        // prg/chr RAM blocks, mirroring, two enables, IRQ reload/counter, three IRQ flags, side count
+        let side_offset = 4 + PRG_RAM_SIZE + 4 + CHR_RAM_SIZE + 1 + 2 + 2 * 2 + 3 + 4;
+        let side_size = mapper.disk.side_size();
+        assert_eq!(state[side_offset..side_offset + 4], (side_size as u32).to_le_bytes());
+        for new_size in [side_size - 1, side_size + 1] {
+            let mut resized = state[..side_offset].to_vec();
+            resized.extend_from_slice(&(new_size as u32).to_le_bytes());
+            resized.resize(resized.len() + new_size, 0);
+            resized.extend_from_slice(&state[side_offset + 4 + side_size..]);
+            let err = mapper.load_state(&mut StateReader::new(&resized)).unwrap_err();
+            assert!(err.contains("disk side"), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,514 @@
+// GDB remote serial protocol server, so external debuggers and IDE front-ends can attach over TCP.
+// It drives the same debugger core (breakpoints, watchpoints, stepping) as the UI.
+//
+// Registers (g/G/p/P, numbered in this order): a, x, y, p, sp (8 bits each) and pc (16 bits, little endian).
+// The layout is also served as target.xml through qXfer:features:read.
+use crate::bus::BusAccess;
+use crate::debug_server::DebugServer;
+use crate::debugger::{AccessKind, PauseReason, WatchSpace, WatchpointSpec};
+use crate::emulator::Emulator;
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 6502;
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+const INTERRUPT: u8 = 0x03; // Ctrl-C from the client
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.tauri-nes.6502">
+    <reg name="a" bitsize="8" regnum="0"/>
+    <reg name="x" bitsize="8"/>
+    <reg name="y" bitsize="8"/>
+    <reg name="p" bitsize="8"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+// Distinct type so the app can manage it separately from the DAP server
+pub struct GdbServer(DebugServer);
+
+impl GdbServer {
+    // Listen on localhost only; one client at a time
+    pub fn start(emulator: Arc<Mutex<Emulator>>, port: u16) -> Result<Self, String> {
+        DebugServer::start("GDB", emulator, port, |stream, emulator, running| {
+            let mut session = Session::new(stream, emulator, running);
+            if let Err(e) = session.run() {
+                eprintln!("GDB session ended: {}", e);
+            }
+            session.cleanup();
+        })
+        .map(Self)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.0.port()
+    }
+}
+
+enum Incoming {
+    Packet(String),
+    Interrupt,
+}
+
+struct Session {
+    stream: TcpStream,
+    emulator: Arc<Mutex<Emulator>>,
+    running: Arc<AtomicBool>,
+    no_ack: bool,
+    resume_on_detach: bool, // The game was running when the client attached
+    buffer: Vec<u8>,
+    breakpoints: Vec<u16>,
+    watchpoints: HashMap<(u8, u16, u16), u32>, // (Z type, address, length) -> watchpoint id
+}
+
+impl Session {
+    fn new(stream: TcpStream, emulator: Arc<Mutex<Emulator>>, running: Arc<AtomicBool>) -> Self {
+        Self {
+            stream,
+            emulator,
+            running,
+            no_ack: false,
+            resume_on_detach: false,
+            buffer: Vec::new(),
+            breakpoints: Vec::new(),
+            watchpoints: HashMap::new(),
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Emulator>, String> {
+        self.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        self.stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+        self.stream.set_read_timeout(Some(POLL_INTERVAL * 10)).map_err(|e| e.to_string())?;
+        // The target is stopped while a debugger is attached
+        self.resume_on_detach = {
+            let mut emulator = self.lock()?;
+            let was_running = !emulator.paused;
+            emulator.halt();
+            was_running
+        };
+
+        while self.running.load(Ordering::SeqCst) {
+            let Some(incoming) = self.receive()? else { continue };
+            let packet = match incoming {
+                Incoming::Packet(packet) => packet,
+                Incoming::Interrupt => {
+                    self.lock()?.halt();
+                    continue;
+                }
+            };
+            match packet.as_bytes().first() {
+                Some(b'k') => return Ok(()),
+                Some(b'D') => {
+                    self.send("OK")?;
+                    return Ok(());
+                }
+                Some(b'c') => {
+                    let reply = self.continue_execution()?;
+                    self.send(&reply)?;
+                }
+                _ => {
+                    let reply = self.handle(&packet)?;
+                    self.send(&reply)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Remove what this client left behind and let the game run again if it was running before
+    fn cleanup(&mut self) {
+        let Ok(mut emulator) = self.emulator.lock() else { return };
+        {
+            let mut debugger = emulator.bus.debugger.borrow_mut();
+            for addr in self.breakpoints.drain(..) {
+                let _ = debugger.remove_breakpoint(addr);
+            }
+            for (_, id) in self.watchpoints.drain() {
+                let _ = debugger.remove_watchpoint(id);
+            }
+        }
+        if self.resume_on_detach {
+            emulator.set_paused(false);
+        }
+    }
+
+    fn handle(&mut self, packet: &str) -> Result<String, String> {
+        // An empty packet or one starting with a multi-byte character gets the "unsupported" reply
+        let Some(command) = packet.get(..1) else { return Ok(String::new()) };
+        let args = &packet[1..];
+        let reply = match command {
+            "?" => {
+                let emulator = self.lock()?;
+                emulator.pause_info().map_or(format!("S{:02x}", SIGINT), |info| stop_reply(&info.reason))
+            }
+            "g" => {
+                let emulator = self.lock()?;
+                let r = emulator.bus.cpu.borrow().registers.clone();
+                let pc = r.program_counter.to_le_bytes();
+                hex_encode(&[r.accumulator, r.x_register, r.y_register, r.status, r.stack_pointer, pc[0], pc[1]])
+            }
+            "G" => match hex_decode(args) {
+                Some(bytes) if bytes.len() == 7 => {
+                    let emulator = self.lock()?;
+                    let mut cpu = emulator.bus.cpu.borrow_mut();
+                    let r = &mut cpu.registers;
+                    r.accumulator = bytes[0];
+                    r.x_register = bytes[1];
+                    r.y_register = bytes[2];
+                    r.status = bytes[3];
+                    r.stack_pointer = bytes[4];
+                    r.program_counter = u16::from_le_bytes([bytes[5], bytes[6]]);
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(reg) if reg <= 5 => {
+                    let emulator = self.lock()?;
+                    let r = emulator.bus.cpu.borrow().registers.clone();
+                    match reg {
+                        0 => hex_encode(&[r.accumulator]),
+                        1 => hex_encode(&[r.x_register]),
+                        2 => hex_encode(&[r.y_register]),
+                        3 => hex_encode(&[r.status]),
+                        4 => hex_encode(&[r.stack_pointer]),
+                        _ => hex_encode(&r.program_counter.to_le_bytes()),
+                    }
+                }
+                _ => "E01".to_string(),
+            },
+            "P" => self.write_register(args).unwrap_or_else(|| "E01".to_string()),
+            "m" => match parse_addr_len(args) {
+                Some((addr, len)) => {
+                    let emulator = self.lock()?;
+                    let bytes: Vec<u8> = (0..len).map(|i| emulator.bus.peek(addr.wrapping_add(i))).collect();
+                    hex_encode(&bytes)
+                }
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let parsed = args.split_once(':').and_then(|(range, data)| Some((parse_addr_len(range)?, hex_decode(data)?)));
+                match parsed {
+                    // PPU/APU/controller register writes would scroll, start DMA, strobe the pads and so on
+                    Some(((addr, len), _)) if touches_io_registers(addr, len) => "E01".to_string(),
+                    Some(((addr, len), data)) if data.len() == len as usize => {
+                        let mut emulator = self.lock()?;
+                        for (i, byte) in data.into_iter().enumerate() {
+                            emulator.bus.debug_write(addr.wrapping_add(i as u16), byte);
+                        }
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            "Z" | "z" => self.set_breakpoint(command == "Z", args)?,
+            "s" => {
+                let mut emulator = self.lock()?;
+                match emulator.step_into() {
+                    Ok(info) => stop_reply(&info.reason),
+                    Err(_) => "E01".to_string(),
+                }
+            }
+            "H" | "T" => "OK".to_string(), // Single thread
+            "q" => self.query(args),
+            "Q" if args == "StartNoAckMode" => {
+                self.send("OK")?;
+                self.no_ack = true;
+                return Ok(String::new());
+            }
+            _ => String::new(), // Unsupported
+        };
+        Ok(reply)
+    }
+
+    fn query(&self, args: &str) -> String {
+        if args.starts_with("Supported") {
+            return "PacketSize=1000;qXfer:features:read+;QStartNoAckMode+;swbreak+;hwbreak+".to_string();
+        }
+        if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+            let Some((offset, length)) = range.split_once(',') else { return "E01".to_string() };
+            let (Ok(offset), Ok(length)) = (usize::from_str_radix(offset, 16), usize::from_str_radix(length, 16)) else {
+                return "E01".to_string();
+            };
+            let start = offset.min(TARGET_XML.len());
+            let end = start.saturating_add(length).min(TARGET_XML.len());
+            let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+            return format!("{}{}", marker, &TARGET_XML[start..end]);
+        }
+        match args {
+            "Attached" => "1".to_string(),
+            "C" => "QC1".to_string(),
+            "fThreadInfo" => "m1".to_string(),
+            "sThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn write_register(&mut self, args: &str) -> Option<String> {
+        let (reg, value) = args.split_once('=')?;
+        let reg = usize::from_str_radix(reg, 16).ok()?;
+        let bytes = hex_decode(value)?;
+        let emulator = self.lock().ok()?;
+        let mut cpu = emulator.bus.cpu.borrow_mut();
+        let r = &mut cpu.registers;
+        match (reg, bytes.as_slice()) {
+            (0, [v]) => r.accumulator = *v,
+            (1, [v]) => r.x_register = *v,
+            (2, [v]) => r.y_register = *v,
+            (3, [v]) => r.status = *v,
+            (4, [v]) => r.stack_pointer = *v,
+            (5, [lo, hi]) => r.program_counter = u16::from_le_bytes([*lo, *hi]),
+            _ => return None,
+        }
+        Some("OK".to_string())
+    }
+
+    // Z/z type,addr,kind: 0/1 breakpoint, 2 write, 3 read, 4 access watchpoint
+    fn set_breakpoint(&mut self, insert: bool, args: &str) -> Result<String, String> {
+        let mut fields = args.split(',');
+        let (Some(kind), Some(addr), Some(len)) = (fields.next(), fields.next(), fields.next()) else {
+            return Ok("E01".to_string());
+        };
+        let (Ok(kind), Ok(addr), Ok(len)) =
+            (kind.parse::<u8>(), u16::from_str_radix(addr, 16), u16::from_str_radix(len, 16))
+        else {
+            return Ok("E01".to_string());
+        };
+
+        let shared = self.emulator.clone();
+        let emulator = shared.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+        let mut debugger = emulator.bus.debugger.borrow_mut();
+        match (kind, insert) {
+            (0 | 1, true) => {
+                debugger.add_breakpoint(addr, None, None)?;
+                self.breakpoints.push(addr);
+            }
+            (0 | 1, false) => {
+                let _ = debugger.remove_breakpoint(addr);
+                self.breakpoints.retain(|&a| a != addr);
+            }
+            (2..=4, true) => {
+                let spec = WatchpointSpec {
+                    space: WatchSpace::Cpu,
+                    start: addr,
+                    end: Some(addr.saturating_add(len.max(1) - 1)),
+                    read: kind != 2,
+                    write: kind != 3,
+                    execute: false,
+                    condition: None,
+                    hit_target: None,
+                };
+                let id = debugger.add_watchpoint(spec)?;
+                self.watchpoints.insert((kind, addr, len), id);
+            }
+            (2..=4, false) => {
+                if let Some(id) = self.watchpoints.remove(&(kind, addr, len)) {
+                    let _ = debugger.remove_watchpoint(id);
+                }
+            }
+            _ => return Ok(String::new()),
+        }
+        Ok("OK".to_string())
+    }
+
+    // Let the emulation thread run until the debugger stops it or the client interrupts
+    fn continue_execution(&mut self) -> Result<String, String> {
+        self.lock()?.set_paused(false);
+        loop {
+            {
+                let emulator = self.lock()?;
+                if let Some(info) = emulator.pause_info() {
+                    return Ok(stop_reply(&info.reason));
+                }
+                if emulator.paused {
+                    // Paused from the UI
+                    return Ok(format!("S{:02x}", SIGINT));
+                }
+            }
+            if !self.running.load(Ordering::SeqCst) {
+                return Err("GDB server stopped".to_string());
+            }
+            if let Some(Incoming::Interrupt) = self.receive()? {
+                self.lock()?.halt();
+            }
+        }
+    }
+
+    // Next packet or interrupt; None when nothing arrived within the read timeout
+    fn receive(&mut self) -> Result<Option<Incoming>, String> {
+        loop {
+            if let Some(incoming) = self.take_buffered()? {
+                return Ok(Some(incoming));
+            }
+            let mut chunk = [0u8; 1024];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err("connection closed".to_string()),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => return Ok(None),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+    }
+
+    fn take_buffered(&mut self) -> Result<Option<Incoming>, String> {
+        while let Some(&first) = self.buffer.first() {
+            match first {
+                INTERRUPT => {
+                    self.buffer.remove(0);
+                    return Ok(Some(Incoming::Interrupt));
+                }
+                b'$' => {
+                    let Some((length, body)) = split_packet(&self.buffer) else { return Ok(None) };
+                    self.buffer.drain(..length);
+                    if !self.no_ack {
+                        self.write_raw(if body.is_some() { b"+" } else { b"-" })?;
+                    }
+                    if let Some(body) = body {
+                        return Ok(Some(Incoming::Packet(String::from_utf8_lossy(&body).into_owned())));
+                    }
+                }
+                _ => {
+                    // Acks from the client and stray bytes
+                    self.buffer.remove(0);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn send(&mut self, data: &str) -> Result<(), String> {
+        let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+        self.write_raw(packet.as_bytes())
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.stream.write_all(bytes).map_err(|e| e.to_string())
+    }
+}
+
+fn stop_reply(reason: &PauseReason) -> String {
+    match reason {
+        PauseReason::Breakpoint { .. } => format!("T{:02x}swbreak:;", SIGTRAP),
+        PauseReason::Watchpoint { space: WatchSpace::Cpu, access, address, .. } => match access {
+            AccessKind::Read => format!("T{:02x}rwatch:{:x};", SIGTRAP, address),
+            AccessKind::Write => format!("T{:02x}watch:{:x};", SIGTRAP, address),
+            AccessKind::Execute => format!("T{:02x}hwbreak:;", SIGTRAP),
+        },
+        // PPU-side watchpoints have no GDB equivalent
+        PauseReason::Watchpoint { .. } | PauseReason::Step { .. } => format!("S{:02x}", SIGTRAP),
+        PauseReason::Halt => format!("S{:02x}", SIGINT),
+    }
+}
+
+// A "$body#cc" packet at the start of `buffer`: how many bytes it spans and its body if the checksum
+// matches. None while the packet hasn't fully arrived.
+fn split_packet(buffer: &[u8]) -> Option<(usize, Option<Vec<u8>>)> {
+    let hash = buffer.iter().position(|&b| b == b'#')?;
+    if buffer.len() < hash + 3 {
+        return None;
+    }
+    let body = &buffer[1..hash];
+    let sent = std::str::from_utf8(&buffer[hash + 1..hash + 3])
+        .ok()
+        .and_then(|s| u8::from_str_radix(s, 16).ok());
+    let valid = sent == Some(checksum(body));
+    Some((hash + 3, valid.then(|| body.to_vec())))
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+// Whether an M packet range (wrapping like the write itself) reaches $2000-$401F
+fn touches_io_registers(addr: u16, len: u16) -> bool {
+    (0..len).any(|i| (0x2000..=0x401F).contains(&addr.wrapping_add(i)))
+}
+
+fn parse_addr_len(args: &str) -> Option<(u16, u16)> {
+    let (addr, len) = args.split_once(',')?;
+    let addr = u32::from_str_radix(addr, 16).ok()?;
+    let len = u16::from_str_radix(len, 16).ok()?;
+    Some((addr as u16, len))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hex_decode(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(checksum(b""), 0);
+        assert_eq!(checksum(b"OK"), 0x9A);
+        // Wraps modulo 256
+        assert_eq!(checksum(&[0xFF, 0x02]), 0x01);
+    }
+
+    #[test]
+    fn packets() {
+        assert_eq!(split_packet(b"$OK#9a+"), Some((6, Some(b"OK".to_vec()))));
+        assert_eq!(split_packet(b"$OK#9A"), Some((6, Some(b"OK".to_vec()))));
+        assert_eq!(split_packet(b"$#00"), Some((4, Some(Vec::new()))));
+        // Wrong or unparsable checksums still consume the packet
+        assert_eq!(split_packet(b"$OK#00"), Some((6, None)));
+        assert_eq!(split_packet(b"$OK#zz$g#67"), Some((6, None)));
+        // Not fully received yet
+        assert_eq!(split_packet(b"$OK"), None);
+        assert_eq!(split_packet(b"$OK#9"), None);
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(hex_encode(&[0x00, 0xAB, 0x7F]), "00ab7f");
+        assert_eq!(hex_decode("00ab7F"), Some(vec![0x00, 0xAB, 0x7F]));
+        assert_eq!(hex_decode(""), Some(Vec::new()));
+        assert_eq!(hex_decode("abc"), None);
+        assert_eq!(hex_decode("zz"), None);
+        // Multi-byte characters must not panic on a char boundary
+        assert_eq!(hex_decode("é0"), None);
+    }
+
+    #[test]
+    fn address_and_length() {
+        assert_eq!(parse_addr_len("8000,10"), Some((0x8000, 0x10)));
+        assert_eq!(parse_addr_len("8000"), None);
+        assert_eq!(parse_addr_len("8000,10000"), None);
+        assert_eq!(parse_addr_len("x,1"), None);
+    }
+
+    #[test]
+    fn io_register_writes() {
+        assert!(!touches_io_registers(0x0000, 0x800));
+        assert!(!touches_io_registers(0x1FFF, 1));
+        assert!(touches_io_registers(0x1FFF, 2));
+        assert!(touches_io_registers(0x4016, 1));
+        assert!(!touches_io_registers(0x4020, 0x10));
+        assert!(!touches_io_registers(0xFFFF, 2));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::emulator::Emulator;
+use crate::region::Region;
 use std::sync::{Arc, Mutex};
 use std::io::{self, Read};
 use std::fs::File;
@@ -32,6 +33,28 @@ impl Mirroring {
             Mirroring::SingleScreenUpper => 0x01, // Treat as Vertical for flag purposes?
         }
     }
+
+    // Stable numbering used by save states
+    pub fn to_state_code(self) -> u8 {
+        match self {
+            Mirroring::Vertical => 0,
+            Mirroring::Horizontal => 1,
+            Mirroring::FourScreen => 2,
+            Mirroring::SingleScreenLower => 3,
+            Mirroring::SingleScreenUpper => 4,
+        }
+    }
+
+    pub fn from_state_code(code: u8) -> Result<Self, String> {
+        match code {
+            0 => Ok(Mirroring::Vertical),
+            1 => Ok(Mirroring::Horizontal),
+            2 => Ok(Mirroring::FourScreen),
+            3 => Ok(Mirroring::SingleScreenLower),
+            4 => Ok(Mirroring::SingleScreenUpper),
+            _ => Err(format!("Invalid mirroring code in save state: {}", code)),
+        }
+    }
 }
 
 // Represents the parsed content of a .nes file header and data
@@ -42,15 +65,49 @@ pub struct NesRom {
     pub mapper_id: u8,
     pub mirroring: Mirroring,
     pub has_battery_backed_ram: bool,
+    pub region: Option<Region>, // From the header; None when it doesn't say
     // pub prg_ram_size: usize, // Can be calculated or stored if needed
 }
 
 impl NesRom {
     pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let mut file = File::open(path.as_ref())?; // Use as_ref()
+        let buffer = Self::read_file(path.as_ref())?;
+        Self::from_bytes(&buffer)
+    }
+
+    // Load a ROM and soft-patch it in memory before parsing.
+    // If no patch is given explicitly, a .bps/.ups/.ips with the same stem next to the ROM is used.
+    pub fn from_file_with_patch<P: AsRef<Path>>(path: P, patch_path: Option<&Path>) -> io::Result<Self> {
+        let buffer = Self::read_patched_file(path.as_ref(), patch_path)?;
+        Self::from_bytes(&buffer)
+    }
+
+    // Read an image file, applying an explicit or sibling IPS/UPS/BPS patch if there is one
+    pub fn read_patched_file(path: &Path, patch_path: Option<&Path>) -> io::Result<Vec<u8>> {
+        let buffer = Self::read_file(path)?;
+        let patch_path = match patch_path {
+            Some(p) => Some(p.to_path_buf()),
+            None => patch::find_sibling_patch(path),
+        };
+
+        match patch_path {
+            Some(p) => {
+                log::info!("Applying patch: {}", p.display());
+                patch::apply_patch_file(&buffer, &p)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+            None => Ok(buffer),
+        }
+    }
+
+    fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+        let mut file = File::open(path)?;
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
 
+    pub fn from_bytes(buffer: &[u8]) -> io::Result<Self> {
         // TODO: Proper NES header validation and parsing
         if buffer.len() < NES_HEADER_SIZE || &buffer[0..4] != b"NES\x1a" {
              return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid NES ROM header"));
@@ -104,6 +161,7 @@ impl NesRom {
             mapper_id,
             mirroring,
             has_battery_backed_ram,
+            region: Region::from_header(&buffer[..NES_HEADER_SIZE]),
         })
     }
 }
@@ -115,15 +173,15 @@ pub struct NesEmu {
 
 impl NesEmu {
     pub fn new() -> Self {
-        println!("NESエミュレータを初期化中...");
+        log::debug!("NESエミュレータを初期化中...");
         let emulator = Arc::new(Mutex::new(Emulator::new()));
         
         // Emulatorを初期化しておく
         if let Ok(mut emu) = emulator.lock() {
-            println!("初期テストパターンを描画中...");
+            log::debug!("初期テストパターンを描画中...");
             let _ = emu.toggle_test_mode(); // テストモードを有効化してパターンを描画
         } else {
-            println!("エミュレータのロックに失敗しました");
+            log::warn!("エミュレータのロックに失敗しました");
         }
         
         NesEmu { emulator }
@@ -141,6 +199,24 @@ pub mod apu;
 pub mod controller;
 pub mod debugger;
 pub mod registers;
+pub mod patch;
+pub mod fds;
+pub mod savestate;
+pub mod rewind;
+pub mod region;
+pub mod trace;
+pub mod disasm;
+pub mod expr;
+pub mod emu_thread;
+pub mod debug_server;
+pub mod gdbstub;
+pub mod dbginfo;
+pub mod dap;
+pub mod symbols;
+pub mod cdl;
+pub mod ramsearch;
+pub mod cheats;
+pub mod ppuview;
 
 // Tauriコマンド実装はsrc-tauri/src/main.rsに移動
 // このファイルからは削除しました
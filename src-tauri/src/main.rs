@@ -11,10 +11,72 @@ use tauri_nes::cpu::InspectState;
 use tauri_nes::NesEmu;
 use serde::Serialize;
 use std::time::Instant;
-use tauri_nes::bus::Bus;
+use tauri_nes::bus::{Bus, MemorySpace};
 use tauri_nes::cpu::Cpu6502;
 use tauri_nes::cartridge::Cartridge;
-use tauri_nes::emulator::Emulator;
+use tauri_nes::emulator::{Emulator, RegionStatus, SaveSlotInfo, SpeedStatus};
+use tauri_nes::region::Region;
+use tauri_nes::disasm::Instruction;
+use tauri_nes::symbols::Symbol;
+use tauri_nes::cdl::CdlStats;
+use tauri_nes::cheats::Cheat;
+use tauri_nes::ppuview::{NametableView, PatternSource, SpriteView};
+use tauri_nes::ramsearch::{Comparison, Freeze, SearchResults, ValueSize};
+use tauri_nes::debugger::{Breakpoint, PauseInfo, Watchpoint, WatchpointSpec};
+use tauri_nes::fds::FdsDiskInfo;
+use tauri_nes::apu;
+use tauri_nes::gdbstub::{self, GdbServer};
+use tauri_nes::dap::{self, DapServer};
+use tauri_nes::emu_thread::{self, EmuInput, EmulationThread, FrameOutput};
+use std::collections::VecDeque;
+use tauri::http::{Request, Response, ResponseBuilder};
+
+// Audio older than this is dropped if the frontend falls behind (in samples)
+const MAX_QUEUED_AUDIO: usize = apu::SAMPLE_RATE as usize / 4;
+// Candidates returned per RAM search call (the total count is always included)
+const RAM_SEARCH_RESULT_LIMIT: usize = 1000;
+
+// Output of the emulation thread, served to the frontend through the nes:// protocol
+#[derive(Default)]
+struct FrameStore {
+    latest_frame: Mutex<Vec<u8>>, // emu_thread::FrameOutput::encode_frame format
+    audio: Mutex<VecDeque<f32>>,
+}
+
+impl FrameStore {
+    fn publish(&self, output: &FrameOutput) {
+        if let Ok(mut frame) = self.latest_frame.lock() {
+            *frame = output.encode_frame();
+        }
+        if let Ok(mut audio) = self.audio.lock() {
+            audio.extend(output.audio.iter().copied());
+            let excess = audio.len().saturating_sub(MAX_QUEUED_AUDIO);
+            audio.drain(..excess);
+        }
+    }
+}
+
+// nes://localhost/frame -> 最新フレーム, nes://localhost/audio -> 未送信の音声サンプル
+fn handle_nes_protocol(
+    app: &tauri::AppHandle,
+    request: &Request,
+) -> Result<Response, Box<dyn std::error::Error>> {
+    let store = app.state::<Arc<FrameStore>>();
+    let path = request.uri().split('?').next().unwrap_or("").trim_end_matches('/');
+    let body = if path.ends_with("/frame") {
+        store.latest_frame.lock().map(|f| f.clone()).unwrap_or_default()
+    } else if path.ends_with("/audio") {
+        let samples: Vec<f32> = store.audio.lock().map(|mut a| a.drain(..).collect()).unwrap_or_default();
+        emu_thread::encode_audio(&samples)
+    } else {
+        return ResponseBuilder::new().status(404).body(Vec::new());
+    };
+    ResponseBuilder::new()
+        .status(200)
+        .mimetype("application/octet-stream")
+        .header("Access-Control-Allow-Origin", "*")
+        .body(body)
+}
 
 // Define a struct to combine CPU state and PPU frame for frontend
 #[derive(Serialize, Clone)]
@@ -23,112 +85,26 @@ struct FullStateInfo {
     ppu_frame: FrameData,
 }
 
-// フレームを実行して取得するコマンド
+// 最新フレームの取得（エミュレーションは専用スレッドで実行され、"frame-ready" イベントで通知される）
 #[tauri::command]
 fn get_frame(state: tauri::State<'_, NesEmu>) -> Result<FrameData, String> {
-    // First, check if ROM is loaded before doing anything else
-    let rom_loaded = {
-        let emulator_lock_check = state.emulator.lock();
-        if let Ok(emu) = emulator_lock_check {
-            emu.is_rom_loaded()
-        } else {
-            // If locking fails, assume no ROM is loaded or there's an issue
-            // println!("Failed to lock emulator to check ROM status in get_frame command.");
-            false // Treat lock failure as ROM not loaded for safety
-        }
-    };
-
-    // If no ROM is loaded, return a default frame immediately without logging or processing
-    if !rom_loaded {
+    let emulator = state.emulator.lock().map_err(|e| format!("Cannot lock emulator: {}", e))?;
+    if !emulator.is_rom_loaded() {
         return Ok(FrameData::default());
     }
-
-    // --- Proceed only if ROM is loaded ---
-    static mut FRAME_COUNTER: u32 = 0;
-    let start_time = std::time::Instant::now();
-    
-    let frame_number = unsafe {
-        FRAME_COUNTER += 1;
-        FRAME_COUNTER
-    };
-    
-    // More detailed logging - Comment out
-    // println!("Frame execution request #{} - start", frame_number);
-    
-    // Safely access the emulator field
-    let mut emulator_lock = state.emulator.lock().map_err(|e| format!("Cannot lock emulator: {}", e))?;
-    
-    // Check test mode status via Bus
-    let is_test_mode = emulator_lock.bus.test_mode;
-    
-    let result = if is_test_mode {
-        // Comment out test mode log
-        // println!("Test mode active, getting test frame for frame #{}", frame_number);
-        emulator_lock.get_ppu_test_frame() // Call function to get test pattern
-    } else {
-        // Comment out normal mode log
-        // println!("Normal mode, running frame #{} execution", frame_number);
-        
-        // Check initial CPU state (Only for normal execution)
-        let bus = &emulator_lock.bus;
-        let cpu_state = bus.get_cpu_state();
-        // Comment out starting CPU state log
-        // println!("Frame#{} starting CPU: PC=${:04X}, A=${:02X}, X=${:02X}, Y=${:02X}, SP=${:02X}",
-        //          frame_number,
-        //          cpu_state.registers.program_counter,
-        //          cpu_state.registers.accumulator,
-        //          cpu_state.registers.x_register,
-        //          cpu_state.registers.y_register,
-        //          cpu_state.registers.stack_pointer);
-        
-        // Run the frame
-        let frame_result = emulator_lock.run_frame();
-        
-        if let Ok(frame) = &frame_result {
-            // Output debug info
-            let elapsed = start_time.elapsed();
-            let non_zero = frame.pixels.iter().filter(|&&x| x != 0).count();
-            // Comment out success log
-            // println!("Frame#{} success: {}x{}, non-zero pixels: {}/{}, processing time: {:?}", 
-            //          frame_number, frame.width, frame.height, non_zero, frame.pixels.len(), elapsed);
-            
-            // Report end CPU state
-            let bus = &emulator_lock.bus;
-            let cpu_state = bus.get_cpu_state();
-            // Comment out end CPU state log
-            // println!("Frame#{} end CPU: PC=${:04X}, A=${:02X}, X=${:02X}, Y=${:02X}, SP=${:02X}",
-            //          frame_number,
-            //          cpu_state.registers.program_counter,
-            //          cpu_state.registers.accumulator,
-            //          cpu_state.registers.x_register,
-            //          cpu_state.registers.y_register,
-            //          cpu_state.registers.stack_pointer);
-        } else if let Err(e) = &frame_result {
-            // Keep error log active
-            println!("Frame execution error: {}", e);
-        }
-        
-        frame_result
-    };
-    
-    result
+    Ok(emulator.bus.get_ppu_frame())
 }
 
-// キー入力イベントを処理するコマンド
+// キー入力イベントを処理するコマンド（エミュレーションスレッドへチャネルで送る）
 #[tauri::command]
-fn handle_key_event(state: tauri::State<'_, NesEmu>, key_code: String, pressed: bool) -> Result<(), String> {
-    // Lock the emulator state
-    let mut nes_emu = state.emulator.lock().map_err(|e| format!("Failed to lock emulator state: {}", e))?;
-    
-    // Call the consolidated key event handler in Emulator
-    nes_emu.handle_key_event(&key_code, pressed);
-    
-    Ok(())
+fn handle_key_event(thread: tauri::State<'_, Mutex<EmulationThread>>, key_code: String, pressed: bool) -> Result<(), String> {
+    let thread = thread.lock().map_err(|e| format!("Failed to lock emulation thread: {}", e))?;
+    thread.send_input(EmuInput::Key { code: key_code, pressed })
 }
 
 // ゲームROMをロードするコマンド
 #[tauri::command]
-fn load_rom(state: tauri::State<'_, NesEmu>, file_path: String) -> Result<bool, String> {
+fn load_rom(state: tauri::State<'_, NesEmu>, file_path: String, patch_path: Option<String>) -> Result<bool, String> {
     println!("ROM load request: {}", file_path);
     
     // Lock the emulator
@@ -140,8 +116,8 @@ fn load_rom(state: tauri::State<'_, NesEmu>, file_path: String) -> Result<bool,
         emulator.toggle_test_mode()?;
     }
     
-    // Attempt to load the ROM
-    match emulator.load_rom(&file_path) {
+    // Attempt to load the ROM (soft-patched if a patch is given or sits next to the ROM)
+    match emulator.load_rom_with_patch(&file_path, patch_path.as_deref()) {
         Ok(_) => {
             println!("ROM loaded successfully: {}", file_path);
             
@@ -168,6 +144,528 @@ fn load_rom(state: tauri::State<'_, NesEmu>, file_path: String) -> Result<bool,
     }
 }
 
+// FDS BIOS (disksys.rom) を設定するコマンド
+#[tauri::command]
+fn set_fds_bios(state: tauri::State<'_, NesEmu>, bios_path: String) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.set_fds_bios_path(&bios_path)
+}
+
+// ディスクシステムの状態（面数・挿入中の面）を取得するコマンド
+#[tauri::command]
+fn fds_get_disk_info(state: tauri::State<'_, NesEmu>) -> Result<FdsDiskInfo, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.fds_disk_info()
+}
+
+#[tauri::command]
+fn fds_eject_disk(state: tauri::State<'_, NesEmu>) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.fds_eject_disk()
+}
+
+// side: 0 = Disk 1 Side A, 1 = Disk 1 Side B, 2 = Disk 2 Side A, ...
+#[tauri::command]
+fn fds_insert_disk(state: tauri::State<'_, NesEmu>, side: usize) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.fds_insert_disk(side)
+}
+
+#[tauri::command]
+fn fds_switch_side(state: tauri::State<'_, NesEmu>) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.fds_switch_side()
+}
+
+// ステートセーブ（スロット番号 0-9）
+#[tauri::command]
+fn save_state_slot(state: tauri::State<'_, NesEmu>, slot: u8) -> Result<(), String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.save_state_to_slot(slot)
+}
+
+// ステートロード
+#[tauri::command]
+fn load_state_slot(state: tauri::State<'_, NesEmu>, slot: u8) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.load_state_from_slot(slot)
+}
+
+#[tauri::command]
+fn list_state_slots(state: tauri::State<'_, NesEmu>) -> Result<Vec<SaveSlotInfo>, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.list_state_slots()
+}
+
+// 巻き戻し: 指定フレーム数だけ戻してその時点の画面を返す
+#[tauri::command]
+fn rewind_step(state: tauri::State<'_, NesEmu>, frames: Option<u32>) -> Result<FrameData, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.rewind_step(frames.unwrap_or(1))
+}
+
+// 巻き戻しキーの押下/解放（押している間はエミュレーションスレッドが逆再生する）
+#[tauri::command]
+fn rewind_hold(state: tauri::State<'_, NesEmu>, active: bool) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.set_rewinding(active);
+    Ok(())
+}
+
+#[tauri::command]
+fn configure_rewind(
+    state: tauri::State<'_, NesEmu>,
+    enabled: bool,
+    interval_frames: u32,
+    memory_budget_mb: u32,
+) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.configure_rewind(enabled, interval_frames, memory_budget_mb as usize * 1024 * 1024);
+    Ok(())
+}
+
+// エミュレーション速度 (0.25 = スロー, 4.0 = 早送り)
+#[tauri::command]
+fn set_emulation_speed(state: tauri::State<'_, NesEmu>, multiplier: f64) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.set_speed(multiplier)
+}
+
+// ターボ（上限なしの早送り、途中フレームの描画は省略）
+#[tauri::command]
+fn set_turbo(state: tauri::State<'_, NesEmu>, enabled: bool) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.turbo = enabled;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_paused(state: tauri::State<'_, NesEmu>, paused: bool) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.set_paused(paused);
+    Ok(())
+}
+
+// 一時停止の切り替え（切り替え後の状態を返す）
+#[tauri::command]
+fn toggle_pause(state: tauri::State<'_, NesEmu>) -> Result<bool, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    let paused = !emulator.paused;
+    emulator.set_paused(paused);
+    Ok(emulator.paused)
+}
+
+// コマ送り: ちょうど1フレーム実行して一時停止
+#[tauri::command]
+fn frame_advance(state: tauri::State<'_, NesEmu>) -> Result<FrameData, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.frame_advance()
+}
+
+#[tauri::command]
+fn get_speed_status(state: tauri::State<'_, NesEmu>) -> Result<SpeedStatus, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.speed_status())
+}
+
+// 地域設定 ("ntsc" / "pal" / "dendy"、"auto" または未指定でヘッダ・ゲームデータベース・ファイル名から自動判定)
+#[tauri::command]
+fn set_region(state: tauri::State<'_, NesEmu>, region: Option<String>) -> Result<RegionStatus, String> {
+    let region = match region.as_deref() {
+        None | Some("auto") => None,
+        Some(name) => Some(Region::from_name(name)?),
+    };
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.set_region_override(region);
+    Ok(emulator.region_status())
+}
+
+#[tauri::command]
+fn get_region(state: tauri::State<'_, NesEmu>) -> Result<RegionStatus, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.region_status())
+}
+
+// リージョン判定用のゲームデータベース（nes20db.xml）を読み込むコマンド。登録件数を返す
+#[tauri::command]
+fn load_game_database(state: tauri::State<'_, NesEmu>, db_path: String) -> Result<usize, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.load_game_database(&db_path)
+}
+
+// CPUトレース開始 (path指定でファイルへ、省略時はメモリ上のリングバッファへ)
+#[tauri::command]
+fn start_cpu_trace(state: tauri::State<'_, NesEmu>, path: Option<String>, ring_size: Option<usize>) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    match path {
+        Some(path) => {
+            let file = std::fs::File::create(&path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+            emulator.set_trace_output(Some(Box::new(std::io::BufWriter::new(file))));
+        }
+        None => emulator.start_trace_ring(ring_size.unwrap_or(tauri_nes::trace::DEFAULT_RING_CAPACITY)),
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn stop_cpu_trace(state: tauri::State<'_, NesEmu>) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.set_trace_output(None);
+    Ok(())
+}
+
+// リングバッファの最新トレース行を取得
+#[tauri::command]
+fn get_cpu_trace(state: tauri::State<'_, NesEmu>, max_lines: Option<usize>) -> Result<Vec<String>, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.trace_lines(max_lines.unwrap_or(200)))
+}
+
+// 現在のメモリマップで逆アセンブル
+#[tauri::command]
+fn disassemble(state: tauri::State<'_, NesEmu>, start: u16, count: usize) -> Result<Vec<Instruction>, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.disassemble(start, count))
+}
+
+// PRG ROM の指定バンクを base に配置したものとして逆アセンブル
+#[tauri::command]
+fn disassemble_prg_bank(
+    state: tauri::State<'_, NesEmu>,
+    bank: usize,
+    bank_size: usize,
+    base: u16,
+    count: usize,
+) -> Result<Vec<Instruction>, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.disassemble_prg_bank(bank, bank_size, base, count)
+}
+
+// 逆アセンブル表示用のラベルを設定 (name が空なら削除)
+#[tauri::command]
+fn set_label(state: tauri::State<'_, NesEmu>, addr: u16, name: Option<String>) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.set_label(addr, name);
+    Ok(())
+}
+
+// シンボルファイル (ca65 .dbg / FCEUX .nl / Mesen .mlb) を読み込み、読み込んだ件数を返す
+#[tauri::command]
+fn import_symbols(state: tauri::State<'_, NesEmu>, path: String) -> Result<usize, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.import_symbols(&path)
+}
+
+#[tauri::command]
+fn list_symbols(state: tauri::State<'_, NesEmu>) -> Result<Vec<Symbol>, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.symbols.all())
+}
+
+#[tauri::command]
+fn clear_symbols(state: tauri::State<'_, NesEmu>) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.symbols.clear();
+    Ok(())
+}
+
+// コード/データログ (CDL) の記録を開始・停止する
+#[tauri::command]
+fn set_cdl_logging(state: tauri::State<'_, NesEmu>, logging: bool) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.set_cdl_logging(logging)
+}
+
+#[tauri::command]
+fn reset_cdl(state: tauri::State<'_, NesEmu>) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.reset_cdl();
+    Ok(())
+}
+
+// FCEUX 互換の .cdl ファイルとして保存
+#[tauri::command]
+fn save_cdl(state: tauri::State<'_, NesEmu>, path: String) -> Result<(), String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.save_cdl(&path)
+}
+
+// .cdl ファイルを読み込む（読み込み中の ROM とサイズが一致する必要あり）
+#[tauri::command]
+fn load_cdl(state: tauri::State<'_, NesEmu>, path: String) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.load_cdl(&path)
+}
+
+// コード/データ/未使用のバイト数
+#[tauri::command]
+fn get_cdl_stats(state: tauri::State<'_, NesEmu>) -> Result<CdlStats, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.cdl_stats())
+}
+
+// RAM サーチを開始（全アドレスを候補にして現在値をスナップショットとする）
+#[tauri::command]
+fn start_ram_search(state: tauri::State<'_, NesEmu>, size: ValueSize, signed: bool, limit: Option<usize>) -> Result<SearchResults, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.start_ram_search(size, signed);
+    Ok(emulator.ram_search_results(limit.unwrap_or(RAM_SEARCH_RESULT_LIMIT)))
+}
+
+// 候補を絞り込む。value を省略すると前回の値と比較する
+#[tauri::command]
+fn filter_ram_search(
+    state: tauri::State<'_, NesEmu>,
+    comparison: Comparison,
+    value: Option<i32>,
+    limit: Option<usize>,
+) -> Result<SearchResults, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.filter_ram_search(comparison, value);
+    Ok(emulator.ram_search_results(limit.unwrap_or(RAM_SEARCH_RESULT_LIMIT)))
+}
+
+// 候補を減らさずに現在値をスナップショットとして記録
+#[tauri::command]
+fn snapshot_ram_search(state: tauri::State<'_, NesEmu>, limit: Option<usize>) -> Result<SearchResults, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.snapshot_ram_search();
+    Ok(emulator.ram_search_results(limit.unwrap_or(RAM_SEARCH_RESULT_LIMIT)))
+}
+
+#[tauri::command]
+fn get_ram_search_results(state: tauri::State<'_, NesEmu>, limit: Option<usize>) -> Result<SearchResults, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.ram_search_results(limit.unwrap_or(RAM_SEARCH_RESULT_LIMIT)))
+}
+
+#[tauri::command]
+fn reset_ram_search(state: tauri::State<'_, NesEmu>) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.reset_ram_search();
+    Ok(())
+}
+
+// アドレスを毎フレーム固定値で上書きする。value 省略時は現在値、size 省略時は RAM サーチの幅
+#[tauri::command]
+fn freeze_address(state: tauri::State<'_, NesEmu>, address: u16, value: Option<i32>, size: Option<ValueSize>) -> Result<Freeze, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.freeze(address, value, size)
+}
+
+#[tauri::command]
+fn unfreeze_address(state: tauri::State<'_, NesEmu>, address: u16) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.unfreeze(address);
+    Ok(())
+}
+
+#[tauri::command]
+fn list_freezes(state: tauri::State<'_, NesEmu>) -> Result<Vec<Freeze>, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.freezes().to_vec())
+}
+
+// チートコード（ゲームジニー 6/8 文字、または RAM コード AAAA:VV）を追加して ROM ごとのファイルに保存
+#[tauri::command]
+fn add_cheat(state: tauri::State<'_, NesEmu>, code: String, description: Option<String>) -> Result<Cheat, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.add_cheat(&code, description.as_deref().unwrap_or(""))
+}
+
+#[tauri::command]
+fn remove_cheat(state: tauri::State<'_, NesEmu>, code: String) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.remove_cheat(&code)
+}
+
+#[tauri::command]
+fn set_cheat_enabled(state: tauri::State<'_, NesEmu>, code: String, enabled: bool) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.set_cheat_enabled(&code, enabled)
+}
+
+#[tauri::command]
+fn list_cheats(state: tauri::State<'_, NesEmu>) -> Result<Vec<Cheat>, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.cheats().to_vec())
+}
+
+// ブレークポイントを追加（既存なら有効化）。condition は "A == $20 && [$0300] > 5" のような式
+#[tauri::command]
+fn add_breakpoint(
+    state: tauri::State<'_, NesEmu>,
+    addr: u16,
+    condition: Option<String>,
+    hit_target: Option<u32>,
+) -> Result<(), String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.bus.debugger.borrow_mut().add_breakpoint(addr, condition.as_deref(), hit_target)?;
+    Ok(())
+}
+
+// ラベル名の位置にブレークポイントを追加し、そのアドレスを返す
+#[tauri::command]
+fn add_breakpoint_at_label(
+    state: tauri::State<'_, NesEmu>,
+    label: String,
+    condition: Option<String>,
+    hit_target: Option<u32>,
+) -> Result<u16, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    let addr = emulator.resolve_symbol(&label)?;
+    emulator.bus.debugger.borrow_mut().add_breakpoint(addr, condition.as_deref(), hit_target)?;
+    Ok(addr)
+}
+
+#[tauri::command]
+fn remove_breakpoint(state: tauri::State<'_, NesEmu>, addr: u16) -> Result<(), String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.bus.debugger.borrow_mut().remove_breakpoint(addr)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn list_breakpoints(state: tauri::State<'_, NesEmu>) -> Result<Vec<Breakpoint>, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    let breakpoints = emulator.bus.debugger.borrow().breakpoints();
+    Ok(breakpoints)
+}
+
+// ブレークポイントの有効/無効を切り替え
+#[tauri::command]
+fn set_breakpoint_enabled(state: tauri::State<'_, NesEmu>, addr: u16, enabled: bool) -> Result<(), String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.bus.debugger.borrow_mut().set_breakpoint_enabled(addr, enabled)?;
+    Ok(())
+}
+
+// ウォッチポイントを追加して ID を返す (space: cpu / vram / palette / oam, 条件式と回数は省略可)
+#[tauri::command]
+fn add_watchpoint(state: tauri::State<'_, NesEmu>, spec: WatchpointSpec) -> Result<u32, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    let id = emulator.bus.debugger.borrow_mut().add_watchpoint(spec)?;
+    Ok(id)
+}
+
+#[tauri::command]
+fn remove_watchpoint(state: tauri::State<'_, NesEmu>, id: u32) -> Result<(), String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.bus.debugger.borrow_mut().remove_watchpoint(id)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn list_watchpoints(state: tauri::State<'_, NesEmu>) -> Result<Vec<Watchpoint>, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    let watchpoints = emulator.bus.debugger.borrow().watchpoints();
+    Ok(watchpoints)
+}
+
+#[tauri::command]
+fn set_watchpoint_enabled(state: tauri::State<'_, NesEmu>, id: u32, enabled: bool) -> Result<(), String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.bus.debugger.borrow_mut().set_watchpoint_enabled(id, enabled)?;
+    Ok(())
+}
+
+// デバッガで停止中なら停止理由と CPU 状態を返す
+#[tauri::command]
+fn get_pause_info(state: tauri::State<'_, NesEmu>) -> Result<Option<PauseInfo>, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.pause_info())
+}
+
+// ステップ実行: 1命令だけ実行
+#[tauri::command]
+fn step_into(state: tauri::State<'_, NesEmu>) -> Result<PauseInfo, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.step_into()
+}
+
+// ステップオーバー: JSR はサブルーチンごと1ステップとして扱う
+#[tauri::command]
+fn step_over(state: tauri::State<'_, NesEmu>) -> Result<PauseInfo, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.step_over()
+}
+
+// ステップアウト: 現在のスタック深さの RTS/RTI まで実行
+#[tauri::command]
+fn step_out(state: tauri::State<'_, NesEmu>) -> Result<PauseInfo, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.step_out()
+}
+
+#[tauri::command]
+fn step_to_scanline(state: tauri::State<'_, NesEmu>, scanline: isize) -> Result<PauseInfo, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.step_to_scanline(scanline)
+}
+
+#[tauri::command]
+fn step_to_next_frame(state: tauri::State<'_, NesEmu>) -> Result<PauseInfo, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.step_to_next_frame()
+}
+
+// カーソル位置まで実行
+#[tauri::command]
+fn run_to_address(state: tauri::State<'_, NesEmu>, addr: u16) -> Result<PauseInfo, String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.run_to_address(addr)
+}
+
+// GDBリモートプロトコルのサーバーを起動して待ち受けポートを返す (localhostのみ)
+#[tauri::command]
+fn start_gdb_server(
+    state: tauri::State<'_, NesEmu>,
+    server: tauri::State<'_, Mutex<Option<GdbServer>>>,
+    port: Option<u16>,
+) -> Result<u16, String> {
+    let mut server = server.lock().map_err(|e| format!("Failed to lock GDB server: {}", e))?;
+    if let Some(running) = server.as_ref() {
+        return Ok(running.port());
+    }
+    let started = GdbServer::start(state.emulator.clone(), port.unwrap_or(gdbstub::DEFAULT_PORT))?;
+    let port = started.port();
+    *server = Some(started);
+    Ok(port)
+}
+
+// GDBサーバーを停止 (接続中のクライアントも切断)
+#[tauri::command]
+fn stop_gdb_server(server: tauri::State<'_, Mutex<Option<GdbServer>>>) -> Result<(), String> {
+    let mut server = server.lock().map_err(|e| format!("Failed to lock GDB server: {}", e))?;
+    server.take();
+    Ok(())
+}
+
+// エディタ向けのDebug Adapter Protocolサーバーを起動して待ち受けポートを返す (localhostのみ)
+#[tauri::command]
+fn start_dap_server(
+    state: tauri::State<'_, NesEmu>,
+    server: tauri::State<'_, Mutex<Option<DapServer>>>,
+    port: Option<u16>,
+) -> Result<u16, String> {
+    let mut server = server.lock().map_err(|e| format!("Failed to lock DAP server: {}", e))?;
+    if let Some(running) = server.as_ref() {
+        return Ok(running.port());
+    }
+    let started = DapServer::start(state.emulator.clone(), port.unwrap_or(dap::DEFAULT_PORT))?;
+    let port = started.port();
+    *server = Some(started);
+    Ok(port)
+}
+
+// DAPサーバーを停止
+#[tauri::command]
+fn stop_dap_server(server: tauri::State<'_, Mutex<Option<DapServer>>>) -> Result<(), String> {
+    let mut server = server.lock().map_err(|e| format!("Failed to lock DAP server: {}", e))?;
+    server.take();
+    Ok(())
+}
+
 // Command to handle controller input (Deprecated)
 #[tauri::command]
 fn handle_input(
@@ -217,24 +715,71 @@ fn get_cpu_state(state: tauri::State<Arc<Mutex<Emulator>>>) -> Result<InspectSta
     Ok(cpu_state)
 }
 
+// メモリエディタ: 指定したアドレス空間の範囲を副作用なしで読み出す
+#[tauri::command]
+fn read_memory(state: tauri::State<'_, NesEmu>, space: MemorySpace, start: usize, length: usize) -> Result<Vec<u8>, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.read_memory(space, start, length)
+}
+
+// メモリエディタ: ROM/RAM はそのまま書き換える（CPU 空間の I/O レジスタ $2000-$5FFF への書き込みはエラー）
+#[tauri::command]
+fn write_memory(state: tauri::State<'_, NesEmu>, space: MemorySpace, start: usize, data: Vec<u8>) -> Result<(), String> {
+    let mut emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.write_memory(space, start, &data)
+}
+
+// パターンテーブルを 128x128 の RGBA 画像で返す。palette は 0-7（4-7 はスプライト用）
+// source 省略時は現在のバンクの $0000/$1000、{"kind":"bank","index":n} や {"kind":"all"} で CHR の任意のバンク
+#[tauri::command]
+fn get_pattern_tables(state: tauri::State<'_, NesEmu>, palette: u8, source: Option<PatternSource>) -> Result<Vec<FrameData>, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    emulator.pattern_tables(source.unwrap_or_default(), palette)
+}
+
+// 4 枚のネームテーブルを 512x480 の画像で返す。scroll は次フレームの表示位置 (t)、
+// scanlines は直前のフレームの各ライン開始時のスクロール位置（画面分割の確認用）
+#[tauri::command]
+fn get_nametables(state: tauri::State<'_, NesEmu>) -> Result<NametableView, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.nametables())
+}
+
+// OAM の 64 スプライトを座標・タイル・パレット・反転などに分解し、それぞれ 8x8 / 8x16 の画像を付けて返す
+// overflow は直前のフレームで 1 ラインに 9 個以上並び、描画されなかったスプライトの一覧
+#[tauri::command]
+fn get_sprites(state: tauri::State<'_, NesEmu>) -> Result<SpriteView, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.sprites())
+}
+
+#[tauri::command]
+fn get_memory_size(state: tauri::State<'_, NesEmu>, space: MemorySpace) -> Result<usize, String> {
+    let emulator = state.emulator.lock().map_err(|e| format!("Failed to lock emulator: {}", e))?;
+    Ok(emulator.memory_size(space))
+}
+
 // Add memory debug commands
 #[tauri::command]
-fn debug_memory(state: tauri::State<'_, Arc<Mutex<Emulator>>>, start_addr: u16, length: u16) -> Vec<u8> {
+fn debug_memory(state: tauri::State<'_, NesEmu>, start_addr: u16, length: u16) -> Vec<u8> {
+    // $FFFF を越える範囲はそこで打ち切る
+    let end = (start_addr as u32 + length as u32).min(0x10000);
+    let length = (end - start_addr as u32) as u16;
     let mut result = Vec::with_capacity(length as usize);
-    if let Ok(emulator) = state.lock() {
+    if let Ok(emulator) = state.emulator.lock() {
         let bus = &emulator.bus;
-        for addr in start_addr..(start_addr + length) {
-            result.push(bus.debug_read(addr));
+        for addr in start_addr as u32..end {
+            result.push(bus.debug_read(addr as u16));
         }
         
-        bus.debug_memory_dump(start_addr, length);
+        emulator.debug_memory_dump(start_addr, length); // ラベル付きで出力
     }
     result
 }
 
 #[tauri::command]
-fn debug_disassemble(state: tauri::State<'_, Arc<Mutex<Emulator>>>, start_addr: u16, num_instructions: u16) {
-    if let Ok(emulator) = state.lock() {
+fn debug_disassemble(state: tauri::State<'_, NesEmu>, start_addr: u16, num_instructions: u16) {
+    if let Ok(emulator) = state.emulator.lock() {
         emulator.debug_disassemble_range(start_addr, num_instructions);
     }
 }
@@ -284,8 +829,8 @@ fn debug_current_code(state: tauri::State<Arc<Mutex<Emulator>>>, num_instruction
 }
 
 #[tauri::command]
-fn monitor_address(state: tauri::State<'_, Arc<Mutex<Emulator>>>, addr: u16) -> u8 {
-    if let Ok(emulator) = state.lock() {
+fn monitor_address(state: tauri::State<'_, NesEmu>, addr: u16) -> u8 {
+    if let Ok(emulator) = state.emulator.lock() {
         let bus = &emulator.bus;
         bus.debug_read(addr)
     } else {
@@ -326,16 +871,112 @@ fn main() {
         .manage(NesEmu {
             emulator: Arc::new(Mutex::new(Emulator::new())),
         })
+        .manage(Mutex::new(None::<GdbServer>))
+        .manage(Mutex::new(None::<DapServer>))
         .invoke_handler(tauri::generate_handler![
             get_frame,
             handle_key_event,
             load_rom,
+            set_fds_bios,
+            fds_get_disk_info,
+            fds_eject_disk,
+            fds_insert_disk,
+            fds_switch_side,
+            save_state_slot,
+            load_state_slot,
+            list_state_slots,
+            rewind_step,
+            rewind_hold,
+            configure_rewind,
+            set_emulation_speed,
+            set_turbo,
+            set_paused,
+            toggle_pause,
+            frame_advance,
+            get_speed_status,
+            set_region,
+            get_region,
+            load_game_database,
+            start_cpu_trace,
+            stop_cpu_trace,
+            get_cpu_trace,
+            disassemble,
+            disassemble_prg_bank,
+            set_label,
+            import_symbols,
+            list_symbols,
+            clear_symbols,
+            set_cdl_logging,
+            reset_cdl,
+            save_cdl,
+            load_cdl,
+            get_cdl_stats,
+            start_ram_search,
+            filter_ram_search,
+            snapshot_ram_search,
+            get_ram_search_results,
+            reset_ram_search,
+            freeze_address,
+            unfreeze_address,
+            list_freezes,
+            add_cheat,
+            remove_cheat,
+            set_cheat_enabled,
+            list_cheats,
+            add_breakpoint,
+            add_breakpoint_at_label,
+            remove_breakpoint,
+            list_breakpoints,
+            set_breakpoint_enabled,
+            get_pause_info,
+            add_watchpoint,
+            remove_watchpoint,
+            list_watchpoints,
+            set_watchpoint_enabled,
+            step_into,
+            step_over,
+            step_out,
+            step_to_scanline,
+            step_to_next_frame,
+            run_to_address,
+            start_gdb_server,
+            stop_gdb_server,
+            start_dap_server,
+            stop_dap_server,
+            debug_memory,
+            debug_disassemble,
+            monitor_address,
+            read_memory,
+            write_memory,
+            get_memory_size,
+            get_pattern_tables,
+            get_nametables,
+            get_sprites,
             // toggle_test_mode // Removed: This command is redundant, handled by handle_key_event
         ])
+        .register_uri_scheme_protocol("nes", handle_nes_protocol)
         .setup(|app| {
             let window = app.get_window("main").unwrap();
             window.set_title("Tauri NES Emulator").unwrap();
             window.show().unwrap();
+
+            // エミュレーション専用スレッドを起動し、フレーム毎にフロントエンドへ通知する
+            let store = Arc::new(FrameStore::default());
+            app.manage(store.clone());
+            let emulator = app.state::<NesEmu>().emulator.clone();
+            // チートリストはアプリのデータフォルダに ROM ごとに保存する
+            if let Some(dir) = app.path_resolver().app_data_dir() {
+                emulator.lock().unwrap().cheat_dir = Some(dir.join("cheats"));
+            }
+            let handle = app.handle();
+            let thread = EmulationThread::spawn(emulator, move |output| {
+                store.publish(&output);
+                let _ = handle.emit_all("frame-ready", output.frame_number);
+                if let Some(info) = &output.paused {
+                    let _ = handle.emit_all("debugger-paused", info);
+                }
+            });
+            app.manage(Mutex::new(thread));
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -0,0 +1,577 @@
+// Soft-patching support for IPS, UPS and BPS patch files.
+// Patches are applied in memory to the raw ROM file (header included) before NesRom parsing,
+// so the original ROM on disk is never modified.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Largest ROM a UPS/BPS patch may ask for; patches are untrusted, so the claimed size is checked before allocating
+pub const MAX_TARGET_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    Ips,
+    Ups,
+    Bps,
+}
+
+impl PatchFormat {
+    // Detect the patch format from the magic bytes at the start of the file
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"PATCH") {
+            Some(PatchFormat::Ips)
+        } else if data.starts_with(b"UPS1") {
+            Some(PatchFormat::Ups)
+        } else if data.starts_with(b"BPS1") {
+            Some(PatchFormat::Bps)
+        } else {
+            None
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            PatchFormat::Ips => "ips",
+            PatchFormat::Ups => "ups",
+            PatchFormat::Bps => "bps",
+        }
+    }
+}
+
+// Look for a patch with the same stem next to the ROM (e.g. game.nes -> game.bps).
+// Checksummed formats are preferred when more than one patch is present.
+pub fn find_sibling_patch(rom_path: &Path) -> Option<PathBuf> {
+    [PatchFormat::Bps, PatchFormat::Ups, PatchFormat::Ips]
+        .iter()
+        .map(|format| rom_path.with_extension(format.extension()))
+        .find(|candidate| candidate.is_file())
+}
+
+// Read a patch file from disk and apply it to the ROM data
+pub fn apply_patch_file(rom: &[u8], patch_path: &Path) -> Result<Vec<u8>, String> {
+    let patch = fs::read(patch_path)
+        .map_err(|e| format!("Failed to read patch {}: {}", patch_path.display(), e))?;
+    apply_patch(rom, &patch)
+}
+
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    match PatchFormat::detect(patch) {
+        Some(PatchFormat::Ips) => apply_ips(rom, patch),
+        Some(PatchFormat::Ups) => apply_ups(rom, patch),
+        Some(PatchFormat::Bps) => apply_bps(rom, patch),
+        None => Err("Unknown patch format (expected IPS, UPS or BPS header)".to_string()),
+    }
+}
+
+// --- IPS ---
+// "PATCH", then records of [offset:3][size:2][data] (size 0 = RLE [count:2][value:1]),
+// terminated by "EOF" and an optional 3-byte truncation length.
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if !patch.starts_with(b"PATCH") {
+        return Err("Not an IPS patch (missing PATCH header)".to_string());
+    }
+    let mut output = rom.to_vec();
+    let mut pos = 5;
+
+    loop {
+        if pos + 3 > patch.len() {
+            return Err("IPS patch is truncated (missing EOF marker)".to_string());
+        }
+        if &patch[pos..pos + 3] == b"EOF" {
+            pos += 3;
+            break;
+        }
+        if pos + 5 > patch.len() {
+            return Err("IPS patch is truncated (incomplete record header)".to_string());
+        }
+        let offset = read_be(&patch[pos..pos + 3]);
+        let size = read_be(&patch[pos + 3..pos + 5]);
+        pos += 5;
+
+        if size == 0 {
+            // RLE record
+            if pos + 3 > patch.len() {
+                return Err("IPS patch is truncated (incomplete RLE record)".to_string());
+            }
+            let count = read_be(&patch[pos..pos + 2]);
+            let value = patch[pos + 2];
+            pos += 3;
+            if output.len() < offset + count {
+                output.resize(offset + count, 0);
+            }
+            output[offset..offset + count].fill(value);
+        } else {
+            if pos + size > patch.len() {
+                return Err("IPS patch is truncated (incomplete record data)".to_string());
+            }
+            if output.len() < offset + size {
+                output.resize(offset + size, 0);
+            }
+            output[offset..offset + size].copy_from_slice(&patch[pos..pos + size]);
+            pos += size;
+        }
+    }
+
+    // Lunar IPS truncation extension
+    if pos + 3 <= patch.len() {
+        let truncate_to = read_be(&patch[pos..pos + 3]);
+        output.truncate(truncate_to);
+    }
+
+    Ok(output)
+}
+
+// Build an IPS patch that turns `original` into `modified`.
+// Used to store changes (e.g. FDS disk writes) without touching the original image.
+pub fn create_ips(original: &[u8], modified: &[u8]) -> Vec<u8> {
+    const MAX_RECORD: usize = 0xFFFF;
+    let mut patch = b"PATCH".to_vec();
+    let mut pos = 0;
+
+    while pos < modified.len() {
+        if pos < original.len() && original[pos] == modified[pos] {
+            pos += 1;
+            continue;
+        }
+        // Offset 0x454F46 would be read back as "EOF"; start the record one byte earlier
+        let start = if pos == 0x454F46 { pos - 1 } else { pos };
+        let mut end = pos;
+        while end < modified.len()
+            && end - start < MAX_RECORD
+            && (end >= original.len() || original[end] != modified[end])
+        {
+            end += 1;
+        }
+        patch.extend_from_slice(&[(start >> 16) as u8, (start >> 8) as u8, start as u8]);
+        let size = end - start;
+        patch.extend_from_slice(&[(size >> 8) as u8, size as u8]);
+        patch.extend_from_slice(&modified[start..end]);
+        pos = end;
+    }
+
+    patch.extend_from_slice(b"EOF");
+    if modified.len() < original.len() {
+        let len = modified.len();
+        patch.extend_from_slice(&[(len >> 16) as u8, (len >> 8) as u8, len as u8]);
+    }
+    patch
+}
+
+// --- UPS ---
+// "UPS1", source size, target size, then hunks of [relative offset][xor bytes .. 0],
+// followed by the source, target and patch CRC32s.
+pub fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.len() < 4 + 12 {
+        return Err("UPS patch is too small".to_string());
+    }
+    let footer = patch.len() - 12;
+    let (source_crc, target_crc) = verify_footer("UPS", rom, patch)?;
+
+    let mut pos = 4;
+    let source_size = read_vlq(patch, &mut pos)?;
+    let target_size = read_vlq(patch, &mut pos)?;
+    check_target_size("UPS", target_size)?;
+    if rom.len() != source_size {
+        return Err(format!(
+            "UPS patch expects a base ROM of {} bytes (CRC32 {:08X}), but the loaded ROM is {} bytes",
+            source_size, source_crc, rom.len()
+        ));
+    }
+
+    let mut output = rom.to_vec();
+    output.resize(target_size, 0);
+    let mut out_pos = 0usize;
+
+    while pos < footer {
+        out_pos = out_pos
+            .checked_add(read_vlq(patch, &mut pos)?)
+            .ok_or("UPS patch writes past the end of the target")?;
+        loop {
+            if pos >= footer {
+                return Err("UPS patch is truncated (unterminated hunk)".to_string());
+            }
+            let xor = patch[pos];
+            pos += 1;
+            // The terminating zero may sit one past the end; any real change there is an error
+            match output.get_mut(out_pos) {
+                Some(byte) => *byte ^= xor,
+                None if xor == 0 => {}
+                None => return Err("UPS patch writes past the end of the target".to_string()),
+            }
+            out_pos += 1;
+            if xor == 0 {
+                break;
+            }
+        }
+    }
+
+    check_target_crc("UPS", &output, target_crc)?;
+    Ok(output)
+}
+
+// --- BPS ---
+// "BPS1", source size, target size, metadata, then actions (SourceRead, TargetRead,
+// SourceCopy, TargetCopy), followed by the source, target and patch CRC32s.
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.len() < 4 + 12 {
+        return Err("BPS patch is too small".to_string());
+    }
+    let footer = patch.len() - 12;
+    let (_, target_crc) = verify_footer("BPS", rom, patch)?;
+
+    let mut pos = 4;
+    let source_size = read_vlq(patch, &mut pos)?;
+    let target_size = read_vlq(patch, &mut pos)?;
+    let metadata_size = read_vlq(patch, &mut pos)?;
+    pos = pos.checked_add(metadata_size).ok_or("BPS patch is truncated (metadata)")?;
+    check_target_size("BPS", target_size)?;
+    if rom.len() != source_size {
+        return Err(format!(
+            "BPS patch expects a base ROM of {} bytes, but the loaded ROM is {} bytes",
+            source_size, rom.len()
+        ));
+    }
+
+    let mut output = vec![0u8; target_size];
+    let mut out_pos = 0usize;
+    let mut source_rel = 0isize;
+    let mut target_rel = 0isize;
+
+    while pos < footer {
+        let data = read_vlq(patch, &mut pos)?;
+        let length = (data >> 2) + 1;
+        let out_end = match out_pos.checked_add(length) {
+            Some(end) if end <= target_size => end,
+            _ => return Err("BPS patch writes past the end of the target".to_string()),
+        };
+        match data & 3 {
+            0 => {
+                // SourceRead
+                if out_end > rom.len() {
+                    return Err("BPS SourceRead past the end of the source".to_string());
+                }
+                output[out_pos..out_end].copy_from_slice(&rom[out_pos..out_end]);
+                out_pos = out_end;
+            }
+            1 => {
+                // TargetRead
+                let data_end = pos.checked_add(length).filter(|&end| end <= footer);
+                let data_end = data_end.ok_or("BPS patch is truncated (TargetRead)")?;
+                output[out_pos..out_end].copy_from_slice(&patch[pos..data_end]);
+                pos = data_end;
+                out_pos = out_end;
+            }
+            2 => {
+                // SourceCopy
+                let start = source_rel
+                    .checked_add(read_signed_vlq(patch, &mut pos)?)
+                    .and_then(|rel| usize::try_from(rel).ok())
+                    .filter(|&start| start.checked_add(length).is_some_and(|end| end <= rom.len()))
+                    .ok_or("BPS SourceCopy outside of the source")?;
+                output[out_pos..out_end].copy_from_slice(&rom[start..start + length]);
+                // start + length <= rom.len(), so this fits in an isize
+                source_rel = (start + length) as isize;
+                out_pos = out_end;
+            }
+            _ => {
+                // TargetCopy (may overlap the bytes being written, so copy one at a time)
+                target_rel = target_rel
+                    .checked_add(read_signed_vlq(patch, &mut pos)?)
+                    .ok_or("BPS TargetCopy outside of the written target")?;
+                if target_rel < 0 || target_rel as usize >= out_pos {
+                    return Err("BPS TargetCopy outside of the written target".to_string());
+                }
+                for _ in 0..length {
+                    output[out_pos] = output[target_rel as usize];
+                    out_pos += 1;
+                    target_rel += 1;
+                }
+            }
+        }
+    }
+
+    check_target_crc("BPS", &output, target_crc)?;
+    Ok(output)
+}
+
+fn check_target_size(kind: &str, target_size: usize) -> Result<(), String> {
+    if target_size > MAX_TARGET_SIZE {
+        return Err(format!(
+            "{} patch asks for a {} byte ROM; the limit is {} bytes",
+            kind, target_size, MAX_TARGET_SIZE
+        ));
+    }
+    Ok(())
+}
+
+// Check the patch's own CRC and that the base ROM matches the source CRC.
+// Returns (source_crc, target_crc).
+fn verify_footer(kind: &str, rom: &[u8], patch: &[u8]) -> Result<(u32, u32), String> {
+    let footer = patch.len() - 12;
+    let source_crc = read_le32(&patch[footer..]);
+    let target_crc = read_le32(&patch[footer + 4..]);
+    let patch_crc = read_le32(&patch[footer + 8..]);
+
+    let actual_patch_crc = crc32(&patch[..footer + 8]);
+    if actual_patch_crc != patch_crc {
+        return Err(format!(
+            "{} patch is corrupt: checksum {:08X} does not match stored {:08X}",
+            kind, actual_patch_crc, patch_crc
+        ));
+    }
+
+    let rom_crc = crc32(rom);
+    if rom_crc != source_crc {
+        return Err(format!(
+            "{} patch was made for a different ROM: expected base CRC32 {:08X}, but the loaded ROM has CRC32 {:08X}",
+            kind, source_crc, rom_crc
+        ));
+    }
+
+    Ok((source_crc, target_crc))
+}
+
+fn check_target_crc(kind: &str, output: &[u8], expected: u32) -> Result<(), String> {
+    let actual = crc32(output);
+    if actual != expected {
+        return Err(format!(
+            "{} patch produced an unexpected result: CRC32 {:08X}, expected {:08X}",
+            kind, actual, expected
+        ));
+    }
+    Ok(())
+}
+
+// Variable-length integer used by UPS and BPS
+fn read_vlq(data: &[u8], pos: &mut usize) -> Result<usize, String> {
+    let mut value: usize = 0;
+    let mut shift: usize = 1;
+    loop {
+        let byte = *data.get(*pos).ok_or("Patch is truncated (variable-length integer)")?;
+        *pos += 1;
+        value = value
+            .checked_add((byte & 0x7F) as usize * shift)
+            .ok_or("Patch contains an out-of-range integer")?;
+        if byte & 0x80 != 0 {
+            return Ok(value);
+        }
+        shift = shift.checked_shl(7).ok_or("Patch contains an out-of-range integer")?;
+        value = value.checked_add(shift).ok_or("Patch contains an out-of-range integer")?;
+    }
+}
+
+fn read_signed_vlq(data: &[u8], pos: &mut usize) -> Result<isize, String> {
+    let raw = read_vlq(data, pos)?;
+    let magnitude = (raw >> 1) as isize;
+    Ok(if raw & 1 != 0 { -magnitude } else { magnitude })
+}
+
+fn read_be(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |acc, &b| (acc << 8) | b as usize)
+}
+
+fn read_le32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+// --- CRC32 (IEEE 802.3, as used by UPS/BPS and most ROM databases) ---
+const CRC32_TABLE: [u32; 256] = build_crc32_table();
+
+const fn build_crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc = CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_vlq(out: &mut Vec<u8>, mut value: usize) {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(0x80 | byte);
+                return;
+            }
+            out.push(byte);
+            value -= 1;
+        }
+    }
+
+    // Append the source, target and patch CRC32s shared by UPS and BPS
+    fn finish(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let patch_crc = crc32(&patch);
+        patch.extend_from_slice(&patch_crc.to_le_bytes());
+        patch
+    }
+
+    fn source() -> Vec<u8> {
+        (0..16).collect()
+    }
+
+    #[test]
+    fn ips_round_trip() {
+        let original = source();
+        let mut modified = original.clone();
+        modified[3] = 0xFF;
+        modified[4] = 0xEE;
+        modified.extend_from_slice(&[1, 2, 3]);
+        let patch = create_ips(&original, &modified);
+        assert_eq!(apply_patch(&original, &patch).unwrap(), modified);
+
+        // Shrinking uses the truncation extension
+        let shorter = original[..10].to_vec();
+        let patch = create_ips(&original, &shorter);
+        assert_eq!(apply_patch(&original, &patch).unwrap(), shorter);
+    }
+
+    #[test]
+    fn ips_rle_record() {
+        let patch = b"PATCH\x00\x00\x02\x00\x00\x00\x03\xAAEOF";
+        let output = apply_ips(&source(), patch).unwrap();
+        assert_eq!(&output[..6], &[0, 1, 0xAA, 0xAA, 0xAA, 5]);
+    }
+
+    #[test]
+    fn ups_round_trip() {
+        let rom = source();
+        let mut target = rom.clone();
+        target[2] ^= 0x55;
+        target[3] ^= 0xAA;
+        target.push(0x42);
+
+        let mut patch = b"UPS1".to_vec();
+        write_vlq(&mut patch, rom.len());
+        write_vlq(&mut patch, target.len());
+        write_vlq(&mut patch, 2);
+        patch.extend_from_slice(&[0x55, 0xAA, 0]);
+        // The last hunk's terminator lands one past the end of the target
+        write_vlq(&mut patch, 16 - 5);
+        patch.extend_from_slice(&[0x42, 0]);
+        let patch = finish(patch, &rom, &target);
+
+        assert_eq!(apply_patch(&rom, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn ups_rejects_writes_past_target() {
+        let rom = source();
+        let mut patch = b"UPS1".to_vec();
+        write_vlq(&mut patch, rom.len());
+        write_vlq(&mut patch, rom.len());
+        write_vlq(&mut patch, 20);
+        patch.extend_from_slice(&[0x01, 0]);
+        let patch = finish(patch, &rom, &rom);
+
+        let err = apply_ups(&rom, &patch).unwrap_err();
+        assert!(err.contains("past the end"), "{}", err);
+    }
+
+    #[test]
+    fn bps_round_trip() {
+        let rom = source();
+        let mut target = rom[..4].to_vec();
+        target.extend_from_slice(&[0xDE, 0xAD]);
+        target.extend_from_slice(&rom[8..12]);
+        target.extend_from_slice(&rom[..3]);
+
+        let action = |length: usize, kind: usize| ((length - 1) << 2) | kind;
+        let mut patch = b"BPS1".to_vec();
+        write_vlq(&mut patch, rom.len());
+        write_vlq(&mut patch, target.len());
+        write_vlq(&mut patch, 0);
+        write_vlq(&mut patch, action(4, 0)); // SourceRead
+        write_vlq(&mut patch, action(2, 1)); // TargetRead
+        patch.extend_from_slice(&[0xDE, 0xAD]);
+        write_vlq(&mut patch, action(4, 2)); // SourceCopy from +8
+        write_vlq(&mut patch, 8 << 1);
+        write_vlq(&mut patch, action(3, 3)); // TargetCopy from 0
+        write_vlq(&mut patch, 0);
+        let patch = finish(patch, &rom, &target);
+
+        assert_eq!(apply_patch(&rom, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn oversized_targets_are_rejected_before_allocating() {
+        let rom = source();
+        for magic in [b"UPS1", b"BPS1"] {
+            let mut patch = magic.to_vec();
+            write_vlq(&mut patch, rom.len());
+            write_vlq(&mut patch, usize::MAX / 2);
+            write_vlq(&mut patch, 0);
+            let patch = finish(patch, &rom, &rom);
+            let err = apply_patch(&rom, &patch).unwrap_err();
+            assert!(err.contains("limit"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn bps_rejects_overflowing_lengths_and_offsets() {
+        let rom = source();
+        let header = |metadata_size: usize| {
+            let mut patch = b"BPS1".to_vec();
+            write_vlq(&mut patch, rom.len());
+            write_vlq(&mut patch, rom.len());
+            write_vlq(&mut patch, metadata_size);
+            patch
+        };
+
+        // Metadata size that would wrap the read position
+        let patch = finish(header(usize::MAX - 2), &rom, &rom);
+        assert!(apply_bps(&rom, &patch).is_err());
+
+        // Action length that would wrap the output position
+        let mut patch = header(0);
+        write_vlq(&mut patch, usize::MAX - 3);
+        let patch = finish(patch, &rom, &rom);
+        assert!(apply_bps(&rom, &patch).is_err());
+
+        // SourceCopy with an offset far outside the source in either direction
+        for offset in [usize::MAX - 1, usize::MAX - 2] {
+            let mut patch = header(0);
+            write_vlq(&mut patch, 2);
+            write_vlq(&mut patch, offset);
+            let patch = finish(patch, &rom, &rom);
+            let err = apply_bps(&rom, &patch).unwrap_err();
+            assert!(err.contains("SourceCopy"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn checksummed_patches_reject_wrong_base_rom() {
+        let rom = source();
+        let mut patch = b"BPS1".to_vec();
+        write_vlq(&mut patch, rom.len());
+        write_vlq(&mut patch, rom.len());
+        write_vlq(&mut patch, 0);
+        write_vlq(&mut patch, (rom.len() - 1) << 2); // SourceRead of the whole ROM
+        let patch = finish(patch, &rom, &rom);
+
+        let mut other = rom.clone();
+        other[0] = 0xFF;
+        let err = apply_patch(&other, &patch).unwrap_err();
+        assert!(err.contains("different ROM"), "{}", err);
+    }
+}
@@ -2,6 +2,8 @@ use serde::Serialize; // Import Serialize
 use crate::Mirroring; // Ensure Mirroring is imported from crate root (main.rs)
 use crate::bus::BusAccess;             // Ensure Bus is imported
 use crate::registers::{AddrRegister, ControlRegister, MaskRegister, StatusRegister}; // Assuming registers module exists
+use crate::savestate::{SaveState, StateReader, StateWriter};
+use crate::region::Region;
 // use std::cell::RefCell; // Remove unused import
 // use std::rc::Rc; // Remove unused import
 // use std::cell::RefCell; // Remove unused import
@@ -48,7 +50,8 @@ pub struct Ppu {
     
     // PPUタイミング
     pub cycle: usize,                // 現在のピクセルサイクル (0-340)
-    pub scanline: isize,             // 現在のスキャンライン (-1 to 260)
+    pub scanline: isize,             // 現在のスキャンライン (-1 to 260, PAL/Dendy: -1 to 310)
+    pub region: Region,              // スキャンライン数とVBlank開始位置を決める
     pub frame_complete: bool,        // フレーム完了フラグ
     pub frame_counter: u64,          // フレームカウンタ
     
@@ -72,6 +75,32 @@ pub struct Ppu {
     
     // フレームデータ
     pub frame: FrameData,           // 現在のフレームデータ
+    pub skip_rendering: bool,       // 早送り時: ピクセル出力を省略（タイミングはそのまま）
+
+    // 各表示スキャンラインの開始スクロール位置（ネームテーブルビューア用、描画オフのラインは None）
+    scroll_log: [Option<ScrollPosition>; VISIBLE_SCANLINES],
+    pub last_frame_scroll: [Option<ScrollPosition>; VISIBLE_SCANLINES], // 直前のフレーム分
+    // 各スキャンラインに掛かっていたスプライト（ビット n = OAM の n 番）。9 個目以降は実機では描画されない
+    sprite_lines: [u64; VISIBLE_SCANLINES],
+    pub last_frame_sprite_lines: [u64; VISIBLE_SCANLINES], // 直前のフレーム分
+}
+
+const VISIBLE_SCANLINES: usize = 240;
+
+// 4画面分 (512x480) のネームテーブル空間上のスクロール位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ScrollPosition {
+    pub x: u16,
+    pub y: u16,
+}
+
+impl ScrollPosition {
+    // v/t レジスタ (yyy NN YYYYY XXXXX) と fine X から求める
+    pub fn from_address(addr: u16, fine_x: u8) -> Self {
+        let x = (((addr & 0x1F) << 3) | (fine_x as u16 & 0x07)) + ((addr >> 10) & 1) * 256;
+        let y = ((((addr >> 5) & 0x1F) << 3) | ((addr >> 12) & 0x07)) + ((addr >> 11) & 1) * 240;
+        Self { x, y }
+    }
 }
 
 impl Ppu {
@@ -90,6 +119,7 @@ impl Ppu {
             oam_data: [0; 256],
             cycle: 0,
             scanline: -1,
+            region: Region::Ntsc,
             frame_complete: false,
             frame_counter: 0,
             palette_ram: [0; 32],
@@ -105,6 +135,11 @@ impl Ppu {
             bg_shifter_attrib_lo: 0,
             bg_shifter_attrib_hi: 0,
             frame: FrameData::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+            skip_rendering: false,
+            scroll_log: [None; VISIBLE_SCANLINES],
+            last_frame_scroll: [None; VISIBLE_SCANLINES],
+            sprite_lines: [0; VISIBLE_SCANLINES],
+            last_frame_sprite_lines: [0; VISIBLE_SCANLINES],
         };
 
         ppu.reset();
@@ -112,13 +147,17 @@ impl Ppu {
     }
 
     pub fn reset(&mut self) {
-        println!("PPU Reset started...");
+        log::debug!("PPU Reset started...");
         
         // PPUの内部状態をリセット
         self.cycle = 0;
         self.scanline = -1; // Start at pre-render scanline
         self.frame_complete = false;
         self.frame_counter = 0;
+        self.scroll_log = [None; VISIBLE_SCANLINES];
+        self.last_frame_scroll = [None; VISIBLE_SCANLINES];
+        self.sprite_lines = [0; VISIBLE_SCANLINES];
+        self.last_frame_sprite_lines = [0; VISIBLE_SCANLINES];
         self.nmi_line_low = true;
         self.address_latch_low = true;
         self.fine_x_scroll = 0;
@@ -188,7 +227,7 @@ impl Ppu {
         // マスクレジスタを更新して背景とスプライトを表示
         self.mask.set_bits(0x1E);  // 背景とスプライトを有効化（0x1EはBGとスプライト両方有効）
         
-        println!("PPU Reset complete. PPUCTRL=${:02X}, PPUMASK=${:02X}", self.ctrl.bits(), self.mask.bits());
+        log::debug!("PPU Reset complete. PPUCTRL=${:02X}, PPUMASK=${:02X}", self.ctrl.bits(), self.mask.bits());
     }
 
     // テスト用のパターンを描画
@@ -212,7 +251,7 @@ impl Ppu {
         };
         
         if should_log {
-            println!("初期テストパターンを描画中...");
+            log::debug!("初期テストパターンを描画中...");
         }
         
         // テストモード用のグラデーションパターンを描画
@@ -245,44 +284,16 @@ impl Ppu {
         }
         
         if should_log {
-            println!("初期テストパターン描画完了！");
+            log::debug!("初期テストパターン描画完了！");
         }
     }
 
     // CHR-RAMからタイルを描画する補助メソッド
     fn draw_chr_tile(&mut self, x_pos: usize, y_pos: usize, tile_index: usize, palette: usize) {
         let base_addr = tile_index * 16; // 各タイルは16バイト
-        
-        for y in 0..8 {
-            let low_byte = self.chr_ram[base_addr + y];
-            let high_byte = self.chr_ram[base_addr + y + 8];
-            
-            for x in 0..8 {
-                let bit = 7 - x; // ビット位置は反転している
-                let low_bit = (low_byte >> bit) & 0x01;
-                let high_bit = (high_byte >> bit) & 0x01;
-                let pixel_value = (high_bit << 1) | low_bit;
-                
-                if pixel_value > 0 { // 0以外のピクセルのみ描画（0は透明）
-                    let color_addr = (palette * 4 + pixel_value as usize) % 32;
-                    let color_idx = self.palette_ram[color_addr] as usize;
-                    let (r, g, b) = NES_PALETTE[color_idx & 0x3F];
-                    
-                    let screen_x = x_pos + x;
-                    let screen_y = y_pos + y;
-                    
-                    if screen_x < SCREEN_WIDTH && screen_y < SCREEN_HEIGHT {
-                        let idx = (screen_y * SCREEN_WIDTH + screen_x) * 4;
-                        if idx + 3 < self.frame.pixels.len() {
-                            self.frame.pixels[idx] = r;
-                            self.frame.pixels[idx + 1] = g;
-                            self.frame.pixels[idx + 2] = b;
-                            self.frame.pixels[idx + 3] = 255;
-                        }
-                    }
-                }
-            }
-        }
+        let tile = &self.chr_ram[base_addr..base_addr + 16];
+        let colors: [u8; 4] = std::array::from_fn(|i| self.palette_ram[(palette * 4 + i) % 32]);
+        draw_tile(&mut self.frame, x_pos, y_pos, tile, &colors, true); // 0は透明
     }
 
     // テスト用のCHR-RAMデータを初期化
@@ -392,14 +403,14 @@ impl Ppu {
     pub fn step_cycle(&mut self, bus: &impl BusAccess) -> bool {
         // Log state at the beginning of the cycle (less frequently)
         if self.cycle == 0 && self.scanline % 16 == 0 { // Log every 16 scanlines at cycle 0
-             println!("[Cycle Start] Scanline: {}, Cycle: {}, v: {:04X}, t: {:04X}",
+             log::trace!("[Cycle Start] Scanline: {}, Cycle: {}, v: {:04X}, t: {:04X}",
                       self.scanline, self.cycle, self.vram_addr.get(), self.temp_vram_addr.get());
         }
 
         let rendering_enabled = self.mask.show_background() || self.mask.show_sprites();
 
-        // --- Pre-render Scanline (-1 or 261) ---
-        if self.scanline == -1 || self.scanline == 261 {
+        // --- Pre-render Scanline (-1) ---
+        if self.scanline == -1 {
             if self.cycle == 1 {
                 // Clear VBlank, Sprite Overflow, Sprite Zero Hit flags
                 self.status.register &= !(0x80 | 0x20 | 0x40);
@@ -445,7 +456,7 @@ impl Ppu {
 
                         // Log current vram_addr before fetch
                         if self.cycle % 32 == 1 { // Log less frequently
-                            println!("[NT Fetch Cycle {}] v: {:04X}", self.cycle, self.vram_addr.get());
+                            log::trace!("[NT Fetch Cycle {}] v: {:04X}", self.cycle, self.vram_addr.get());
                         }
 
                         // Calculate address for Nametable byte
@@ -469,7 +480,7 @@ impl Ppu {
                         // --- Add Attribute Fetch Log ---
                         // Log less frequently
                         if self.cycle > 0 && (self.cycle % 32 == 3) && self.scanline >= 0 && (self.scanline % 16 == 0) { // Re-enable this log
-                             println!(
+                             log::trace!(
                                  "AttrFetch [Cycle {}, Scanline {}]: addr={:04X} mirrored={:04X} byte={:02X} shift={} -> attr={:02X} (v={:04X})",
                                  self.cycle, self.scanline,
                                  attr_addr, mirrored_attr_addr, attr_byte, shift, self.bg_next_tile_attr, self.vram_addr.get()
@@ -483,7 +494,7 @@ impl Ppu {
                         let tile_addr = pattern_table_base + (self.bg_next_tile_id as u16 * 16);
                         let fine_y = self.vram_addr.fine_y() as u16;
                         let addr = tile_addr + fine_y;
-                        println!("[PT Fetch Low Cycle {}, Scanline {}] PPUCTRL=${:02X}, bits={:08b}, bit4={}, Table=${:04X}, TileID=${:02X}, FineY={}, Addr=${:04X}, Raw Ctrl Bits: {:08b}",
+                        log::trace!("[PT Fetch Low Cycle {}, Scanline {}] PPUCTRL=${:02X}, bits={:08b}, bit4={}, Table=${:04X}, TileID=${:02X}, FineY={}, Addr=${:04X}, Raw Ctrl Bits: {:08b}",
                             self.cycle, self.scanline,
                             current_ctrl_bits,
                             current_ctrl_bits,
@@ -499,7 +510,7 @@ impl Ppu {
                         let pattern_table_base = self.ctrl.background_pattern_addr();
                         let current_ctrl_bits = self.ctrl.bits();
                         if self.cycle > 0 && self.cycle % 64 == 7 && self.scanline >= 0 {
-                            println!("[PT Fetch High Cycle {}, Scanline {}] TileID=${:02X}, Table=${:04X}, FineY={}, Addr=${:04X}",
+                            log::trace!("[PT Fetch High Cycle {}, Scanline {}] TileID=${:02X}, Table=${:04X}, FineY={}, Addr=${:04X}",
                                      self.cycle, self.scanline,
                                      self.bg_next_tile_id,
                                      pattern_table_base,
@@ -598,8 +609,8 @@ impl Ppu {
             // PPU is idle, CPU runs freely
         }
 
-        // --- VBlank Scanlines (241-260) ---
-        if self.scanline == 241 {
+        // --- VBlank Scanlines (NTSC 241-260, PAL 241-310, Dendy 291-310) ---
+        if self.scanline == self.region.vblank_scanline() {
             if self.cycle == 1 {
                 self.status.set_vblank_started(true);
                 if self.ctrl.generate_nmi() {
@@ -609,12 +620,30 @@ impl Ppu {
             }
         }
 
+        // 次のスキャンラインの開始位置を記録（プリレンダーの垂直コピー後 / 各ラインの水平コピー後）
+        let next_line_start = (self.scanline == -1 && self.cycle == 304) || ((0..239).contains(&self.scanline) && self.cycle == 257);
+        if next_line_start {
+            let mut addr = self.vram_addr.get();
+            if self.scanline == -1 {
+                // 水平方向はフレーム先頭で t からコピーされる値（実機のプリレンダー 257 サイクル相当）
+                addr = (addr & !0x041F) | (self.temp_vram_addr.get() & 0x041F);
+            }
+            let position = rendering_enabled.then(|| ScrollPosition::from_address(addr, self.fine_x_scroll));
+            self.scroll_log[(self.scanline + 1) as usize] = position;
+            // スプライト評価（次のラインに表示されるスプライト）。ライン 0 にはスプライトは表示されない
+            if self.scanline >= 0 {
+                self.sprite_lines[(self.scanline + 1) as usize] = if rendering_enabled { self.sprites_on_next_line() } else { 0 };
+            }
+        }
+
         // --- Cycle and Scanline Advancement ---
         self.cycle += 1;
         if self.cycle > 340 {
             self.cycle = 0;
             self.scanline += 1;
-            if self.scanline > 261 { // Wrap around after scanline 261 (pre-render scanline)
+            if self.scanline > self.region.scanlines_per_frame() - 2 { // Wrap around to the pre-render scanline
+                self.last_frame_scroll = std::mem::replace(&mut self.scroll_log, [None; VISIBLE_SCANLINES]);
+                self.last_frame_sprite_lines = std::mem::replace(&mut self.sprite_lines, [0; VISIBLE_SCANLINES]);
                 self.scanline = -1; // Reset to pre-render scanline for next frame
                 self.frame_complete = true;
                 self.frame_counter = self.frame_counter.wrapping_add(1);
@@ -645,7 +674,7 @@ impl Ppu {
         // --- Add Log BEFORE Loading ---
         // Log less frequently
         if self.cycle > 0 && (self.cycle % 32 == 1) && self.scanline >= 0 && (self.scanline % 16 == 0) {
-            println!(
+            log::trace!(
                 "LoadShifters Pre [Cycle {}, Scanline {}]: \
                  tile_id={:02X}, tile_attr={:02X}, lsb={:02X}, msb={:02X}, \
                  PRE_pat_lo={:04X}, PRE_pat_hi={:04X}", // Add PRE shifter values
@@ -674,7 +703,7 @@ impl Ppu {
         // --- DEBUG LOG AFTER Loading ---
         // Log less frequently
         if self.cycle > 0 && (self.cycle % 32 == 1) && self.scanline >= 0 && (self.scanline % 16 == 0) { // Re-enable this log
-             println!(
+             log::trace!(
                  "LoadShifters Post[Cycle {}, Scanline {}]: pat_lo={:04X}, pat_hi={:04X}, attr_lo={:04X}, attr_hi={:04X}",
                  self.cycle, self.scanline,
                  self.bg_shifter_pattern_lo, self.bg_shifter_pattern_hi,
@@ -695,7 +724,7 @@ impl Ppu {
         }
         // Log vram_addr after potential change
         if self.cycle % 32 == 0 { // Log less frequently
-            println!("[IncScrollX Cycle {}] v: {:04X}", self.cycle, self.vram_addr.get());
+            log::trace!("[IncScrollX Cycle {}] v: {:04X}", self.cycle, self.vram_addr.get());
         }
     }
 
@@ -713,6 +742,13 @@ impl Ppu {
         // println!("[IncScrollY Cycle {}] v: {:04X}", self.cycle, self.vram_addr.get()); 
     }
 
+    // OAM の Y は表示位置 -1。現在のラインから見て高さの範囲内にあるスプライトが次のラインに表示される
+    fn sprites_on_next_line(&self) -> u64 {
+        let height = if self.ctrl.sprite_size_large() { 16 } else { 8 };
+        let line = self.scanline as usize;
+        (0..64).filter(|&i| line.wrapping_sub(self.oam_data[i * 4] as usize) < height).fold(0, |mask, i| mask | (1 << i))
+    }
+
     // tからvへ水平関連ビットをコピー
     fn transfer_address_x(&mut self) {
         if self.mask.show_background() || self.mask.show_sprites() {
@@ -731,6 +767,11 @@ impl Ppu {
 
     // Pixel rendering process
     fn render_pixel(&mut self) {
+        // Frames that will never be displayed (fast-forward) don't need pixels
+        if self.skip_rendering {
+            return;
+        }
+
         // Get the current pixel coordinates
         let x = self.cycle - 1;
         let y = self.scanline;
@@ -832,7 +873,7 @@ impl Ppu {
             .filter(|rgba| rgba[0] != 0 || rgba[1] != 0 || rgba[2] != 0) // Check if R, G, or B is non-zero
             .count();
 
-        println!("PPU render_frame: non-black pixels: {} / total pixels: {}", non_black_pixels, frame.width * frame.height);
+        log::trace!("PPU render_frame: non-black pixels: {} / total pixels: {}", non_black_pixels, frame.width * frame.height);
 
         frame
     }
@@ -887,26 +928,26 @@ impl Ppu {
     }
 
     pub fn increment_vram_addr(&mut self) {
-        println!("[PPU] increment_vram_addr called. Current addr: ${:04X}", self.vram_addr.addr()); // ★★★ Log entry
+        log::trace!("[PPU] increment_vram_addr called. Current addr: ${:04X}", self.vram_addr.addr()); // ★★★ Log entry
         let increment = if self.ctrl.vram_addr_increment() != 0 { 32 } else { 1 }; // Check if the flag is non-zero
         self.vram_addr.increment(increment);
         // VRAM addresses wrap around above $3FFF, actual mirroring handled by bus read/write
         // self.vram_addr.set(self.vram_addr.get() % 0x4000); // Don't do simplified wrapping here
-        println!("[PPU Inc VRAM Addr] Incremented by {}. New Addr Reg: {:?}", increment, self.vram_addr);
+        log::trace!("[PPU Inc VRAM Addr] Incremented by {}. New Addr Reg: {:?}", increment, self.vram_addr);
     }
 
     pub fn write_ctrl(&mut self, data: u8) {
         let old_nmi_enable = self.ctrl.generate_nmi(); // Use helper method
         let old_bits = self.ctrl.bits();
-        println!("[PPU Write CTRL] Old bits=${:02X}, New bits=${:02X}", old_bits, data);
-        println!("[PPU Write CTRL] Old BG Pattern=${:04X}, New BG Pattern will be=${:04X}",
+        log::trace!("[PPU Write CTRL] Old bits=${:02X}, New bits=${:02X}", old_bits, data);
+        log::trace!("[PPU Write CTRL] Old BG Pattern=${:04X}, New BG Pattern will be=${:04X}",
                  if (old_bits & 0x10) == 0 { 0x0000 } else { 0x1000 },
                  if (data & 0x10) == 0 { 0x0000 } else { 0x1000 });
         
         self.ctrl.set_bits(data); // Use setter method
         
         // Verify the bits were actually set
-        println!("[PPU Write CTRL] Verification: CTRL bits=${:02X}, BG Pattern=${:04X}, NMI={}, Sprite Pattern=${:04X}, VRAM Inc={}",
+        log::trace!("[PPU Write CTRL] Verification: CTRL bits=${:02X}, BG Pattern=${:04X}, NMI={}, Sprite Pattern=${:04X}, VRAM Inc={}",
                  self.ctrl.bits(), self.ctrl.background_pattern_addr(),
                  self.ctrl.generate_nmi(), self.ctrl.sprite_pattern_addr(),
                  self.ctrl.vram_addr_increment());
@@ -938,21 +979,21 @@ impl Ppu {
 
     pub fn write_scroll(&mut self, data: u8) {
         // <<< Add Log >>>
-        println!("[PPU $2005 Write] Data=${:02X}, Latch={}", data, self.address_latch_low);
+        log::trace!("[PPU $2005 Write] Data=${:02X}, Latch={}", data, self.address_latch_low);
 
         if self.address_latch_low {
             // First write (X scroll)
             self.temp_vram_addr.set_coarse_x(data >> 3);
             self.fine_x_scroll = data & 0x07;
             // <<< Add Log >>>
-            println!("  -> First write: coarse_x={}, fine_x={}", data >> 3, self.fine_x_scroll);
+            log::trace!("  -> First write: coarse_x={}, fine_x={}", data >> 3, self.fine_x_scroll);
             self.address_latch_low = false;
         } else {
             // Second write (Y scroll)
             self.temp_vram_addr.set_fine_y(data & 0x07);
             self.temp_vram_addr.set_coarse_y(data >> 3);
              // <<< Add Log >>>
-            println!("  -> Second write: coarse_y={}, fine_y={}", data >> 3, data & 0x07);
+            log::trace!("  -> Second write: coarse_y={}, fine_y={}", data >> 3, data & 0x07);
            self.address_latch_low = true;
         }
     }
@@ -1065,7 +1106,7 @@ impl Ppu {
             // Address is outside the Nametable/Attribute table range ($2000-$3EFF)
             // This function is primarily for VRAM mirroring.
             // CHR reads ($0000-$1FFF) or Palette reads ($3F00-$3FFF) shouldn't rely on this.
-            eprintln!("[WARN] mirror_vram_addr called with non-VRAM address: {:04X}", addr);
+            log::warn!("[WARN] mirror_vram_addr called with non-VRAM address: {:04X}", addr);
             // Return a masked address, but this indicates a potential logic error elsewhere
             (addr_masked & 0x07FF) as usize // Return address within 2KB range as a fallback
         }
@@ -1110,7 +1151,7 @@ impl Ppu {
             0x1C => 0x0C,
             _ => mapped_addr,
         };
-        println!("[PPU Write Palette] Writing Data=${:02X} to Addr=${:04X} (Mapped from {:04X})", data, final_addr, addr);
+        log::trace!("[PPU Write Palette] Writing Data=${:02X} to Addr=${:04X} (Mapped from {:04X})", data, final_addr, addr);
         self.palette_ram[final_addr] = data;
     }
 
@@ -1132,22 +1173,22 @@ impl Ppu {
     // $2007 PPUDATA Write Handler
     pub fn write_data(&mut self, data: u8, bus: &mut impl BusAccess) {
         let addr = self.vram_addr.get();
-        println!("[PPU $2007 Write] write_data function entered! VRAM Addr=${:04X}, Data=${:02X}", addr, data); // ★★★ Log entry
+        log::trace!("[PPU $2007 Write] write_data function entered! VRAM Addr=${:04X}, Data=${:02X}", addr, data); // ★★★ Log entry
 
         // Write to appropriate memory (Palette RAM or VRAM/CHR via bus)
         if addr >= 0x3F00 {
             // Palette RAM write
-            println!("[PPU $2007 Write] Writing to Palette RAM addr=${:04X}", addr);
+            log::trace!("[PPU $2007 Write] Writing to Palette RAM addr=${:04X}", addr);
             self.write_palette_ram(addr, data); // Use the existing internal function
         } else {
             // VRAM/CHR write via bus
-            println!("[PPU $2007 Write] Writing to VRAM/CHR via bus addr=${:04X}", addr);
+            log::trace!("[PPU $2007 Write] Writing to VRAM/CHR via bus addr=${:04X}", addr);
             bus.ppu_write_vram(addr, data); // <- 修正後: PPU VRAM 書き込み用のメソッドを使用
         }
 
         // Increment VRAM address based on PPUCTRL setting
         self.increment_vram_addr();
-        println!("[PPU $2007 Write] VRAM address incremented. New VRAM Addr=${:04X}", self.vram_addr.get());
+        log::trace!("[PPU $2007 Write] VRAM address incremented. New VRAM Addr=${:04X}", self.vram_addr.get());
     }
 }
 
@@ -1157,6 +1198,35 @@ impl Default for Ppu {
     }
 }
 
+// 8x8タイル (16バイト: 下位プレーン8行 + 上位プレーン8行) を RGBA 画像に描画する。
+// colors はピクセル値 0-3 に対応する NES カラー番号。transparent なら値 0 のピクセルは描かない
+pub fn draw_tile(image: &mut FrameData, x_pos: usize, y_pos: usize, tile: &[u8], colors: &[u8; 4], transparent: bool) {
+    for y in 0..8 {
+        let low_byte = tile[y];
+        let high_byte = tile[y + 8];
+
+        for x in 0..8 {
+            let bit = 7 - x; // ビット位置は反転している
+            let pixel_value = (((high_byte >> bit) & 0x01) << 1) | ((low_byte >> bit) & 0x01);
+            if pixel_value == 0 && transparent {
+                continue;
+            }
+
+            let (r, g, b) = nes_color(colors[pixel_value as usize]);
+            let (screen_x, screen_y) = (x_pos + x, y_pos + y);
+            if screen_x < image.width && screen_y < image.height {
+                let idx = (screen_y * image.width + screen_x) * 4;
+                image.pixels[idx..idx + 4].copy_from_slice(&[r, g, b, 255]);
+            }
+        }
+    }
+}
+
+// NES カラー番号 -> RGB
+pub fn nes_color(index: u8) -> (u8, u8, u8) {
+    NES_PALETTE[(index & 0x3F) as usize]
+}
+
 // --- FrameData Definition (Re-added at the end) ---
 #[derive(Debug, Clone, Serialize)]
 pub struct FrameData {
@@ -1180,3 +1250,69 @@ impl Default for FrameData {
         Self::new(SCREEN_WIDTH, SCREEN_HEIGHT)
     }
 }
+
+// The frame buffer is not saved; it is redrawn by the next frame
+impl SaveState for Ppu {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.begin_section(b"PPU ");
+        w.write_bool(self.nmi_line_low);
+        w.write_u8(self.ctrl.bits());
+        w.write_u8(self.mask.bits());
+        w.write_u8(self.status.register);
+        w.write_u16(self.vram_addr.get());
+        w.write_u16(self.temp_vram_addr.get());
+        w.write_u8(self.fine_x_scroll);
+        w.write_bool(self.address_latch_low);
+        w.write_u8(self.data_buffer);
+        w.write_u8(self.oam_addr);
+        w.write_bytes(&self.oam_data);
+        w.write_u32(self.cycle as u32);
+        w.write_i32(self.scanline as i32);
+        w.write_bool(self.frame_complete);
+        w.write_u64(self.frame_counter);
+        w.write_bytes(&self.palette_ram);
+        w.write_bytes(&self.vram);
+        w.write_bytes(&self.chr_ram);
+        w.write_u8(self.mirroring.to_state_code());
+        w.write_u8(self.bg_next_tile_id);
+        w.write_u8(self.bg_next_tile_attr);
+        w.write_u8(self.bg_next_tile_lsb);
+        w.write_u8(self.bg_next_tile_msb);
+        w.write_u16(self.bg_shifter_pattern_lo);
+        w.write_u16(self.bg_shifter_pattern_hi);
+        w.write_u16(self.bg_shifter_attrib_lo);
+        w.write_u16(self.bg_shifter_attrib_hi);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.expect_section(b"PPU ")?;
+        self.nmi_line_low = r.read_bool()?;
+        self.ctrl.set_bits(r.read_u8()?);
+        self.mask.set_bits(r.read_u8()?);
+        self.status.register = r.read_u8()?;
+        self.vram_addr.set(r.read_u16()?);
+        self.temp_vram_addr.set(r.read_u16()?);
+        self.fine_x_scroll = r.read_u8()?;
+        self.address_latch_low = r.read_bool()?;
+        self.data_buffer = r.read_u8()?;
+        self.oam_addr = r.read_u8()?;
+        r.read_bytes_into(&mut self.oam_data)?;
+        self.cycle = r.read_u32()? as usize;
+        self.scanline = r.read_i32()? as isize;
+        self.frame_complete = r.read_bool()?;
+        self.frame_counter = r.read_u64()?;
+        r.read_bytes_into(&mut self.palette_ram)?;
+        r.read_bytes_into(&mut self.vram)?;
+        r.read_bytes_into(&mut self.chr_ram)?;
+        self.mirroring = Mirroring::from_state_code(r.read_u8()?)?;
+        self.bg_next_tile_id = r.read_u8()?;
+        self.bg_next_tile_attr = r.read_u8()?;
+        self.bg_next_tile_lsb = r.read_u8()?;
+        self.bg_next_tile_msb = r.read_u8()?;
+        self.bg_shifter_pattern_lo = r.read_u16()?;
+        self.bg_shifter_pattern_hi = r.read_u16()?;
+        self.bg_shifter_attrib_lo = r.read_u16()?;
+        self.bg_shifter_attrib_hi = r.read_u16()?;
+        Ok(())
+    }
+}
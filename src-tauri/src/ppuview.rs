@@ -0,0 +1,199 @@
+// PPU viewers for the debugger: pattern tables, nametables and OAM sprites rendered as images.
+// Everything here reads through side-effect free paths and can run at any time.
+use crate::bus::{Bus, MemorySpace};
+use crate::ppu::{draw_tile, FrameData, ScrollPosition};
+use serde::{Deserialize, Serialize};
+
+pub const PATTERN_TABLE_SIZE: usize = 0x1000;
+const TILE_SIZE: usize = 16;
+const PATTERN_TABLE_PIXELS: usize = 128; // 16x16 tiles
+const NAMETABLE_BASE: usize = 0x2000;
+const NAMETABLE_SIZE: usize = 0x400;
+const ATTRIBUTE_OFFSET: usize = 0x3C0;
+const NAMETABLE_COLUMNS: usize = 32;
+const NAMETABLE_ROWS: usize = 30;
+const SPRITE_COUNT: usize = 64;
+const SPRITES_PER_LINE: usize = 8;
+
+// Which CHR data the pattern table viewer shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PatternSource {
+    #[default]
+    Mapped, // $0000-$1FFF through the current mapper banks
+    Bank { index: usize }, // One 4KB bank of CHR ROM / RAM
+    All, // Every 4KB bank of CHR ROM / RAM
+}
+
+// The four colors of one of the eight palettes (0-3 background, 4-7 sprites); color 0 is the shared backdrop
+pub fn palette_colors(bus: &Bus, palette: u8) -> [u8; 4] {
+    let base = (palette as u16 & 0x07) * 4;
+    std::array::from_fn(|i| if i == 0 { bus.read_palette(0) } else { bus.read_palette(base + i as u16) })
+}
+
+// One 128x128 image per 4KB pattern table
+pub fn pattern_tables(bus: &Bus, source: PatternSource, palette: u8) -> Result<Vec<FrameData>, String> {
+    let chr: Vec<u8> = match source {
+        PatternSource::Mapped => (0..2 * PATTERN_TABLE_SIZE).map(|addr| bus.peek_memory(MemorySpace::Vram, addr)).collect(),
+        PatternSource::Bank { .. } | PatternSource::All => bus.cartridge().map_or(Vec::new(), |cart| cart.lock().unwrap().chr().to_vec()),
+    };
+    let tables: Vec<&[u8]> = match source {
+        PatternSource::Bank { index } => {
+            let bank = index
+                .checked_mul(PATTERN_TABLE_SIZE)
+                .and_then(|start| chr.get(start..start.checked_add(PATTERN_TABLE_SIZE)?));
+            vec![bank.ok_or_else(|| format!("CHR bank {} is out of range ({} bytes of CHR)", index, chr.len()))?]
+        }
+        _ => chr.chunks_exact(PATTERN_TABLE_SIZE).collect(),
+    };
+    let colors = palette_colors(bus, palette);
+    Ok(tables.into_iter().map(|table| render_pattern_table(table, &colors)).collect())
+}
+
+fn render_pattern_table(table: &[u8], colors: &[u8; 4]) -> FrameData {
+    let mut image = FrameData::new(PATTERN_TABLE_PIXELS, PATTERN_TABLE_PIXELS);
+    for (index, tile) in table.chunks_exact(TILE_SIZE).enumerate() {
+        draw_tile(&mut image, (index % 16) * 8, (index / 16) * 8, tile, colors, false);
+    }
+    image
+}
+
+// The four logical nametables ($2000 $2400 / $2800 $2C00) as one 512x480 image, with where the screen sits on it
+#[derive(Debug, Clone, Serialize)]
+pub struct NametableView {
+    pub image: FrameData,
+    pub scroll: ScrollPosition,  // Top left of the next frame, from t and fine X
+    pub current: ScrollPosition, // Where the PPU is fetching right now, from v and fine X
+    pub scanlines: Vec<Option<ScrollPosition>>, // Start of each visible line of the last frame; None while rendering was off
+}
+
+pub fn nametables(bus: &Bus) -> NametableView {
+    let ppu = bus.ppu.borrow();
+    let pattern_base = ppu.ctrl.background_pattern_addr() as usize;
+    let scroll = ScrollPosition::from_address(ppu.temp_vram_addr.get(), ppu.fine_x_scroll);
+    let current = ScrollPosition::from_address(ppu.vram_addr.get(), ppu.fine_x_scroll);
+    let scanlines = ppu.last_frame_scroll.to_vec();
+    drop(ppu); // peek_memory borrows the PPU again
+
+    let patterns: Vec<u8> = (pattern_base..pattern_base + PATTERN_TABLE_SIZE).map(|addr| bus.peek_memory(MemorySpace::Vram, addr)).collect();
+    let palettes: [[u8; 4]; 4] = std::array::from_fn(|palette| palette_colors(bus, palette as u8));
+    let mut image = FrameData::new(2 * NAMETABLE_COLUMNS * 8, 2 * NAMETABLE_ROWS * 8);
+    for table in 0..4 {
+        let base = NAMETABLE_BASE + table * NAMETABLE_SIZE;
+        let (left, top) = ((table & 1) * NAMETABLE_COLUMNS * 8, (table >> 1) * NAMETABLE_ROWS * 8);
+        for row in 0..NAMETABLE_ROWS {
+            for column in 0..NAMETABLE_COLUMNS {
+                let tile = bus.peek_memory(MemorySpace::Vram, base + row * NAMETABLE_COLUMNS + column) as usize;
+                // Each attribute byte covers 4x4 tiles, two bits per 2x2 quadrant
+                let attribute = bus.peek_memory(MemorySpace::Vram, base + ATTRIBUTE_OFFSET + (row / 4) * 8 + column / 4);
+                let palette = (attribute >> (((row & 2) << 1) | (column & 2))) & 0x03;
+                let pattern = &patterns[tile * TILE_SIZE..(tile + 1) * TILE_SIZE];
+                draw_tile(&mut image, left + column * 8, top + row * 8, pattern, &palettes[palette as usize], false);
+            }
+        }
+    }
+    NametableView { image, scroll, current, scanlines }
+}
+
+// One OAM entry, decoded
+#[derive(Debug, Clone, Serialize)]
+pub struct SpriteInfo {
+    pub index: u8,
+    pub x: u8,
+    pub y: u8,    // As stored in OAM: the sprite shows up from line y + 1
+    pub tile: u8, // For 8x16 sprites bit 0 selects the pattern table
+    pub palette: u8, // 4-7
+    pub behind_background: bool,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    pub dropped: bool, // Lost at least one line to the 8 sprites per line limit in the last frame
+    pub image: FrameData, // 8x8 or 8x16, transparent where the pattern is 0
+}
+
+// A scanline of the last frame with more than 8 sprites on it
+#[derive(Debug, Clone, Serialize)]
+pub struct SpriteOverflow {
+    pub scanline: u16,
+    pub drawn: Vec<u8>,   // OAM indices of the 8 sprites the PPU picked
+    pub dropped: Vec<u8>, // The rest, in OAM order
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpriteView {
+    pub large: bool, // 8x16 sprites
+    pub sprites: Vec<SpriteInfo>,
+    pub overflow: Vec<SpriteOverflow>,
+}
+
+pub fn sprites(bus: &Bus) -> SpriteView {
+    let ppu = bus.ppu.borrow();
+    let large = ppu.ctrl.sprite_size_large();
+    let pattern_base = ppu.ctrl.sprite_pattern_addr();
+    let oam = ppu.oam_data;
+    let lines = ppu.last_frame_sprite_lines;
+    drop(ppu); // peek_memory borrows the PPU again
+
+    // The PPU takes the first 8 sprites in OAM order
+    let overflow: Vec<SpriteOverflow> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, mask)| mask.count_ones() as usize > SPRITES_PER_LINE)
+        .map(|(scanline, &mask)| {
+            let indices: Vec<u8> = (0..SPRITE_COUNT as u8).filter(|&i| mask & (1 << i) != 0).collect();
+            let (drawn, dropped) = indices.split_at(SPRITES_PER_LINE);
+            SpriteOverflow { scanline: scanline as u16, drawn: drawn.to_vec(), dropped: dropped.to_vec() }
+        })
+        .collect();
+    let dropped_mask = overflow.iter().flat_map(|line| &line.dropped).fold(0u64, |mask, &i| mask | (1 << i));
+
+    let read_tile = |addr: u16| -> Vec<u8> { (0..TILE_SIZE as u16).map(|i| bus.peek_memory(MemorySpace::Vram, (addr + i) as usize)).collect() };
+    let sprites = oam
+        .chunks_exact(4)
+        .enumerate()
+        .map(|(index, entry)| {
+            let (y, tile, attributes, x) = (entry[0], entry[1], entry[2], entry[3]);
+            let palette = 4 + (attributes & 0x03);
+            let (flip_horizontal, flip_vertical) = (attributes & 0x40 != 0, attributes & 0x80 != 0);
+            // Top half first; an 8x16 sprite flipped vertically also swaps its two tiles
+            let mut tiles = if large {
+                let addr = (tile as u16 & 0x01) * PATTERN_TABLE_SIZE as u16 + (tile as u16 & 0xFE) * TILE_SIZE as u16;
+                vec![read_tile(addr), read_tile(addr + TILE_SIZE as u16)]
+            } else {
+                vec![read_tile(pattern_base + tile as u16 * TILE_SIZE as u16)]
+            };
+            if flip_vertical {
+                tiles.reverse();
+            }
+            let mut image = FrameData::new(8, 8 * tiles.len());
+            let colors = palette_colors(bus, palette);
+            for (half, pattern) in tiles.iter_mut().enumerate() {
+                flip_tile(pattern, flip_horizontal, flip_vertical);
+                draw_tile(&mut image, 0, half * 8, pattern, &colors, true);
+            }
+            SpriteInfo {
+                index: index as u8,
+                x,
+                y,
+                tile,
+                palette,
+                behind_background: attributes & 0x20 != 0,
+                flip_horizontal,
+                flip_vertical,
+                dropped: dropped_mask & (1 << index) != 0,
+                image,
+            }
+        })
+        .collect();
+    SpriteView { large, sprites, overflow }
+}
+
+// Mirrors one 16-byte tile in place: each row's bits for horizontal, the row order of both planes for vertical
+fn flip_tile(tile: &mut [u8], horizontal: bool, vertical: bool) {
+    if horizontal {
+        tile.iter_mut().for_each(|row| *row = row.reverse_bits());
+    }
+    if vertical {
+        tile[..8].reverse();
+        tile[8..].reverse();
+    }
+}
@@ -1,4 +1,5 @@
 use serde::Serialize;
+use crate::savestate::{SaveState, StateReader, StateWriter};
 
 #[derive(Serialize)]
 pub struct Memory {
@@ -33,3 +34,15 @@ impl Memory {
         }
     }
 }
+
+impl SaveState for Memory {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.begin_section(b"RAM ");
+        w.write_bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.expect_section(b"RAM ")?;
+        r.read_bytes_into(&mut self.ram)
+    }
+}
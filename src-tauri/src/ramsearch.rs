@@ -0,0 +1,180 @@
+// RAM search ("cheat finder"): narrow down where a game keeps a value by comparing memory between snapshots.
+// Covers internal RAM ($0000-$07FF) and PRG RAM ($6000-$7FFF); 16-bit values are little endian.
+// Found addresses can be frozen, i.e. rewritten with a fixed value every frame.
+use crate::expr::Machine;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+const SEARCH_RANGES: [RangeInclusive<u16>; 2] = [0x0000..=0x07FF, 0x6000..=0x7FFF];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueSize {
+    #[default]
+    Byte,
+    Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+}
+
+impl Comparison {
+    fn matches(self, value: i32, reference: i32) -> bool {
+        match self {
+            Comparison::Equal => value == reference,
+            Comparison::NotEqual => value != reference,
+            Comparison::Greater => value > reference,
+            Comparison::Less => value < reference,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Candidate {
+    pub address: u16,
+    pub value: i32,    // Now
+    pub previous: i32, // At the last snapshot / filter
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResults {
+    pub total: usize,
+    pub size: ValueSize,
+    pub signed: bool,
+    pub candidates: Vec<Candidate>, // At most the requested number
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Freeze {
+    pub address: u16,
+    pub value: u16,
+    pub size: ValueSize,
+}
+
+fn read_value(machine: &dyn Machine, addr: u16, size: ValueSize, signed: bool) -> i32 {
+    let lo = machine.peek(addr);
+    match (size, signed) {
+        (ValueSize::Byte, false) => lo as i32,
+        (ValueSize::Byte, true) => lo as i8 as i32,
+        (ValueSize::Word, _) => {
+            let word = u16::from_le_bytes([lo, machine.peek(addr.wrapping_add(1))]);
+            if signed { word as i16 as i32 } else { word as i32 }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RamSearch {
+    size: ValueSize,
+    signed: bool,
+    candidates: Vec<(u16, i32)>, // Address and value at the last snapshot
+}
+
+impl RamSearch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Every address is a candidate again; the current memory becomes the snapshot
+    pub fn start(&mut self, machine: &dyn Machine, size: ValueSize, signed: bool) {
+        self.size = size;
+        self.signed = signed;
+        // A word must not straddle the end of its region
+        let width = if size == ValueSize::Word { 1 } else { 0 };
+        self.candidates = SEARCH_RANGES
+            .iter()
+            .flat_map(|range| *range.start()..=*range.end() - width)
+            .map(|addr| (addr, read_value(machine, addr, size, signed)))
+            .collect();
+    }
+
+    pub fn reset(&mut self) {
+        self.candidates.clear();
+    }
+
+    // Record the current values without dropping any candidates
+    pub fn snapshot(&mut self, machine: &dyn Machine) {
+        let (size, signed) = (self.size, self.signed);
+        for (addr, previous) in &mut self.candidates {
+            *previous = read_value(machine, *addr, size, signed);
+        }
+    }
+
+    // Keep the candidates whose value compares true against `constant`, or against their previous value if None
+    pub fn filter(&mut self, machine: &dyn Machine, comparison: Comparison, constant: Option<i32>) {
+        let (size, signed) = (self.size, self.signed);
+        self.candidates.retain_mut(|(addr, previous)| {
+            let value = read_value(machine, *addr, size, signed);
+            let keep = comparison.matches(value, constant.unwrap_or(*previous));
+            *previous = value;
+            keep
+        });
+    }
+
+    pub fn results(&self, machine: &dyn Machine, limit: usize) -> SearchResults {
+        SearchResults {
+            total: self.candidates.len(),
+            size: self.size,
+            signed: self.signed,
+            candidates: self
+                .candidates
+                .iter()
+                .take(limit)
+                .map(|&(address, previous)| Candidate { address, value: read_value(machine, address, self.size, self.signed), previous })
+                .collect(),
+        }
+    }
+
+    pub fn size(&self) -> ValueSize {
+        self.size
+    }
+}
+
+// Only RAM can be frozen; other addresses are registers or ROM where writes have side effects
+pub fn check_ram_address(address: u16, size: ValueSize) -> Result<(), String> {
+    let last = if size == ValueSize::Word { address.checked_add(1) } else { Some(address) };
+    let in_ram = |addr: u16| SEARCH_RANGES.iter().any(|range| range.contains(&addr));
+    if in_ram(address) && last.is_some_and(in_ram) {
+        Ok(())
+    } else {
+        Err(format!("${:04X} is not in RAM ($0000-$07FF or $6000-$7FFF)", address))
+    }
+}
+
+// Checks a value typed by the user against the width of the search (signed or unsigned)
+pub fn encode_value(value: i32, size: ValueSize) -> Result<u16, String> {
+    let range = match size {
+        ValueSize::Byte => -0x80..=0xFF,
+        ValueSize::Word => -0x8000..=0xFFFF,
+    };
+    if !range.contains(&value) {
+        return Err(format!("{} doesn't fit in a {}", value, if size == ValueSize::Byte { "byte" } else { "word" }));
+    }
+    Ok(match size {
+        ValueSize::Byte => value as u8 as u16,
+        ValueSize::Word => value as u16,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_ram_can_be_frozen() {
+        assert!(check_ram_address(0x0000, ValueSize::Byte).is_ok());
+        assert!(check_ram_address(0x07FE, ValueSize::Word).is_ok());
+        assert!(check_ram_address(0x7FFF, ValueSize::Byte).is_ok());
+        assert!(check_ram_address(0x07FF, ValueSize::Word).is_err());
+        assert!(check_ram_address(0x2000, ValueSize::Byte).is_err());
+        assert!(check_ram_address(0x4016, ValueSize::Byte).is_err());
+        assert!(check_ram_address(0x8000, ValueSize::Byte).is_err());
+        assert!(check_ram_address(0xFFFF, ValueSize::Word).is_err());
+    }
+}
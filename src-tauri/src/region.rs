@@ -0,0 +1,235 @@
+// Console region: NTSC (RP2A03/RP2C02), PAL (RP2A07/RP2C07) or Dendy (UA6527P clone).
+// Decides the CPU/PPU clock ratio, frame layout, APU rate tables and frame rate.
+use crate::apu::{ApuTiming, DENDY_APU_TIMING, NTSC_APU_TIMING, PAL_APU_TIMING};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+impl Region {
+    pub fn name(self) -> &'static str {
+        match self {
+            Region::Ntsc => "NTSC",
+            Region::Pal => "PAL",
+            Region::Dendy => "Dendy",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "ntsc" => Ok(Region::Ntsc),
+            "pal" => Ok(Region::Pal),
+            "dendy" => Ok(Region::Dendy),
+            _ => Err(format!("Unknown region: {}", name)),
+        }
+    }
+
+    // PPU dots per CPU cycle as a fraction (3 for NTSC and Dendy, 3.2 for PAL)
+    pub fn ppu_clock_ratio(self) -> (u32, u32) {
+        match self {
+            Region::Ntsc | Region::Dendy => (3, 1),
+            Region::Pal => (16, 5),
+        }
+    }
+
+    // Including the pre-render line
+    pub fn scanlines_per_frame(self) -> isize {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    // Scanline on which the vblank flag is set and NMI fires.
+    // PAL stretches vblank to 70 lines; the Dendy instead adds 50 idle lines before a 20-line vblank.
+    pub fn vblank_scanline(self) -> isize {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    pub fn frame_rate(self) -> f64 {
+        match self {
+            Region::Ntsc => 60.0988,
+            Region::Pal | Region::Dendy => 50.0070,
+        }
+    }
+
+    pub fn apu_timing(self) -> ApuTiming {
+        match self {
+            Region::Ntsc => NTSC_APU_TIMING,
+            Region::Pal => PAL_APU_TIMING,
+            Region::Dendy => DENDY_APU_TIMING,
+        }
+    }
+
+    // CPU cycles in one frame (rounded up)
+    pub fn cpu_cycles_per_frame(self) -> u32 {
+        let (num, den) = self.ppu_clock_ratio();
+        let dots = self.scanlines_per_frame() as u32 * 341;
+        (dots * den).div_ceil(num)
+    }
+
+    // Stable numbering used by save states
+    pub fn to_state_code(self) -> u8 {
+        match self {
+            Region::Ntsc => 0,
+            Region::Pal => 1,
+            Region::Dendy => 2,
+        }
+    }
+
+    pub fn from_state_code(code: u8) -> Result<Self, String> {
+        match code {
+            0 => Ok(Region::Ntsc),
+            1 => Ok(Region::Pal),
+            2 => Ok(Region::Dendy),
+            _ => Err(format!("Invalid region code in save state: {}", code)),
+        }
+    }
+
+    // NES 2.0 byte 12 (CPU/PPU timing), or the rarely used iNES byte 9 TV system bit
+    pub fn from_header(header: &[u8]) -> Option<Self> {
+        if header.len() < 16 {
+            return None;
+        }
+        let is_nes2 = header[7] & 0x0C == 0x08;
+        if is_nes2 {
+            return Some(Self::from_timing_code(header[12]));
+        }
+        // Most iNES dumps leave byte 9 at 0, so only trust an explicit PAL bit
+        if header[9] & 0x01 != 0 {
+            return Some(Region::Pal);
+        }
+        None
+    }
+
+    // NES 2.0 CPU/PPU timing value, shared by the header and the NES 2.0 game database
+    fn from_timing_code(code: u8) -> Self {
+        match code & 0x03 {
+            0 => Region::Ntsc,
+            1 => Region::Pal,
+            2 => Region::Ntsc, // Multi-region: prefer NTSC
+            _ => Region::Dendy,
+        }
+    }
+
+    // Region tags in No-Intro / GoodNES style file names, e.g. "Game (Europe).nes" or "Game (E).nes"
+    pub fn from_file_name(path: &Path) -> Option<Self> {
+        let name = path.file_stem()?.to_string_lossy().to_ascii_lowercase();
+        const PAL_TAGS: [&str; 11] = [
+            "(e)", "(europe", "(pal)", "(a)", "(australia", "(g)", "(germany", "(f)", "(france", "(i)", "(spain",
+        ];
+        const NTSC_TAGS: [&str; 5] = ["(u)", "(usa", "(j)", "(japan", "(world"];
+        if name.contains("(dendy)") || name.contains("(russia") {
+            Some(Region::Dendy)
+        } else if NTSC_TAGS.iter().any(|tag| name.contains(tag)) {
+            Some(Region::Ntsc)
+        } else if PAL_TAGS.iter().any(|tag| name.contains(tag)) {
+            Some(Region::Pal)
+        } else {
+            None
+        }
+    }
+}
+
+// Game database keyed by the CRC32 of the ROM data without the iNES header (PRG + CHR).
+// Reads the NES 2.0 XML database (nes20db.xml), where each <game> has a
+// <rom crc32="..."/> and a <console region="..."/> element; everything else is ignored.
+#[derive(Debug, Clone, Default)]
+pub struct GameDatabase {
+    regions: HashMap<u32, Region>,
+}
+
+pub const GAME_DATABASE_FILE: &str = "nes20db.xml";
+
+impl GameDatabase {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read game database {}: {}", path.display(), e))?;
+        let database = Self::parse(&text);
+        if database.is_empty() {
+            return Err(format!("No games found in {}", path.display()));
+        }
+        Ok(database)
+    }
+
+    // nes20db.xml next to the executable, if there is one
+    pub fn load_default() -> Self {
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| Self::load(&exe.with_file_name(GAME_DATABASE_FILE)).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut regions = HashMap::new();
+        for game in text.split("<game>").skip(1) {
+            let game = game.split("</game>").next().unwrap_or(game);
+            let crc = xml_attribute(game, "rom", "crc32").and_then(|v| u32::from_str_radix(v, 16).ok());
+            let region = xml_attribute(game, "console", "region").and_then(|v| v.parse::<u8>().ok());
+            if let (Some(crc), Some(region)) = (crc, region) {
+                regions.insert(crc, Region::from_timing_code(region));
+            }
+        }
+        GameDatabase { regions }
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    pub fn region(&self, prg_rom: &[u8], chr_rom: &[u8]) -> Option<Region> {
+        if self.regions.is_empty() {
+            return None;
+        }
+        let rom = [prg_rom, chr_rom].concat();
+        self.regions.get(&crate::patch::crc32(&rom)).copied()
+    }
+}
+
+// Value of `attribute` on the first <element ...> in `text`
+fn xml_attribute<'a>(text: &'a str, element: &str, attribute: &str) -> Option<&'a str> {
+    let start = text.find(&format!("<{} ", element))?;
+    let tag = &text[start..];
+    let tag = &tag[..tag.find('>')?];
+    let key = format!(" {}=\"", attribute);
+    let value = &tag[tag.find(&key)? + key.len()..];
+    Some(&value[..value.find('"')?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_database_lookup() {
+        let prg = vec![0xEAu8; 0x4000];
+        let chr = vec![0x55u8; 0x2000];
+        let crc = crate::patch::crc32(&[prg.as_slice(), chr.as_slice()].concat());
+        let xml = format!(
+            "<nes20db>\n<game>\n  <!-- Test (Europe).nes -->\n  <prgrom size=\"16384\" crc32=\"00000000\"/>\n  \
+             <rom size=\"24576\" crc32=\"{:08X}\"/>\n  <console type=\"0\" region=\"1\"/>\n</game>\n\
+             <game><rom crc32=\"12345678\"/><console region=\"3\"/></game>\n\
+             <game><rom crc32=\"zz\"/><console region=\"1\"/></game>\n</nes20db>",
+            crc
+        );
+        let database = GameDatabase::parse(&xml);
+        assert_eq!(database.len(), 2);
+        assert_eq!(database.region(&prg, &chr), Some(Region::Pal));
+        assert_eq!(database.region(&prg, &[]), None);
+    }
+}
@@ -0,0 +1,396 @@
+// Rewind buffer: periodic save states kept as XOR+RLE deltas, plus a per-frame input log
+// so that every intermediate frame can be reproduced by re-emulating from a snapshot.
+//
+// Positions count emulated frames. The snapshot at position p is the state before frame p runs,
+// and inputs[p - base] is the controller input used while emulating frame p.
+use crate::ppu::FrameData;
+use std::collections::VecDeque;
+
+pub const DEFAULT_SNAPSHOT_INTERVAL: u32 = 5;
+pub const DEFAULT_MEMORY_BUDGET: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameInput {
+    pub controller1: u8,
+    pub controller2: u8,
+}
+
+enum SnapshotData {
+    Full(Vec<u8>),  // Only the newest snapshot
+    Delta(Vec<u8>), // XOR against the next newer snapshot, zero-run compressed
+}
+
+struct Snapshot {
+    position: u64,
+    data: SnapshotData,
+}
+
+impl Snapshot {
+    fn size(&self) -> usize {
+        match &self.data {
+            SnapshotData::Full(d) | SnapshotData::Delta(d) => d.len(),
+        }
+    }
+}
+
+// A re-emulated frame waiting to be shown while rewinding
+pub struct PlaybackFrame {
+    pub position: u64,
+    pub state: Vec<u8>,
+    pub frame: FrameData,
+}
+
+pub struct RewindBuffer {
+    interval: u32,
+    memory_budget: usize,
+    snapshots: VecDeque<Snapshot>, // Oldest first
+    inputs: VecDeque<FrameInput>,
+    input_base: u64, // Position of inputs[0]
+    position: u64,
+    used_bytes: usize,
+    playback: Vec<PlaybackFrame>, // Re-emulated frames, newest last
+}
+
+impl RewindBuffer {
+    pub fn new(interval: u32, memory_budget: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            memory_budget,
+            snapshots: VecDeque::new(),
+            inputs: VecDeque::new(),
+            input_base: 0,
+            position: 0,
+            used_bytes: 0,
+            playback: Vec::new(),
+        }
+    }
+
+    pub fn configure(&mut self, interval: u32, memory_budget: usize) {
+        self.interval = interval.max(1);
+        self.memory_budget = memory_budget;
+        self.enforce_budget();
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.interval, self.memory_budget);
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn oldest_position(&self) -> Option<u64> {
+        self.snapshots.front().map(|s| s.position)
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    // Does the frame about to run need a snapshot first?
+    pub fn wants_snapshot(&self) -> bool {
+        self.position.is_multiple_of(self.interval as u64)
+            && self.snapshots.back().is_none_or(|s| s.position != self.position)
+    }
+
+    // Record the state before the next frame runs (only called when wants_snapshot() is true)
+    pub fn push_snapshot(&mut self, state: Vec<u8>) {
+        // The previous newest becomes a delta against the new one
+        if let Some(prev) = self.snapshots.back_mut() {
+            if let SnapshotData::Full(prev_state) = &prev.data {
+                let delta = encode_delta(&state, prev_state);
+                self.used_bytes = self.used_bytes - prev_state.len() + delta.len();
+                prev.data = SnapshotData::Delta(delta);
+            }
+        }
+        if self.snapshots.is_empty() {
+            self.input_base = self.position;
+            self.inputs.clear();
+        }
+        self.used_bytes += state.len();
+        self.snapshots.push_back(Snapshot { position: self.position, data: SnapshotData::Full(state) });
+        self.enforce_budget();
+    }
+
+    // Log the input of the frame about to run and advance the position
+    pub fn record_frame(&mut self, input: FrameInput) {
+        if self.snapshots.is_empty() {
+            // Nothing to replay from yet
+            self.position += 1;
+            return;
+        }
+        self.inputs.push_back(input);
+        self.used_bytes += std::mem::size_of::<FrameInput>();
+        self.position += 1;
+    }
+
+    pub fn input_at(&self, position: u64) -> Option<FrameInput> {
+        position
+            .checked_sub(self.input_base)
+            .and_then(|i| self.inputs.get(i as usize).copied())
+    }
+
+    fn enforce_budget(&mut self) {
+        // Always keep at least the newest snapshot
+        while self.used_bytes > self.memory_budget && self.snapshots.len() > 1 {
+            let oldest = self.snapshots.pop_front().unwrap();
+            self.used_bytes -= oldest.size();
+            let next_position = self.snapshots.front().unwrap().position;
+            while self.input_base < next_position && !self.inputs.is_empty() {
+                self.inputs.pop_front();
+                self.input_base += 1;
+                self.used_bytes -= std::mem::size_of::<FrameInput>();
+            }
+        }
+    }
+
+    // Full state of the newest snapshot at or before `position`
+    pub fn snapshot_before(&self, position: u64) -> Option<(u64, Vec<u8>)> {
+        let mut state = match &self.snapshots.back()?.data {
+            SnapshotData::Full(d) => d.clone(),
+            SnapshotData::Delta(_) => return None,
+        };
+        for snapshot in self.snapshots.iter().rev() {
+            if let SnapshotData::Delta(delta) = &snapshot.data {
+                state = decode_delta(&state, delta)?;
+            }
+            if snapshot.position <= position {
+                return Some((snapshot.position, state));
+            }
+        }
+        None
+    }
+
+    // Forget everything after `position` so recording continues from there
+    pub fn truncate_to(&mut self, position: u64) {
+        while self.snapshots.back().is_some_and(|s| s.position > position) {
+            let newest = self.snapshots.pop_back().unwrap();
+            self.used_bytes -= newest.size();
+            let SnapshotData::Full(newest_state) = newest.data else { break };
+            // Promote the next newest snapshot to a full state
+            if let Some(prev) = self.snapshots.back_mut() {
+                if let SnapshotData::Delta(delta) = &prev.data {
+                    if let Some(full) = decode_delta(&newest_state, delta) {
+                        self.used_bytes = self.used_bytes - delta.len() + full.len();
+                        prev.data = SnapshotData::Full(full);
+                    }
+                }
+            }
+        }
+        let keep = position.saturating_sub(self.input_base) as usize;
+        while self.inputs.len() > keep {
+            self.inputs.pop_back();
+            self.used_bytes -= std::mem::size_of::<FrameInput>();
+        }
+        self.playback.retain(|f| f.position <= position);
+        self.position = position;
+    }
+
+    pub fn take_playback_frame(&mut self, position: u64) -> Option<PlaybackFrame> {
+        if self.playback.last().is_some_and(|f| f.position == position) {
+            self.playback.pop()
+        } else {
+            self.playback.clear();
+            None
+        }
+    }
+
+    pub fn set_playback(&mut self, frames: Vec<PlaybackFrame>) {
+        self.playback = frames;
+    }
+
+    pub fn is_playing_back(&self) -> bool {
+        !self.playback.is_empty()
+    }
+}
+
+// --- Delta encoding: XOR with the base, then (zero run, literal run) pairs as varints ---
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut value = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+        if shift > 63 {
+            return None;
+        }
+    }
+}
+
+fn encode_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let xor_at = |i: usize| target[i] ^ base.get(i).copied().unwrap_or(0);
+    let mut out = Vec::new();
+    write_varint(&mut out, target.len());
+
+    let mut i = 0;
+    while i < target.len() {
+        let zero_start = i;
+        while i < target.len() && xor_at(i) == 0 {
+            i += 1;
+        }
+        let literal_start = i;
+        // A literal run ends at the first stretch of 4+ unchanged bytes
+        while i < target.len() {
+            if xor_at(i) == 0 && (i..(i + 4).min(target.len())).all(|j| xor_at(j) == 0) {
+                break;
+            }
+            i += 1;
+        }
+        write_varint(&mut out, literal_start - zero_start);
+        write_varint(&mut out, i - literal_start);
+        out.extend((literal_start..i).map(xor_at));
+    }
+    out
+}
+
+fn decode_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 0;
+    let len = read_varint(delta, &mut pos)?;
+    let mut out: Vec<u8> = (0..len).map(|i| base.get(i).copied().unwrap_or(0)).collect();
+
+    let mut i = 0;
+    while i < len {
+        i = i.checked_add(read_varint(delta, &mut pos)?)?;
+        let literal_len = read_varint(delta, &mut pos)?;
+        let literal = delta.get(pos..pos.checked_add(literal_len)?)?;
+        out.get_mut(i..i.checked_add(literal_len)?)?
+            .iter_mut()
+            .zip(literal)
+            .for_each(|(b, x)| *b ^= x);
+        pos += literal_len;
+        i += literal_len;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(base: &[u8], target: &[u8]) -> Vec<u8> {
+        let delta = encode_delta(base, target);
+        assert_eq!(decode_delta(base, &delta).as_deref(), Some(target));
+        delta
+    }
+
+    #[test]
+    fn delta_round_trips() {
+        let base: Vec<u8> = (0..=255).collect();
+
+        // Empty buffers on either side
+        assert_eq!(round_trip(&[], &[]), vec![0]);
+        round_trip(&base, &[]);
+        round_trip(&[], &base);
+
+        // Unchanged data is a single zero run
+        assert_eq!(round_trip(&base, &base), vec![0x80, 0x02, 0x80, 0x02, 0x00]);
+
+        // Every byte changed is a single literal run
+        let flipped: Vec<u8> = base.iter().map(|b| !b).collect();
+        let delta = round_trip(&base, &flipped);
+        assert_eq!(delta.len(), 2 + 1 + 2 + 256);
+
+        // Target longer or shorter than the base
+        round_trip(&base[..100], &flipped);
+        round_trip(&base, &flipped[..100]);
+    }
+
+    #[test]
+    fn delta_run_boundaries() {
+        let base = vec![0u8; 32];
+        let with_changes = |positions: &[usize]| {
+            let mut target = base.clone();
+            for &i in positions {
+                target[i] = 0xFF;
+            }
+            target
+        };
+
+        // Up to three unchanged bytes stay inside a literal run
+        let delta = round_trip(&base, &with_changes(&[4, 8]));
+        assert_eq!(delta, [&[32, 4, 5, 0xFF, 0, 0, 0, 0xFF][..], &[23, 0]].concat());
+
+        // Four unchanged bytes split it
+        let delta = round_trip(&base, &with_changes(&[4, 9]));
+        assert_eq!(delta, vec![32, 4, 1, 0xFF, 4, 1, 0xFF, 22, 0]);
+
+        // Changes at both ends
+        round_trip(&base, &with_changes(&[0, 31]));
+        round_trip(&base, &with_changes(&[28, 29, 30, 31]));
+
+        // Run lengths on the varint boundary
+        let long_base = vec![0u8; 300];
+        let mut target = long_base.clone();
+        target[127..255].fill(1);
+        let delta = round_trip(&long_base, &target);
+        assert_eq!(&delta[2..6], &[0x7F, 0x80, 0x01, 1]);
+    }
+
+    #[test]
+    fn corrupt_deltas_are_rejected() {
+        let base = vec![0u8; 8];
+        assert_eq!(decode_delta(&base, &[]), None);
+        assert_eq!(decode_delta(&base, &[8, 0, 4, 1, 2]), None); // Literal runs past the data
+        assert_eq!(decode_delta(&base, &[8, 6, 4, 1, 2, 3, 4]), None); // Literal runs past the end
+    }
+
+    fn state(position: u64) -> Vec<u8> {
+        vec![position as u8; 16]
+    }
+
+    // Record `frames` frames the way the emulator does, taking snapshots when asked
+    fn record(buffer: &mut RewindBuffer, frames: u64) {
+        for _ in 0..frames {
+            if buffer.wants_snapshot() {
+                buffer.push_snapshot(state(buffer.position()));
+            }
+            let input = FrameInput { controller1: buffer.position() as u8, controller2: 0 };
+            buffer.record_frame(input);
+        }
+    }
+
+    #[test]
+    fn truncate_after_seeking_back() {
+        let mut buffer = RewindBuffer::new(5, DEFAULT_MEMORY_BUDGET);
+        record(&mut buffer, 23);
+        assert_eq!(buffer.position(), 23);
+        assert_eq!(buffer.snapshot_before(22), Some((20, state(20))));
+        assert_eq!(buffer.snapshot_before(12), Some((10, state(10))));
+
+        buffer.truncate_to(12);
+        assert_eq!(buffer.position(), 12);
+        assert_eq!(buffer.snapshot_before(u64::MAX), Some((10, state(10))));
+        assert_eq!(buffer.snapshot_before(4), Some((0, state(0))));
+        assert_eq!(buffer.input_at(11), Some(FrameInput { controller1: 11, controller2: 0 }));
+        assert_eq!(buffer.input_at(12), None);
+
+        // Recording carries on from the truncated position
+        record(&mut buffer, 8);
+        assert_eq!(buffer.position(), 20);
+        assert_eq!(buffer.snapshot_before(19), Some((15, state(15))));
+        assert_eq!(buffer.snapshot_before(13), Some((10, state(10))));
+        assert_eq!(buffer.input_at(12), Some(FrameInput { controller1: 12, controller2: 0 }));
+
+        // Memory accounting matches a buffer that recorded the same history directly
+        let mut direct = RewindBuffer::new(5, DEFAULT_MEMORY_BUDGET);
+        record(&mut direct, 20);
+        assert_eq!(buffer.used_bytes(), direct.used_bytes());
+    }
+}
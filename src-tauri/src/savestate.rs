@@ -0,0 +1,276 @@
+// Binary save state format.
+// Layout: magic, format version, CRC32 of the loaded ROM image, then one tagged section per component.
+// Bump STATE_VERSION whenever a component changes what it writes.
+
+pub const STATE_MAGIC: &[u8; 8] = b"TNESSAVE";
+pub const STATE_VERSION: u32 = 5; // 2: APU state, 3: region, 4: CPU cycle count, 5: NROM PRG RAM
+
+// Components that can be written to / restored from a save state
+pub trait SaveState {
+    fn save_state(&self, w: &mut StateWriter);
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String>;
+}
+
+#[derive(Default)]
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Sections make corrupt or mismatched states fail with a readable error
+    pub fn begin_section(&mut self, tag: &[u8; 4]) {
+        self.buf.extend_from_slice(tag);
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.buf.push(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_i64(&mut self, value: i64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Length-prefixed byte block
+    pub fn write_bytes(&mut self, data: &[u8]) {
+        self.write_u32(data.len() as u32);
+        self.buf.extend_from_slice(data);
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len());
+        let Some(end) = end else {
+            return Err(format!("Save state is truncated at offset {}", self.pos));
+        };
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    pub fn expect_section(&mut self, tag: &[u8; 4]) -> Result<(), String> {
+        let found = self.take(4)?;
+        if found != tag {
+            return Err(format!(
+                "Save state section mismatch: expected '{}', found '{}'",
+                String::from_utf8_lossy(tag),
+                String::from_utf8_lossy(found)
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, String> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_i64(&mut self) -> Result<i64, String> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_vec(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.read_u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    // Read a length-prefixed block into a fixed-size buffer (sizes must match)
+    pub fn read_bytes_into(&mut self, out: &mut [u8]) -> Result<(), String> {
+        let len = self.read_u32()? as usize;
+        if len != out.len() {
+            return Err(format!(
+                "Save state block size mismatch: expected {} bytes, found {}",
+                out.len(),
+                len
+            ));
+        }
+        out.copy_from_slice(self.take(len)?);
+        Ok(())
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.pos == self.data.len()
+    }
+}
+
+#[derive(Debug)]
+pub struct StateHeader {
+    pub version: u32,
+    pub rom_crc32: u32,
+}
+
+pub fn write_header(w: &mut StateWriter, rom_crc32: u32) {
+    w.buf.extend_from_slice(STATE_MAGIC);
+    w.write_u32(STATE_VERSION);
+    w.write_u32(rom_crc32);
+}
+
+pub fn read_header(r: &mut StateReader) -> Result<StateHeader, String> {
+    if r.take(STATE_MAGIC.len())? != STATE_MAGIC {
+        return Err("Not a save state file".to_string());
+    }
+    let version = r.read_u32()?;
+    if version != STATE_VERSION {
+        return Err(format!(
+            "Unsupported save state version {} (expected {})",
+            version, STATE_VERSION
+        ));
+    }
+    let rom_crc32 = r.read_u32()?;
+    Ok(StateHeader { version, rom_crc32 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_with_header() -> Vec<u8> {
+        let mut w = StateWriter::new();
+        write_header(&mut w, 0x1234_5678);
+        w.begin_section(b"TEST");
+        w.write_u8(0xAB);
+        w.into_inner()
+    }
+
+    #[test]
+    fn every_value_round_trips() {
+        let mut w = StateWriter::new();
+        w.begin_section(b"TEST");
+        w.write_u8(0xAB);
+        w.write_bool(true);
+        w.write_bool(false);
+        w.write_u16(0xBEEF);
+        w.write_u32(0xDEAD_BEEF);
+        w.write_u64(0x0123_4567_89AB_CDEF);
+        w.write_i32(-2);
+        w.write_i64(i64::MIN);
+        w.write_bytes(&[1, 2, 3]);
+        w.write_bytes(&[]);
+        w.write_bytes(&[4, 5]);
+        let data = w.into_inner();
+
+        let mut r = StateReader::new(&data);
+        r.expect_section(b"TEST").unwrap();
+        assert_eq!(r.read_u8().unwrap(), 0xAB);
+        assert!(r.read_bool().unwrap());
+        assert!(!r.read_bool().unwrap());
+        assert_eq!(r.read_u16().unwrap(), 0xBEEF);
+        assert_eq!(r.read_u32().unwrap(), 0xDEAD_BEEF);
+        assert_eq!(r.read_u64().unwrap(), 0x0123_4567_89AB_CDEF);
+        assert_eq!(r.read_i32().unwrap(), -2);
+        assert_eq!(r.read_i64().unwrap(), i64::MIN);
+        assert_eq!(r.read_vec().unwrap(), vec![1, 2, 3]);
+        assert_eq!(r.read_vec().unwrap(), Vec::<u8>::new());
+        let mut out = [0u8; 2];
+        r.read_bytes_into(&mut out).unwrap();
+        assert_eq!(out, [4, 5]);
+        assert!(r.is_at_end());
+    }
+
+    #[test]
+    fn header_round_trips() {
+        let data = state_with_header();
+        let mut r = StateReader::new(&data);
+        let header = read_header(&mut r).unwrap();
+        assert_eq!(header.version, STATE_VERSION);
+        assert_eq!(header.rom_crc32, 0x1234_5678);
+        r.expect_section(b"TEST").unwrap();
+        assert_eq!(r.read_u8().unwrap(), 0xAB);
+    }
+
+    #[test]
+    fn truncated_states_are_errors() {
+        let data = state_with_header();
+        for len in 0..data.len() {
+            let mut r = StateReader::new(&data[..len]);
+            let result = read_header(&mut r).and_then(|_| r.expect_section(b"TEST")).and_then(|_| r.read_u8());
+            assert!(result.is_err(), "{} bytes should not parse", len);
+        }
+
+        // Length prefixes that point past the end
+        let mut w = StateWriter::new();
+        w.write_u32(100);
+        w.write_u8(0);
+        let data = w.into_inner();
+        assert!(StateReader::new(&data).read_vec().is_err());
+        assert!(StateReader::new(&data).read_bytes_into(&mut [0u8; 100]).is_err());
+    }
+
+    #[test]
+    fn bad_magic_version_and_sections_are_errors() {
+        let mut data = state_with_header();
+        data[0] = b'X';
+        assert!(read_header(&mut StateReader::new(&data)).unwrap_err().contains("Not a save state"));
+
+        let mut data = state_with_header();
+        data[STATE_MAGIC.len()..STATE_MAGIC.len() + 4].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+        assert!(read_header(&mut StateReader::new(&data)).unwrap_err().contains("version"));
+
+        let data = state_with_header();
+        let mut r = StateReader::new(&data);
+        read_header(&mut r).unwrap();
+        assert!(r.expect_section(b"CPU ").unwrap_err().contains("mismatch"));
+
+        // Fixed-size blocks must match exactly
+        let mut w = StateWriter::new();
+        w.write_bytes(&[1, 2, 3]);
+        let data = w.into_inner();
+        assert!(StateReader::new(&data).read_bytes_into(&mut [0u8; 2]).unwrap_err().contains("size mismatch"));
+    }
+}
@@ -0,0 +1,305 @@
+// Debugger symbol table: labels and comments, imported from assembler / other emulators' files or set by hand.
+//
+// Labels in PRG ROM are keyed by their PRG ROM offset, so the same CPU address can carry different names
+// in different banks; they only show up while their bank is mapped. Everything else is keyed by CPU address.
+//
+// Supported files:
+//   .dbg  ca65/ld65 debug info (sym records of type "lab")
+//   .nl   FCEUX name lists: <rom>.<bank>.nl (hex bank number, 16KB banks) or <rom>.ram.nl, lines "$C000#name#comment"
+//   .mlb  Mesen label files, lines "P:1A2B:name:comment" (P = PRG ROM offset, R = internal RAM, S/W = PRG RAM, G = register)
+use crate::dbginfo::DebugInfo;
+use crate::disasm::Labels;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub const BANK_SIZE: usize = 0x2000; // Banks are reported in 8KB units, like prgbank() in conditions
+const NL_BANK_SIZE: usize = 0x4000;
+const PRG_RAM_START: u16 = 0x6000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Symbol {
+    pub name: String, // Empty for comment-only entries
+    pub address: Option<u16>, // CPU address; None for PRG ROM labels that only know their ROM offset
+    pub prg_offset: Option<usize>,
+    pub bank: Option<usize>,
+    pub comment: Option<String>,
+}
+
+impl Symbol {
+    pub fn new(name: &str, address: Option<u16>, prg_offset: Option<usize>, comment: Option<&str>) -> Self {
+        Self {
+            name: name.trim().to_string(),
+            address,
+            prg_offset,
+            bank: prg_offset.map(|o| o / BANK_SIZE),
+            comment: comment.map(str::trim).filter(|c| !c.is_empty()).map(str::to_string),
+        }
+    }
+
+    // Where the symbol currently is in the CPU address space; `mapped` gives the PRG ROM offset of a CPU address
+    pub fn cpu_address(&self, mapped: &impl Fn(u16) -> Option<usize>) -> Option<u16> {
+        let Some(offset) = self.prg_offset else { return self.address };
+        if let Some(addr) = self.address.filter(|&a| mapped(a) == Some(offset)) {
+            return Some(addr);
+        }
+        (PRG_RAM_START..=0xE000).step_by(BANK_SIZE).find_map(|base| {
+            let start = mapped(base)?;
+            (start..start + BANK_SIZE).contains(&offset).then(|| base + (offset - start) as u16)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum SymbolKey {
+    Cpu(u16),
+    Prg(usize),
+}
+
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    symbols: BTreeMap<SymbolKey, Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A later entry for the same place replaces the name and/or comment it provides
+    pub fn insert(&mut self, symbol: Symbol) {
+        let key = match symbol.prg_offset {
+            Some(offset) => SymbolKey::Prg(offset),
+            None => match symbol.address {
+                Some(addr) => SymbolKey::Cpu(addr),
+                None => return,
+            },
+        };
+        match self.symbols.get_mut(&key) {
+            Some(existing) => {
+                if !symbol.name.is_empty() {
+                    existing.name = symbol.name;
+                }
+                if symbol.comment.is_some() {
+                    existing.comment = symbol.comment;
+                }
+                existing.address = existing.address.or(symbol.address);
+            }
+            None => {
+                self.symbols.insert(key, symbol);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, addr: u16, prg_offset: Option<usize>) {
+        match prg_offset {
+            Some(offset) => self.symbols.remove(&SymbolKey::Prg(offset)),
+            None => self.symbols.remove(&SymbolKey::Cpu(addr)),
+        };
+    }
+
+    pub fn clear(&mut self) {
+        self.symbols.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn all(&self) -> Vec<Symbol> {
+        self.symbols.values().cloned().collect()
+    }
+
+    // The symbol for a CPU address given what is mapped there
+    pub fn at(&self, addr: u16, prg_offset: Option<usize>) -> Option<&Symbol> {
+        prg_offset
+            .and_then(|o| self.symbols.get(&SymbolKey::Prg(o)))
+            .or_else(|| self.symbols.get(&SymbolKey::Cpu(addr)))
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.values().find(|s| s.name == name)
+    }
+
+    // Names for the CPU address space as currently mapped
+    pub fn labels(&self, mapped: &impl Fn(u16) -> Option<usize>) -> Labels {
+        self.symbols
+            .values()
+            .filter(|s| !s.name.is_empty())
+            .filter_map(|s| Some((s.cpu_address(mapped)?, s.name.clone())))
+            .collect()
+    }
+
+    // Names for one PRG ROM bank viewed at `base`, plus everything outside PRG ROM
+    pub fn bank_labels(&self, bank_start: usize, bank_size: usize, base: u16) -> Labels {
+        self.symbols
+            .values()
+            .filter(|s| !s.name.is_empty())
+            .filter_map(|s| match s.prg_offset {
+                Some(o) => {
+                    let offset = o.checked_sub(bank_start).filter(|&offset| offset < bank_size)?;
+                    Some((base.wrapping_add(offset as u16), s.name.clone()))
+                }
+                None => Some((s.address?, s.name.clone())),
+            })
+            .collect()
+    }
+
+    // Import by file extension; returns how many entries were read
+    pub fn import_file(&mut self, path: &Path) -> Result<usize, String> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+        let symbols = match extension.as_str() {
+            "dbg" => {
+                let info = DebugInfo::load(path)?;
+                info.symbols.iter().map(|s| Symbol::new(&s.name, Some(s.address), s.prg_offset, None)).collect()
+            }
+            "nl" => parse_nl(&read_text(path)?, nl_bank(path)?),
+            "mlb" => parse_mlb(&read_text(path)?)?,
+            _ => return Err(format!("Unknown symbol file type: {}", path.display())),
+        };
+        let count = symbols.len();
+        for symbol in symbols {
+            self.insert(symbol);
+        }
+        Ok(count)
+    }
+}
+
+fn read_text(path: &Path) -> Result<String, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// "game.nes.1F.nl" -> Some(0x1F), "game.nes.ram.nl" -> None
+fn nl_bank(path: &Path) -> Result<Option<usize>, String> {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let suffix = Path::new(stem).extension().and_then(|e| e.to_str()).unwrap_or_default();
+    if suffix.eq_ignore_ascii_case("ram") {
+        return Ok(None);
+    }
+    usize::from_str_radix(suffix, 16)
+        .map(Some)
+        .map_err(|_| format!("Can't tell the bank of {} (expected <rom>.<bank>.nl or <rom>.ram.nl)", path.display()))
+}
+
+// "$C000#name#comment"; "$0300/10#name#" labels the start of a 16-byte array
+fn parse_nl(text: &str, bank: Option<usize>) -> Vec<Symbol> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().splitn(3, '#');
+            let address = fields.next()?.trim().strip_prefix('$')?;
+            let address = address.split('/').next()?;
+            let address = u16::from_str_radix(address, 16).ok()?;
+            let name = fields.next().unwrap_or_default();
+            let comment = fields.next().map(|c| c.trim_end_matches('#'));
+            let prg_offset = bank.filter(|_| address >= 0x8000).map(|b| b * NL_BANK_SIZE + (address as usize - 0x8000) % NL_BANK_SIZE);
+            Some(Symbol::new(name, Some(address), prg_offset, comment))
+        })
+        .collect()
+}
+
+// "P:1A2B:name:comment" (Mesen) or "NesPrgRom:1A2B:name:comment" (Mesen 2); ranges like "R:0300-030F" use the start
+fn parse_mlb(text: &str) -> Result<Vec<Symbol>, String> {
+    let mut symbols = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line.splitn(4, ':');
+        let (Some(kind), Some(address), Some(name)) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(format!("Line {}: expected TYPE:ADDRESS:LABEL[:COMMENT]", number + 1));
+        };
+        let comment = fields.next().map(|c| c.replace("\\n", "\n"));
+        let start = address.split('-').next().unwrap_or_default();
+        let offset = usize::from_str_radix(start, 16).map_err(|_| format!("Line {}: invalid address '{}'", number + 1, address))?;
+        let (address, prg_offset) = match kind {
+            "P" | "NesPrgRom" => (None, Some(offset)),
+            "R" | "NesInternalRam" => (Some((offset & 0x07FF) as u16), None),
+            "S" | "W" | "NesSaveRam" | "NesWorkRam" => (Some(PRG_RAM_START.wrapping_add(offset as u16)), None),
+            "G" | "NesMemory" | "Register" => (Some(offset as u16), None),
+            _ => continue, // CHR, palette, ... aren't in the CPU address space
+        };
+        symbols.push(Symbol::new(name, address, prg_offset, comment.as_deref()));
+    }
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nl_bank_suffixes() {
+        assert_eq!(nl_bank(Path::new("game.nes.1F.nl")), Ok(Some(0x1F)));
+        assert_eq!(nl_bank(Path::new("dir/game.nes.0.nl")), Ok(Some(0)));
+        assert_eq!(nl_bank(Path::new("game.nes.ram.nl")), Ok(None));
+        assert_eq!(nl_bank(Path::new("game.nes.RAM.nl")), Ok(None));
+        assert!(nl_bank(Path::new("game.nes.zz.nl")).is_err());
+        assert!(nl_bank(Path::new("game.nl")).is_err());
+    }
+
+    #[test]
+    fn nl_labels_and_arrays() {
+        let text = "$C000#Reset#entry point#\r\n$0300/10#buffer#\nnot a label\n$8123##comment only#\n$XYZ#bad#\n";
+        let symbols = parse_nl(text, Some(2));
+        assert_eq!(symbols.len(), 3);
+
+        assert_eq!(symbols[0].name, "Reset");
+        assert_eq!(symbols[0].address, Some(0xC000));
+        assert_eq!(symbols[0].prg_offset, Some(2 * NL_BANK_SIZE));
+        assert_eq!(symbols[0].comment.as_deref(), Some("entry point"));
+
+        // Arrays are labelled at their start; RAM has no PRG offset even in a bank file
+        assert_eq!(symbols[1].name, "buffer");
+        assert_eq!(symbols[1].address, Some(0x0300));
+        assert_eq!(symbols[1].prg_offset, None);
+        assert_eq!(symbols[1].comment, None);
+
+        assert_eq!(symbols[2].name, "");
+        assert_eq!(symbols[2].prg_offset, Some(2 * NL_BANK_SIZE + 0x123));
+        assert_eq!(symbols[2].comment.as_deref(), Some("comment only"));
+
+        let ram = parse_nl("$C000#Reset#", None);
+        assert_eq!(ram[0].prg_offset, None);
+    }
+
+    #[test]
+    fn mlb_forms() {
+        let text = "P:1A2B:reset:entry\\npoint\n\
+                    NesPrgRom:0010:nmi\n\
+                    R:0300-030F:buffer\n\
+                    R:0900:mirrored\n\
+                    S:0010:save\n\
+                    G:2000:PPUCTRL\n\
+                    C:0000:tile\n\
+                    \n";
+        let symbols = parse_mlb(text).unwrap();
+        let summary: Vec<_> = symbols.iter().map(|s| (s.name.as_str(), s.address, s.prg_offset)).collect();
+        assert_eq!(
+            summary,
+            [
+                ("reset", None, Some(0x1A2B)),
+                ("nmi", None, Some(0x0010)),
+                ("buffer", Some(0x0300), None),
+                ("mirrored", Some(0x0100), None),
+                ("save", Some(0x6010), None),
+                ("PPUCTRL", Some(0x2000), None),
+            ]
+        );
+        assert_eq!(symbols[0].comment.as_deref(), Some("entry\npoint"));
+        assert_eq!(symbols[0].bank, Some(0x1A2B / BANK_SIZE));
+    }
+
+    #[test]
+    fn malformed_mlb_lines_are_errors() {
+        let error = parse_mlb("P:0000:ok\nP:1234\n").unwrap_err();
+        assert!(error.starts_with("Line 2"), "{}", error);
+        let error = parse_mlb("R:zz:bad").unwrap_err();
+        assert!(error.starts_with("Line 1"), "{}", error);
+    }
+}
@@ -0,0 +1,184 @@
+// CPU execution trace in Nintendulator / nestest.log format, e.g.
+// C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+//
+// Lines go to a writer (file) or to a bounded in-memory ring that the debugger UI can poll.
+use crate::bus::BusAccess;
+use crate::cpu::{AddressingMode, Cpu6502};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Write;
+
+pub const DEFAULT_RING_CAPACITY: usize = 10_000;
+
+enum TraceSink {
+    Writer(Box<dyn Write + Send>),
+    Ring { lines: VecDeque<String>, capacity: usize },
+}
+
+pub struct CpuTracer {
+    sink: TraceSink,
+}
+
+impl fmt::Debug for CpuTracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.sink {
+            TraceSink::Writer(_) => write!(f, "CpuTracer(writer)"),
+            TraceSink::Ring { lines, capacity } => write!(f, "CpuTracer(ring {}/{})", lines.len(), capacity),
+        }
+    }
+}
+
+impl CpuTracer {
+    pub fn to_writer(writer: Box<dyn Write + Send>) -> Self {
+        Self { sink: TraceSink::Writer(writer) }
+    }
+
+    pub fn ring(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self { sink: TraceSink::Ring { lines: VecDeque::with_capacity(capacity.min(4096)), capacity } }
+    }
+
+    pub fn log(&mut self, line: String) {
+        match &mut self.sink {
+            TraceSink::Writer(writer) => {
+                if writeln!(writer, "{}", line).is_ok() {
+                    return;
+                }
+            }
+            TraceSink::Ring { lines, capacity } => {
+                if lines.len() == *capacity {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+                return;
+            }
+        }
+        // A full disk shouldn't take the emulator down; keep tracing in memory instead
+        eprintln!("CPU trace write failed, switching to in-memory trace");
+        self.sink = TraceSink::Ring { lines: VecDeque::from([line]), capacity: DEFAULT_RING_CAPACITY };
+    }
+
+    // The newest `max` lines, oldest first (empty for file traces)
+    pub fn recent_lines(&self, max: usize) -> Vec<String> {
+        match &self.sink {
+            TraceSink::Writer(_) => Vec::new(),
+            TraceSink::Ring { lines, .. } => lines.iter().skip(lines.len().saturating_sub(max)).cloned().collect(),
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if let TraceSink::Writer(writer) = &mut self.sink {
+            let _ = writer.flush();
+        }
+    }
+}
+
+impl Drop for CpuTracer {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+// Line for the instruction at the CPU's current PC (called before it executes)
+pub fn format_nestest_line(cpu: &Cpu6502, bus: &impl BusAccess) -> String {
+    let r = &cpu.registers;
+    let pc = r.program_counter;
+    let opcode = bus.peek(pc);
+    let (mode, _, name) = Cpu6502::opcode_info(opcode);
+    let (name, unofficial) = match name.strip_suffix('*') {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+
+    let operand_len = operand_length(mode);
+    let bytes: Vec<String> = (0..=operand_len)
+        .map(|i| format!("{:02X}", bus.peek(pc.wrapping_add(i))))
+        .collect();
+    let operand = format_operand(cpu, bus, mode, name, pc);
+    let asm = if operand.is_empty() { name.to_string() } else { format!("{} {}", name, operand) };
+    let (scanline, dot) = bus.ppu_position();
+
+    format!(
+        "{:04X}  {:<9}{}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        pc,
+        bytes.join(" "),
+        if unofficial { '*' } else { ' ' },
+        asm,
+        r.accumulator,
+        r.x_register,
+        r.y_register,
+        r.status,
+        r.stack_pointer,
+        scanline,
+        dot,
+        cpu.total_cycles
+    )
+}
+
+pub fn operand_length(mode: AddressingMode) -> u16 {
+    match mode {
+        AddressingMode::Implied | AddressingMode::Accumulator => 0,
+        AddressingMode::Immediate
+        | AddressingMode::ZeroPage
+        | AddressingMode::ZeroPageX
+        | AddressingMode::ZeroPageY
+        | AddressingMode::Relative
+        | AddressingMode::IndexedIndirect
+        | AddressingMode::IndirectIndexed => 1,
+        AddressingMode::Absolute | AddressingMode::AbsoluteX | AddressingMode::AbsoluteY | AddressingMode::Indirect => 2,
+    }
+}
+
+// Operand with the effective address and the value there, as nestest.log shows it
+fn format_operand(cpu: &Cpu6502, bus: &impl BusAccess, mode: AddressingMode, name: &str, pc: u16) -> String {
+    let r = &cpu.registers;
+    let b1 = bus.peek(pc.wrapping_add(1));
+    let b2 = bus.peek(pc.wrapping_add(2));
+    let abs = u16::from_le_bytes([b1, b2]);
+    let zp_ptr = |zp: u8| u16::from_le_bytes([bus.peek(zp as u16), bus.peek(zp.wrapping_add(1) as u16)]);
+
+    match mode {
+        AddressingMode::Implied => String::new(),
+        AddressingMode::Accumulator => "A".to_string(),
+        AddressingMode::Immediate => format!("#${:02X}", b1),
+        AddressingMode::ZeroPage => format!("${:02X} = {:02X}", b1, bus.peek(b1 as u16)),
+        AddressingMode::ZeroPageX => {
+            let addr = b1.wrapping_add(r.x_register);
+            format!("${:02X},X @ {:02X} = {:02X}", b1, addr, bus.peek(addr as u16))
+        }
+        AddressingMode::ZeroPageY => {
+            let addr = b1.wrapping_add(r.y_register);
+            format!("${:02X},Y @ {:02X} = {:02X}", b1, addr, bus.peek(addr as u16))
+        }
+        AddressingMode::Relative => {
+            let target = pc.wrapping_add(2).wrapping_add(b1 as i8 as u16);
+            format!("${:04X}", target)
+        }
+        AddressingMode::Absolute if name == "JMP" || name == "JSR" => format!("${:04X}", abs),
+        AddressingMode::Absolute => format!("${:04X} = {:02X}", abs, bus.peek(abs)),
+        AddressingMode::AbsoluteX => {
+            let addr = abs.wrapping_add(r.x_register as u16);
+            format!("${:04X},X @ {:04X} = {:02X}", abs, addr, bus.peek(addr))
+        }
+        AddressingMode::AbsoluteY => {
+            let addr = abs.wrapping_add(r.y_register as u16);
+            format!("${:04X},Y @ {:04X} = {:02X}", abs, addr, bus.peek(addr))
+        }
+        AddressingMode::Indirect => {
+            // The 6502 doesn't carry into the high byte when fetching the pointer
+            let hi_addr = (abs & 0xFF00) | (abs.wrapping_add(1) & 0x00FF);
+            let target = u16::from_le_bytes([bus.peek(abs), bus.peek(hi_addr)]);
+            format!("(${:04X}) = {:04X}", abs, target)
+        }
+        AddressingMode::IndexedIndirect => {
+            let zp = b1.wrapping_add(r.x_register);
+            let addr = zp_ptr(zp);
+            format!("(${:02X},X) @ {:02X} = {:04X} = {:02X}", b1, zp, addr, bus.peek(addr))
+        }
+        AddressingMode::IndirectIndexed => {
+            let base = zp_ptr(b1);
+            let addr = base.wrapping_add(r.y_register as u16);
+            format!("(${:02X}),Y = {:04X} @ {:04X} = {:02X}", b1, base, addr, bus.peek(addr))
+        }
+    }
+}
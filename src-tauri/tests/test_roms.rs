@@ -0,0 +1,225 @@
+// Accuracy test ROM harness (blargg-style suites), run headlessly.
+//
+// ROMs are not part of the repository, so these tests are ignored by default. Put them under
+// tests/roms/<suite>/ (cpu, ppu, apu, mapper) or point NES_TEST_ROMS at a directory with the same
+// layout, then run `cargo test --test test_roms -- --ignored`. A suite without ROMs fails.
+//
+// A ROM passes when:
+// - it uses the $6000 protocol (signature DE B0 61 at $6001) and ends with status 0, or
+// - it has an entry in <roms>/screen_hashes.txt and the final frame matches:
+//       <path relative to the roms dir> <frames> <crc32 of RGBA pixels, hex>
+//   (`nes-headless <rom> -n <frames>` prints the CRC to record.)
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use tauri_nes::emulator::Emulator;
+use tauri_nes::patch;
+
+const DEFAULT_MAX_FRAMES: u32 = 60 * 60; // Slowest blargg tests finish in well under a minute
+const RESET_DELAY_FRAMES: u32 = 6; // Protocol asks for a reset "after at least 100ms"
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_NEEDS_RESET: u8 = 0x81;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+#[derive(Debug)]
+enum Outcome {
+    Pass,
+    Fail(String),
+    Skip(String),
+}
+
+struct ScreenHash {
+    frames: u32,
+    crc32: u32,
+}
+
+fn roms_dir() -> PathBuf {
+    match std::env::var_os("NES_TEST_ROMS") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms"),
+    }
+}
+
+fn max_frames() -> u32 {
+    std::env::var("NES_TEST_MAX_FRAMES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_FRAMES)
+}
+
+fn load_screen_hashes(root: &Path) -> HashMap<String, ScreenHash> {
+    let Ok(text) = std::fs::read_to_string(root.join("screen_hashes.txt")) else {
+        return HashMap::new();
+    };
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let path = fields.next()?.replace('\\', "/");
+            let frames = fields.next()?.parse().ok()?;
+            let crc32 = u32::from_str_radix(fields.next()?.trim_start_matches("0x"), 16).ok()?;
+            Some((path, ScreenHash { frames, crc32 }))
+        })
+        .collect()
+}
+
+fn find_roms(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            find_roms(&path, out);
+        } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("nes")) {
+            out.push(path);
+        }
+    }
+}
+
+fn load(rom: &Path) -> Result<Emulator, String> {
+    let mut emulator = Emulator::new();
+    emulator.rewind_enabled = false;
+    emulator.load_rom(&rom.to_string_lossy())?;
+    Ok(emulator)
+}
+
+fn has_signature(emulator: &Emulator) -> bool {
+    (0..3).all(|i| emulator.bus.debug_read(0x6001 + i) == SIGNATURE[i as usize])
+}
+
+// Zero-terminated text the ROM writes at $6004
+fn result_text(emulator: &Emulator) -> String {
+    let mut text = String::new();
+    for addr in 0x6004..0x8000u16 {
+        let byte = emulator.bus.debug_read(addr);
+        if byte == 0 {
+            break;
+        }
+        text.push(byte as char);
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn run_protocol_rom(rom: &Path) -> Outcome {
+    let mut emulator = match load(rom) {
+        Ok(emulator) => emulator,
+        Err(e) => return Outcome::Fail(e),
+    };
+    let mut reset_at = None;
+    for frame in 0..max_frames() {
+        if let Err(e) = emulator.run_frame() {
+            return Outcome::Fail(e);
+        }
+        if !has_signature(&emulator) {
+            continue;
+        }
+        match emulator.bus.debug_read(0x6000) {
+            STATUS_RUNNING => {}
+            STATUS_NEEDS_RESET => match reset_at {
+                None => reset_at = Some(frame + RESET_DELAY_FRAMES),
+                Some(at) if frame >= at => {
+                    emulator.reset();
+                    reset_at = None;
+                }
+                Some(_) => {}
+            },
+            0 => return Outcome::Pass,
+            code => return Outcome::Fail(format!("status {:02X}: {}", code, result_text(&emulator))),
+        }
+    }
+    if has_signature(&emulator) {
+        Outcome::Fail(format!("timed out: {}", result_text(&emulator)))
+    } else {
+        Outcome::Skip("no $6000 result and no screen hash".to_string())
+    }
+}
+
+fn run_screen_hash_rom(rom: &Path, expected: &ScreenHash) -> Outcome {
+    let mut emulator = match load(rom) {
+        Ok(emulator) => emulator,
+        Err(e) => return Outcome::Fail(e),
+    };
+    let mut frame = Default::default();
+    for _ in 0..expected.frames {
+        match emulator.run_frame() {
+            Ok(f) => frame = f,
+            Err(e) => return Outcome::Fail(e),
+        }
+    }
+    let crc32 = patch::crc32(&frame.pixels);
+    if crc32 == expected.crc32 {
+        Outcome::Pass
+    } else {
+        Outcome::Fail(format!("screen crc32 {:08X}, expected {:08X}", crc32, expected.crc32))
+    }
+}
+
+// Run every ROM of a suite and print a pass/fail table; panics if anything failed or no ROMs were found
+fn run_suite(suite: &str) {
+    let root = roms_dir();
+    let suite_dir = root.join(suite);
+    let mut roms = Vec::new();
+    find_roms(&suite_dir, &mut roms);
+    assert!(
+        !roms.is_empty(),
+        "no {} test ROMs found in {} (put them there or set NES_TEST_ROMS)",
+        suite,
+        suite_dir.display()
+    );
+    let hashes = load_screen_hashes(&root);
+
+    let mut table = String::new();
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for rom in &roms {
+        let name = rom.strip_prefix(&root).unwrap_or(rom).to_string_lossy().replace('\\', "/");
+        let outcome = match hashes.get(&name) {
+            Some(expected) => run_screen_hash_rom(rom, expected),
+            None => run_protocol_rom(rom),
+        };
+        let (label, detail) = match &outcome {
+            Outcome::Pass => {
+                passed += 1;
+                ("PASS", "")
+            }
+            Outcome::Fail(detail) => {
+                failed += 1;
+                ("FAIL", detail.as_str())
+            }
+            Outcome::Skip(detail) => {
+                skipped += 1;
+                ("SKIP", detail.as_str())
+            }
+        };
+        let _ = writeln!(table, "{:<4}  {:<60}  {}", label, name, detail);
+    }
+
+    println!("=== {} suite: {} passed, {} failed, {} skipped ===", suite, passed, failed, skipped);
+    print!("{}", table);
+    assert_eq!(failed, 0, "{} of {} {} test ROMs failed", failed, roms.len(), suite);
+}
+
+#[test]
+#[ignore = "needs test ROMs in tests/roms or NES_TEST_ROMS"]
+fn cpu_test_roms() {
+    run_suite("cpu");
+}
+
+#[test]
+#[ignore = "needs test ROMs in tests/roms or NES_TEST_ROMS"]
+fn ppu_test_roms() {
+    run_suite("ppu");
+}
+
+#[test]
+#[ignore = "needs test ROMs in tests/roms or NES_TEST_ROMS"]
+fn apu_test_roms() {
+    run_suite("apu");
+}
+
+#[test]
+#[ignore = "needs test ROMs in tests/roms or NES_TEST_ROMS"]
+fn mapper_test_roms() {
+    run_suite("mapper");
+}
@@ -1,4 +1,5 @@
-import { invoke } from "@tauri-apps/api/tauri";
+import { invoke, convertFileSrc } from "@tauri-apps/api/tauri";
+import { listen } from "@tauri-apps/api/event";
 import { open } from '@tauri-apps/api/dialog'
 import React, { useState, useEffect, useRef, useCallback } from "react"; // Import useState and useRef
 import "./App.css";
@@ -68,75 +69,88 @@ function App() {
     const [isRunning, setIsRunning] = useState(false); // State to track if emulator is running
     const [canvasCtx, setCanvasCtx] = useState<CanvasRenderingContext2D | null>(null); // Ref for the canvas context
 
+    const audioCtxRef = useRef<AudioContext | null>(null); // Created lazily (needs a user gesture)
+    const nextAudioTime = useRef(0); // AudioContext time at which the next chunk starts
+    const frameFetchPending = useRef(false); // Skip frame-ready events while a fetch is in flight
+
+    // Queue a chunk of emulator audio right after the previous one
+    const playAudio = useCallback((buffer: ArrayBuffer) => {
+        const audioCtx = audioCtxRef.current;
+        if (!audioCtx || buffer.byteLength <= 4) return;
+        // Layout: [u32 sample rate][f32 samples...], little endian
+        const sampleRate = new DataView(buffer).getUint32(0, true);
+        const samples = new Float32Array(buffer.slice(4));
+        const chunk = audioCtx.createBuffer(1, samples.length, sampleRate);
+        chunk.copyToChannel(samples, 0);
+        const source = audioCtx.createBufferSource();
+        source.buffer = chunk;
+        source.connect(audioCtx.destination);
+        // Restart slightly ahead after an underrun instead of piling up latency
+        const startAt = Math.max(nextAudioTime.current, audioCtx.currentTime + 0.02);
+        source.start(startAt);
+        nextAudioTime.current = startAt + chunk.duration;
+    }, []);
+
+    // Fetch the latest frame (and pending audio) published by the emulation thread
     const drawFrame = useCallback(async () => {
         if (!canvasRef.current || !canvasCtx) return; // Check if canvasCtx is available
-        
+        if (frameFetchPending.current) return;
+        frameFetchPending.current = true;
+
         const canvas = canvasRef.current;
         const ctx = canvasCtx; // Use the state variable
-        const width = canvas.width;
-        const height = canvas.height;
 
         try {
-            const frameDataResult = await invoke<FrameData>('get_frame');
-            console.log(`Received frame: ${frameDataResult.width}x${frameDataResult.height}`);
-            console.log(`Pixel data length: ${frameDataResult.pixels.length}`);
-            console.log(`Expected length (RGB): ${frameDataResult.width * frameDataResult.height * 3}`);
-            console.log(`First 15 pixels:`, frameDataResult.pixels.slice(0, 15));
-            const frameData = frameDataResult.pixels;
-            const frameWidth = frameDataResult.width;
-            const frameHeight = frameDataResult.height;
-
-            if (frameData && frameWidth > 0 && frameHeight > 0) {
+            const [frameBuffer, audioBuffer] = await Promise.all([
+                fetch(convertFileSrc('frame', 'nes')).then(r => r.arrayBuffer()),
+                fetch(convertFileSrc('audio', 'nes')).then(r => r.arrayBuffer()),
+            ]);
+            playAudio(audioBuffer);
+
+            // Layout: [u32 frame number][u16 width][u16 height][RGBA pixels...], little endian
+            if (frameBuffer.byteLength < 8) return;
+            const header = new DataView(frameBuffer);
+            const frameNumber = header.getUint32(0, true);
+            const frameWidth = header.getUint16(4, true);
+            const frameHeight = header.getUint16(6, true);
+
+            if (frameWidth > 0 && frameHeight > 0) {
                 // Ensure the canvas size matches the frame data
                 if (canvas.width !== frameWidth || canvas.height !== frameHeight) {
                     canvas.width = frameWidth;
                     canvas.height = frameHeight;
                 }
-
-                const imageData = ctx.createImageData(frameWidth, frameHeight);
-                
-                // Convert RGBA (from backend) to ImageData's format if needed
-                // Assuming frameData is already in the correct Uint8ClampedArray format
-                if (frameData instanceof Uint8Array || frameData instanceof Uint8ClampedArray) {
-                    imageData.data.set(frameData);
-                } else if (Array.isArray(frameData)) {
-                    // Handle array case if backend sends plain array
-                    imageData.data.set(new Uint8ClampedArray(frameData));
-                } else {
-                    console.error("Received invalid frame data format:", typeof frameData);
-                    // Optionally draw an error message on canvas
-                    ctx.fillStyle = 'red';
-                    ctx.fillRect(0, 0, width, height);
-                    ctx.fillStyle = 'white';
-                    ctx.font = '20px Arial';
-                    ctx.fillText('Invalid Data', 10, height / 2);
-                    return; // Stop further processing
-                }
-                
-                ctx.putImageData(imageData, 0, 0); // Use ctx which is guaranteed non-null here
-            } else {
-                // Draw placeholder or background if no valid frame data
-                ctx.fillStyle = '#333';
-                ctx.fillRect(0, 0, width, height);
-                ctx.fillStyle = 'white';
-                ctx.font = '16px Arial';
-                ctx.fillText('Waiting for frame...', 10, 30);
+                const pixels = new Uint8ClampedArray(frameBuffer, 8, frameWidth * frameHeight * 4);
+                ctx.putImageData(new ImageData(pixels, frameWidth, frameHeight), 0, 0);
+                setFrameCount(frameNumber);
             }
         } catch (error) {
             console.error('Error fetching or drawing frame:', error);
-            // Draw error state on canvas
-            ctx.fillStyle = 'black';
-            ctx.fillRect(0, 0, width, height);
-            ctx.fillStyle = 'red';
-            ctx.font = '20px Arial';
-            ctx.fillText('Drawing Error', 10, height / 2);
+        } finally {
+            frameFetchPending.current = false;
         }
+    }, [canvasCtx, playAudio]); // Dependencies for useCallback
+
+    // The emulation thread announces every finished frame
+    useEffect(() => {
+        const unlisten = listen<number>('frame-ready', () => {
+            animationFrameId.current = requestAnimationFrame(() => { drawFrame(); });
+        });
+        return () => {
+            unlisten.then(f => f());
+        };
+    }, [drawFrame]);
 
-        // Request the next frame after a short delay
-        setTimeout(() => {
-            animationFrameId.current = requestAnimationFrame(drawFrame); // Pass drawFrame directly
-        }, 16); // Add a delay (e.g., 16ms for ~60fps)
-    }, [canvasCtx]); // Dependencies for useCallback
+    // Debugger stops (breakpoints) are pushed with the reason and CPU registers
+    useEffect(() => {
+        const unlisten = listen<{ reason: { kind: string; address?: number }; cpu: { registers: { program_counter: number } } }>('debugger-paused', (event) => {
+            const pc = event.payload.cpu.registers.program_counter;
+            console.log(`Debugger paused (${event.payload.reason.kind}) at $${pc.toString(16).toUpperCase().padStart(4, '0')}`, event.payload);
+        });
+        return () => {
+            unlisten.then(f => f());
+        };
+    }, []);
 
     useEffect(() => {
         console.log("Canvas Ref Initialized:", canvasRef.current);
@@ -222,6 +236,33 @@ function App() {
                 .catch(console.error);
         }
         
+        // Tab held = turbo, P = pause, F = frame advance
+        if (keyCode === 'Tab') {
+            event.preventDefault();
+            if (!event.repeat) {
+                invoke('set_turbo', { enabled: pressed })
+                    .catch(console.error);
+            }
+        }
+        if (keyCode === 'KeyP' && pressed && !event.repeat) {
+            invoke<boolean>('toggle_pause')
+                .then(paused => console.log(paused ? 'Paused' : 'Resumed'))
+                .catch(console.error);
+        }
+        if (keyCode === 'KeyF' && pressed) {
+            invoke('frame_advance')
+                .catch(console.error);
+        }
+
+        // Backspace held = play backwards (rewind)
+        if (keyCode === 'Backspace') {
+            event.preventDefault();
+            if (!event.repeat) {
+                invoke('rewind_hold', { active: pressed })
+                    .catch(console.error);
+            }
+        }
+
         // Space key to toggle test mode
         if (keyCode === 'Space') {
             if (pressed) {
@@ -324,6 +365,11 @@ function App() {
             
             // Set loading state
             setRomStatus("Loading ROM...");
+
+            // Browsers only allow audio to start from a user gesture
+            if (!audioCtxRef.current) {
+                audioCtxRef.current = new AudioContext();
+            }
             
             // Load the ROM
             await invoke('load_rom', { filePath: selected });